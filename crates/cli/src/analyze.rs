@@ -0,0 +1,35 @@
+use std::{fs, process};
+
+use mandoline_gcode::analyze;
+
+use crate::{args, config};
+
+pub struct AnalyzeCommand {
+    args: args::AnalyzeArgs,
+}
+
+impl AnalyzeCommand {
+    pub fn from_args(args: args::AnalyzeArgs) -> Self {
+        Self { args }
+    }
+
+    pub fn run(self) {
+        let config = config::load_config(&self.args.config);
+        let path = &self.args.gcode_path;
+        let text = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: failed to analyze {}: {}", path, e);
+            process::exit(1);
+        });
+        let analysis = analyze(&text, &config);
+        for warning in &analysis.warnings {
+            eprintln!("warning: skipped {}", warning);
+        }
+        print!("{}", analysis);
+    }
+}
+
+impl args::Subcommand<args::AnalyzeArgs> for AnalyzeCommand {
+    fn run_command(args: args::AnalyzeArgs) {
+        Self::from_args(args).run()
+    }
+}
@@ -1,3 +1,21 @@
+/// Where the slicer settings are read from. Without any of these the
+/// defaults are used.
+#[derive(clap::Args, Debug, Default)]
+pub struct ConfigArgs {
+    /// A TOML or JSON file of slicer settings.
+    #[arg(short, long, conflicts_with = "profiles")]
+    pub config: Option<String>,
+
+    /// A directory of printer, filament and print profiles.
+    #[arg(long, requires = "profiles")]
+    pub profile_dir: Option<String>,
+
+    /// The name of a profile in the profile directory. Can be given once
+    /// for each kind of profile.
+    #[arg(short, long = "profile", requires = "profile_dir")]
+    pub profiles: Vec<String>,
+}
+
 #[derive(clap::Args, Debug)]
 pub struct SvgArgs {
     /// Output path for svg files.
@@ -14,8 +32,55 @@ pub struct SvgArgs {
     #[arg(short, long)]
     pub frame_width: Option<usize>,
 
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
     pub stl_path: String,
 }
+#[derive(clap::Args, Debug)]
+pub struct GcodeArgs {
+    /// Output path for the gcode file.
+    #[arg(short, long)]
+    pub output: String,
+
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    /// A TOML or JSON file listing several objects to print together, with
+    /// where to place them and any settings that differ for each.
+    #[arg(long, conflicts_with = "stl_path")]
+    pub plate: Option<String>,
+
+    /// Places the objects on the plate automatically, ignoring their
+    /// offsets, and prints any copies of them.
+    #[arg(long, conflicts_with = "stl_path")]
+    pub arrange: bool,
+
+    #[arg(required_unless_present = "plate")]
+    pub stl_path: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Output path for the converted settings. The profiles in a config
+    /// bundle are written to this directory, one file per profile.
+    #[arg(short, long)]
+    pub output: String,
+
+    /// A PrusaSlicer or SuperSlicer .ini file, or a Cura .def.json file.
+    pub path: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeArgs {
+    /// The printer's settings, which are used to estimate print times.
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    /// The G-code file to analyze.
+    pub gcode_path: String,
+}
+
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
@@ -26,6 +91,12 @@ pub struct Args {
 #[derive(Debug, clap::Subcommand)]
 pub enum Commands {
     Svg(SvgArgs),
+    Gcode(GcodeArgs),
+    /// Convert another slicer's settings into mandoline settings.
+    Import(ImportArgs),
+    /// Report on the layers, size, filament use and print time of a G-code
+    /// file.
+    Analyze(AnalyzeArgs),
 }
 
 pub trait Subcommand<T: clap::Args> {
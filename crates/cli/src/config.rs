@@ -0,0 +1,25 @@
+use std::process;
+
+use mandoline::{ConfigError, ProfileLibrary, SlicerConfig};
+
+use crate::args;
+
+fn try_load_config(args: &args::ConfigArgs) -> Result<SlicerConfig, ConfigError> {
+    if let Some(path) = &args.config {
+        SlicerConfig::from_file(path)
+    } else if let Some(dir) = &args.profile_dir {
+        let names = args.profiles.iter().map(String::as_str).collect::<Vec<_>>();
+        ProfileLibrary::from_dir(dir)?.config(&names)
+    } else {
+        Ok(SlicerConfig::default())
+    }
+}
+
+/// Loads the slicer settings chosen by `args`, exiting with an error message
+/// if they can't be loaded.
+pub fn load_config(args: &args::ConfigArgs) -> SlicerConfig {
+    try_load_config(args).unwrap_or_else(|e| {
+        eprintln!("error: failed to load settings: {}", e);
+        process::exit(1);
+    })
+}
@@ -0,0 +1,146 @@
+use std::{fs, process};
+
+use mandoline::{
+    footprint, generate_gcode, generate_plate_toolpaths, generate_toolpaths, object_config,
+    slice_mesh, slice_plate, ArrangeItem, PlateFile, PlateFileObject, PlateObject, SlicerConfig,
+    ToolpathLayer, Transform,
+};
+use mandoline_mesh::DefaultMesh;
+
+use crate::{args, config};
+
+pub struct GcodeCommand {
+    args: args::GcodeArgs,
+}
+
+fn read_mesh(object: &PlateFileObject) -> Result<DefaultMesh, String> {
+    mandoline_stl::read_stl::<DefaultMesh, _>(&object.path)
+        .map_err(|e| format!("{}: {}", object.path.display(), e))
+}
+
+fn plate_object(
+    object: &PlateFileObject,
+    mesh: DefaultMesh,
+    transform: Transform,
+    config: &SlicerConfig,
+) -> Result<PlateObject<DefaultMesh>, String> {
+    let config = object_config(config, &object.overrides)
+        .map_err(|e| format!("{}: {}", object.path.display(), e))?;
+    Ok(PlateObject {
+        mesh,
+        transform,
+        config,
+    })
+}
+
+/// Loads the objects listed in the plate file at `path`, each with its own
+/// settings. If `arrange` is set, the objects and their copies are placed
+/// on the bed automatically.
+fn load_plate(
+    path: &str,
+    config: &SlicerConfig,
+    arrange: bool,
+) -> Result<Vec<PlateObject<DefaultMesh>>, String> {
+    let plate = PlateFile::from_file(path).map_err(|e| e.to_string())?;
+    // Each file is only read once, however many copies of it are printed.
+    let meshes = plate
+        .objects
+        .iter()
+        .map(read_mesh)
+        .collect::<Result<Vec<_>, String>>()?;
+    if !arrange {
+        if let Some(object) = plate.objects.iter().find(|o| o.copies.unwrap_or(1) > 1) {
+            return Err(format!(
+                "{}: copies can only be printed with --arrange",
+                object.path.display()
+            ));
+        }
+        return plate
+            .objects
+            .iter()
+            .zip(meshes)
+            .map(|(object, mesh)| plate_object(object, mesh, object.transform, config))
+            .collect();
+    }
+
+    let items = plate
+        .objects
+        .iter()
+        .zip(&meshes)
+        .map(|(object, mesh)| {
+            // The footprint is taken with the object scaled and turned the
+            // way the plate file asks, but not moved.
+            let transform = Transform {
+                offset: (0.0, 0.0),
+                ..object.transform
+            };
+            ArrangeItem {
+                footprint: footprint(&transform.apply_mesh(mesh)),
+                copies: object.copies.unwrap_or(1),
+            }
+        })
+        .collect::<Vec<_>>();
+    let placements = mandoline::arrange(&items, config).map_err(|e| {
+        let path = plate.objects[e.item].path.display();
+        format!("{}: there's no room for it on the bed", path)
+    })?;
+    placements
+        .iter()
+        .map(|placement| {
+            let object = &plate.objects[placement.item];
+            let transform = Transform {
+                offset: placement.transform.offset,
+                rotation: object.transform.rotation + placement.transform.rotation,
+                scale: object.transform.scale,
+            };
+            plate_object(object, meshes[placement.item].clone(), transform, config)
+        })
+        .collect()
+}
+
+impl GcodeCommand {
+    pub fn from_args(args: args::GcodeArgs) -> Self {
+        Self { args }
+    }
+
+    fn toolpaths(&self, config: &SlicerConfig) -> Vec<ToolpathLayer> {
+        if let Some(path) = &self.args.plate {
+            let objects = load_plate(path, config, self.args.arrange).unwrap_or_else(|e| {
+                eprintln!("error: failed to load plate {}: {}", path, e);
+                process::exit(1);
+            });
+            let sliced = slice_plate(objects, config).unwrap_or_else(|e| {
+                eprintln!("error: can't print plate {}: {}", path, e);
+                process::exit(1);
+            });
+            return generate_plate_toolpaths(&sliced, config).unwrap_or_else(|e| {
+                eprintln!("error: can't print plate {}: {}", path, e);
+                process::exit(1);
+            });
+        }
+        // clap requires an STL when there's no plate.
+        let stl_path = self.args.stl_path.as_ref().unwrap();
+        let mesh = mandoline_stl::read_stl::<DefaultMesh, _>(stl_path).unwrap_or_else(|e| {
+            eprintln!("error: failed to read {}: {}", stl_path, e);
+            process::exit(1);
+        });
+        let slices = slice_mesh(mesh, config);
+        generate_toolpaths(&slices, config)
+    }
+
+    pub fn run(self) {
+        let config = config::load_config(&self.args.config);
+        let toolpaths = self.toolpaths(&config);
+        let gcode = generate_gcode(&toolpaths, &config);
+        if let Err(e) = fs::write(&self.args.output, gcode) {
+            eprintln!("error: failed to write {}: {}", self.args.output, e);
+            process::exit(1);
+        }
+    }
+}
+
+impl args::Subcommand<args::GcodeArgs> for GcodeCommand {
+    fn run_command(args: args::GcodeArgs) {
+        Self::from_args(args).run()
+    }
+}
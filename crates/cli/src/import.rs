@@ -0,0 +1,60 @@
+use std::{fs, path::Path, process};
+
+use mandoline::{import_config, ConfigError, Profile};
+
+use crate::args;
+
+pub struct ImportCommand {
+    args: args::ImportArgs,
+}
+
+/// Returns a file name for a profile. Names can contain any characters, so
+/// anything that might not be allowed in a file name is replaced.
+fn profile_file_name(profile: &Profile) -> String {
+    let name = profile
+        .name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    format!("{}-{}.toml", profile.kind, name)
+}
+
+impl ImportCommand {
+    pub fn from_args(args: args::ImportArgs) -> Self {
+        Self { args }
+    }
+
+    fn try_run(&self) -> Result<(), ConfigError> {
+        let text = fs::read_to_string(&self.args.path)?;
+        let import = import_config(&text)?;
+        for setting in &import.unmapped {
+            eprintln!("warning: not imported: {}", setting);
+        }
+        if import.profiles.is_empty() {
+            fs::write(&self.args.output, import.config()?.to_toml())?;
+        } else {
+            let dir = Path::new(&self.args.output);
+            fs::create_dir_all(dir)?;
+            for profile in &import.profiles {
+                fs::write(dir.join(profile_file_name(profile)), profile.to_toml())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run(self) {
+        if let Err(e) = self.try_run() {
+            eprintln!("error: failed to import {}: {}", self.args.path, e);
+            process::exit(1);
+        }
+    }
+}
+
+impl args::Subcommand<args::ImportArgs> for ImportCommand {
+    fn run_command(args: args::ImportArgs) {
+        Self::from_args(args).run()
+    }
+}
@@ -1,12 +1,19 @@
 use crate::args::Subcommand;
 use clap::Parser;
 
+mod analyze;
 mod args;
+mod config;
+mod gcode;
+mod import;
 mod svg;
 
 fn main() {
     let args = args::Args::parse();
     match args.command {
         args::Commands::Svg(svg) => svg::SvgCommand::run_command(svg),
+        args::Commands::Gcode(gcode) => gcode::GcodeCommand::run_command(gcode),
+        args::Commands::Import(import) => import::ImportCommand::run_command(import),
+        args::Commands::Analyze(analyze) => analyze::AnalyzeCommand::run_command(analyze),
     }
 }
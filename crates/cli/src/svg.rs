@@ -1,9 +1,9 @@
 use std::{fs::File, io::Write};
 
-use mandoline::{slice_mesh, Contour, SlicerConfig};
+use mandoline::{slice_mesh, Contour};
 use mandoline_mesh::DefaultMesh;
 
-use crate::args;
+use crate::{args, config};
 
 const DEFAULT_SVG_MARGIN: usize = 15;
 const DEFAULT_SVG_CONTENT_WIDTH: usize = 100;
@@ -64,7 +64,7 @@ impl SvgCommand {
     }
 
     pub fn run(mut self) {
-        let config = SlicerConfig { layer_height: 0.2 };
+        let config = config::load_config(&self.args.config);
         let mesh = mandoline_stl::read_stl::<DefaultMesh, _>(&self.args.stl_path).unwrap();
         let slices = slice_mesh(mesh, &config);
 
@@ -80,7 +80,7 @@ impl SvgCommand {
             .args
             .layer
             .map(|l| l..l + 1)
-            .unwrap_or(0..slices.contours().len());
+            .unwrap_or(0..slices.layers().len());
         if let SvgMode::Animated { state: Some(vec) } = &mut self.mode {
             vec.resize(layers.len(), "hidden");
         }
@@ -88,7 +88,7 @@ impl SvgCommand {
         let mut f = File::create(&self.args.output).unwrap();
         write_svg_header(&mut f);
         for layer in layers {
-            self.generate_layer_paths(&mut f, layer, &slices.contours()[layer]);
+            self.generate_layer_paths(&mut f, layer, slices.layers()[layer].contour());
         }
         write_svg_footer(&mut f);
     }
@@ -215,6 +215,7 @@ mod tests {
             stl_path: input.path().to_str().map(|s| s.to_owned()).unwrap(),
             grid: false,
             frame_width: None,
+            config: Default::default(),
         };
 
         // When - Execute the command
@@ -244,7 +245,6 @@ mod tests {
                 |evt| matches!(evt, svg::parser::Event::Comment(c) if c.starts_with("<!-- Layer")),
             )
             .collect::<Vec<_>>();
-        // TODO: this should be 100.
-        assert_eq!(101, layer_comments.len());
+        assert_eq!(100, layer_comments.len());
     }
 }
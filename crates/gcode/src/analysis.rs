@@ -0,0 +1,448 @@
+use std::fmt;
+
+use cgmath::Vector2;
+use mandoline::{
+    format_duration, FeatureType, PathMove, PrintEstimate, PrintEstimator, SlicerConfig,
+    ARC_SEGMENT_LENGTH,
+};
+
+use crate::parser::{parse, CommandLetter, Instruction, Line, ParseError};
+use crate::state::{MachineState, Motion};
+
+/// Returns the feature named by a `;TYPE:` comment, as written by mandoline,
+/// Cura or PrusaSlicer.
+fn feature_type(name: &str) -> Option<FeatureType> {
+    Some(match name {
+        "ExternalPerimeter" | "WALL-OUTER" | "External perimeter" | "Overhang perimeter" => {
+            FeatureType::ExternalPerimeter
+        }
+        "Perimeter" | "WALL-INNER" => FeatureType::Perimeter,
+        "SolidInfill" | "SKIN" | "Solid infill" | "Top solid infill" => FeatureType::SolidInfill,
+        "SparseInfill" | "FILL" | "Internal infill" => FeatureType::SparseInfill,
+        "Bridge" | "Bridge infill" => FeatureType::Bridge,
+        "Support" | "SUPPORT" | "Support material" => FeatureType::Support,
+        "SupportInterface" | "SUPPORT-INTERFACE" | "Support material interface" => {
+            FeatureType::SupportInterface
+        }
+        "Skirt" | "SKIRT" | "Skirt/Brim" => FeatureType::Skirt,
+        "Brim" => FeatureType::Brim,
+        "RaftBase" => FeatureType::RaftBase,
+        "RaftInterface" => FeatureType::RaftInterface,
+        _ => return None,
+    })
+}
+
+/// An axis aligned box, in mm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Bounds {
+    fn include(bounds: &mut Option<Bounds>, point: [f64; 3]) {
+        let b = bounds.get_or_insert(Bounds {
+            min: point,
+            max: point,
+        });
+        for ((min, max), p) in b.min.iter_mut().zip(&mut b.max).zip(point) {
+            *min = min.min(p);
+            *max = max.max(p);
+        }
+    }
+
+    /// The size of the box along each axis.
+    pub fn size(&self) -> [f64; 3] {
+        [0, 1, 2].map(|axis| self.max[axis] - self.min[axis])
+    }
+}
+
+/// What was printed on a single layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerAnalysis {
+    /// The height of the layer's first extrusion, in mm.
+    pub z: f64,
+    /// The length of filament extruded on the layer, in mm.
+    pub extrusion: f64,
+    /// The estimated time the layer takes to print, in seconds.
+    pub time: f32,
+}
+
+/// A report on a G-code program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub layers: Vec<LayerAnalysis>,
+    /// The box around every extrusion, or `None` if nothing is extruded.
+    pub bounds: Option<Bounds>,
+    /// The number of times filament is retracted.
+    pub retractions: usize,
+    /// The estimated print time and filament use.
+    pub estimate: PrintEstimate,
+    /// The lines that couldn't be parsed, and were skipped.
+    pub warnings: Vec<ParseError>,
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "layers: {}", self.layers.len())?;
+        if let Some(bounds) = &self.bounds {
+            let [x, y, z] = bounds.size();
+            let point = |p: [f64; 3]| format!("({:.2}, {:.2}, {:.2})", p[0], p[1], p[2]);
+            writeln!(
+                f,
+                "size: {:.2} x {:.2} x {:.2} mm, from {} to {}",
+                x,
+                y,
+                z,
+                point(bounds.min),
+                point(bounds.max)
+            )?;
+        }
+        let estimate = &self.estimate;
+        writeln!(
+            f,
+            "filament used: {:.2} mm, {:.2} g",
+            estimate.filament_length, estimate.filament_weight
+        )?;
+        writeln!(f, "retractions: {}", self.retractions)?;
+        if !self.warnings.is_empty() {
+            writeln!(f, "skipped lines: {}", self.warnings.len())?;
+        }
+        writeln!(f, "estimated time: {}", format_duration(estimate.time))?;
+        for (feature, time) in &estimate.feature_times {
+            writeln!(f, "  {}: {}", feature, format_duration(*time))?;
+        }
+        writeln!(f, "  travel: {}", format_duration(estimate.travel_time))?;
+        writeln!(
+            f,
+            "{:>5} {:>8} {:>14} {:>8}",
+            "layer", "z", "filament [mm]", "time [s]"
+        )?;
+        for (i, layer) in self.layers.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>5} {:>8.3} {:>14.2} {:>8.1}",
+                i, layer.z, layer.extrusion, layer.time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows a G-code program line by line, building up an `Analysis`.
+pub struct Analyzer<'a> {
+    config: &'a SlicerConfig,
+    state: MachineState,
+    estimator: PrintEstimator<'a>,
+    layers: Vec<LayerAnalysis>,
+    /// True once a layer change comment has been seen. From then on only
+    /// these comments start new layers, rather than changes in Z.
+    layer_comments: bool,
+    /// True if the current layer has extruded anything.
+    layer_extruded: bool,
+    /// The feature given by the last `;TYPE:` comment.
+    feature: Option<FeatureType>,
+    bounds: Option<Bounds>,
+    retractions: usize,
+    retracted: bool,
+    warnings: Vec<ParseError>,
+}
+
+impl<'a> Analyzer<'a> {
+    /// Creates an analyzer that estimates print times for the printer
+    /// described by `config`.
+    pub fn new(config: &'a SlicerConfig) -> Self {
+        Self {
+            config,
+            state: MachineState::new(),
+            estimator: PrintEstimator::new(config),
+            layers: Vec::new(),
+            layer_comments: false,
+            layer_extruded: false,
+            feature: None,
+            bounds: None,
+            retractions: 0,
+            retracted: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The state of the printer after the lines added so far.
+    pub fn state(&self) -> &MachineState {
+        &self.state
+    }
+
+    fn start_layer(&mut self) {
+        self.estimator.set_layer(self.layers.len());
+        self.layers.push(LayerAnalysis {
+            z: self.state.position.z,
+            extrusion: 0.0,
+            time: 0.0,
+        });
+        self.layer_extruded = false;
+    }
+
+    fn add_comment(&mut self, comment: &str) {
+        if let Some(name) = comment.strip_prefix("TYPE:") {
+            self.feature = feature_type(name.trim());
+        } else if comment.starts_with("LAYER:") || comment == "LAYER_CHANGE" {
+            self.layer_comments = true;
+            self.start_layer();
+        }
+    }
+
+    fn set_retracted(&mut self, retracted: bool) {
+        if retracted && !self.retracted {
+            self.retractions += 1;
+        }
+        self.retracted = retracted;
+    }
+
+    /// Handles G10 and G11, where the firmware retracts by its own settings.
+    /// These are assumed to match the configured retraction.
+    fn firmware_retraction(&mut self, retract: bool) {
+        let retraction = &self.config.retraction;
+        let length = if retract {
+            -retraction.length
+        } else {
+            retraction.length
+        };
+        self.estimator
+            .add_move([0.0, 0.0, 0.0, length], retraction.speed, None);
+        self.set_retracted(retract);
+    }
+
+    fn add_motion(&mut self, motion: &Motion) {
+        let extrusion = motion.extrusion();
+        if extrusion != 0.0 {
+            self.set_retracted(extrusion < 0.0);
+        }
+        let extruding = motion.is_extruding();
+        if extruding {
+            let new_z = self
+                .layers
+                .last()
+                .is_none_or(|layer| (layer.z - motion.to.z).abs() > 1e-6);
+            if self.layers.is_empty() || (!self.layer_comments && new_z) {
+                self.start_layer();
+            }
+            let layer = self.layers.last_mut().unwrap();
+            if !self.layer_extruded {
+                layer.z = motion.to.z;
+                self.layer_extruded = true;
+            }
+            layer.extrusion += extrusion;
+        }
+
+        let state = &self.state;
+        let acceleration = if extruding {
+            state.print_acceleration
+        } else {
+            state.travel_acceleration
+        };
+        self.estimator.set_acceleration(
+            acceleration.map_or(self.config.machine_limits.acceleration, |a| a as f32),
+        );
+        let feature = if extruding { self.feature } else { None };
+
+        // Arcs are planned as a series of straight moves, like firmware
+        // does.
+        let from = Vector2::new(motion.from.x as f32, motion.from.y as f32);
+        let end = Vector2::new(motion.to.x as f32, motion.to.y as f32);
+        let points = match motion.arc {
+            Some(center) => PathMove::Arc {
+                end,
+                center: Vector2::new(center.x as f32, center.y as f32),
+                clockwise: center.clockwise,
+            }
+            .points(from, ARC_SEGMENT_LENGTH),
+            None => vec![end],
+        };
+        // The points along an arc are evenly spaced.
+        let share = 1.0 / points.len() as f32;
+        let dz = (motion.to.z - motion.from.z) as f32;
+        let mut start = from;
+        let mut z = motion.from.z;
+        for point in points {
+            self.estimator.add_move(
+                [
+                    point.x - start.x,
+                    point.y - start.y,
+                    dz * share,
+                    extrusion as f32 * share,
+                ],
+                motion.feedrate as f32,
+                feature,
+            );
+            if extruding {
+                Bounds::include(&mut self.bounds, [start.x as f64, start.y as f64, z]);
+                z += (dz * share) as f64;
+                Bounds::include(&mut self.bounds, [point.x as f64, point.y as f64, z]);
+            }
+            start = point;
+        }
+    }
+
+    /// Adds the next line of the program.
+    pub fn add_line(&mut self, line: &Line) {
+        if let Some(comment) = &line.comment {
+            self.add_comment(comment);
+        }
+        let Some(instruction) = &line.instruction else {
+            return;
+        };
+        if let Instruction::Command(command) = instruction {
+            // G10 with a tool number sets temperatures instead.
+            if command.is(CommandLetter::G, 10) && !command.has_param('P') {
+                self.firmware_retraction(true);
+                return;
+            }
+            if command.is(CommandLetter::G, 11) {
+                self.firmware_retraction(false);
+                return;
+            }
+        }
+        if let Some(motion) = self.state.apply(instruction) {
+            self.add_motion(&motion);
+        }
+    }
+
+    /// Records a line that couldn't be parsed. The analysis carries on as if
+    /// the line wasn't there.
+    pub fn skip_line(&mut self, error: ParseError) {
+        self.warnings.push(error);
+    }
+
+    /// Plans the moves of the program, and returns the finished report.
+    pub fn finish(self) -> Analysis {
+        let estimate = self.estimator.estimate();
+        let mut layers = self.layers;
+        for (layer, &time) in layers.iter_mut().zip(&estimate.layer_times) {
+            layer.time = time;
+        }
+        Analysis {
+            layers,
+            bounds: self.bounds,
+            retractions: self.retractions,
+            estimate,
+            warnings: self.warnings,
+        }
+    }
+}
+
+/// Parses and analyzes a whole G-code program, estimating times for the
+/// printer described by `config`. Lines that can't be parsed, such as
+/// firmware macros, are skipped and listed in `Analysis::warnings`.
+pub fn analyze(text: &str, config: &SlicerConfig) -> Analysis {
+    let mut analyzer = Analyzer::new(config);
+    for line in parse(text) {
+        match line {
+            Ok(line) => analyzer.add_line(&line),
+            Err(error) => analyzer.skip_line(error),
+        }
+    }
+    analyzer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn layers_from_z() {
+        // Two layers of a 10mm square, with a retraction and Z-hop between
+        // them.
+        let gcode = "G28\n\
+             G1 Z0.2 F600\n\
+             G0 X10 Y10 F6000\n\
+             ;TYPE:WALL-OUTER\n\
+             G1 X20 E1 F1200\n\
+             G1 Y20 E2\n\
+             G1 X10 E3\n\
+             G1 Y10 E4\n\
+             G1 E3.2 F2100\n\
+             G1 Z0.6\n\
+             G1 Z0.4\n\
+             G1 E4\n\
+             G1 X20 E5\n\
+             G3 X20 Y20 I0 J5 E6";
+
+        let analysis = analyze(gcode, &SlicerConfig::default());
+
+        assert_eq!(analysis.layers.len(), 2);
+        assert_float_eq!(analysis.layers[0].z, 0.2, abs <= 1e-9);
+        assert_float_eq!(analysis.layers[0].extrusion, 4.0, abs <= 1e-9);
+        assert_float_eq!(analysis.layers[1].z, 0.4, abs <= 1e-9);
+        assert_float_eq!(analysis.layers[1].extrusion, 2.0, abs <= 1e-9);
+        assert_eq!(analysis.retractions, 1);
+        // The arc bulges out to X = 25.
+        let bounds = analysis.bounds.unwrap();
+        assert_float_eq!(bounds.min[0], 10.0, abs <= 1e-4);
+        assert_float_eq!(bounds.max[0], 25.0, abs <= 1e-3);
+        assert_float_eq!(bounds.max[2], 0.4, abs <= 1e-6);
+        assert_float_eq!(analysis.estimate.filament_length, 6.0, abs <= 1e-4);
+        assert_eq!(
+            analysis.estimate.feature_times[0].0,
+            FeatureType::ExternalPerimeter
+        );
+        let layer_time = analysis.layers.iter().map(|l| l.time).sum::<f32>();
+        assert!(layer_time > 0.0 && layer_time < analysis.estimate.time);
+    }
+
+    #[test]
+    fn layers_from_comments() {
+        // Vase mode style G-code, where Z rises on every move.
+        let gcode = "M83\n\
+             ;LAYER:0\n\
+             G1 X10 Z0.2 E1\n\
+             G1 X0 Z0.25 E1\n\
+             ;LAYER:1\n\
+             G1 X10 Z0.3 E1\n\
+             G10\n\
+             G0 X20\n\
+             G11\n\
+             G1 X10 Z0.35 E1";
+
+        let analysis = analyze(gcode, &SlicerConfig::default());
+
+        assert_eq!(analysis.layers.len(), 2);
+        assert_float_eq!(analysis.layers[1].z, 0.3, abs <= 1e-9);
+        assert_float_eq!(analysis.layers[1].extrusion, 2.0, abs <= 1e-9);
+        assert_eq!(analysis.retractions, 1);
+    }
+
+    #[test]
+    fn skips_unknown_lines() {
+        let gcode = "M83\n\
+             G1 X10 Z0.2 E1\n\
+             BAD_MACRO VALUE\n\
+             G1 X0 E1";
+
+        let analysis = analyze(gcode, &SlicerConfig::default());
+
+        assert_eq!(analysis.warnings.len(), 1);
+        assert_eq!(analysis.warnings[0].line, 3);
+        assert_eq!(analysis.layers.len(), 1);
+        assert_float_eq!(analysis.layers[0].extrusion, 2.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn reads_own_feature_names() {
+        for feature in [
+            FeatureType::ExternalPerimeter,
+            FeatureType::Perimeter,
+            FeatureType::SolidInfill,
+            FeatureType::SparseInfill,
+            FeatureType::Bridge,
+            FeatureType::Support,
+            FeatureType::SupportInterface,
+            FeatureType::Skirt,
+            FeatureType::Brim,
+            FeatureType::RaftBase,
+            FeatureType::RaftInterface,
+        ] {
+            assert_eq!(feature_type(feature.name()), Some(feature));
+        }
+    }
+}
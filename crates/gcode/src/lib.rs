@@ -0,0 +1,7 @@
+mod analysis;
+mod parser;
+mod state;
+
+pub use analysis::*;
+pub use parser::*;
+pub use state::*;
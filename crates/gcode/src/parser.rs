@@ -0,0 +1,371 @@
+use std::fmt;
+
+/// The letter a numbered command starts with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandLetter {
+    /// Motion and mode commands.
+    G,
+    /// Machine commands, such as setting temperatures.
+    M,
+    /// Tool changes.
+    T,
+}
+
+/// A parameter of a numbered command, such as `X10`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Param {
+    /// The parameter's letter, in upper case.
+    pub letter: char,
+    /// The parameter's value. Parameters can be given without a value, such
+    /// as the axes in `G28 X Y`.
+    pub value: Option<f64>,
+}
+
+/// A numbered command, such as `G1 X10 Y20 E0.5`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub letter: CommandLetter,
+    pub number: u32,
+    /// The number after a decimal point, such as the 1 in `G29.1`.
+    pub subcode: Option<u32>,
+    pub params: Vec<Param>,
+    /// The argument of commands that take text rather than parameters, such
+    /// as the message in `M117 Printing...`.
+    pub text: Option<String>,
+}
+
+/// Commands that take text, rather than parameters.
+const TEXT_COMMANDS: &[u32] = &[23, 28, 30, 32, 117, 118, 928];
+
+impl Command {
+    /// Returns true if this is the command `letter` `number`, with any
+    /// subcode.
+    pub fn is(&self, letter: CommandLetter, number: u32) -> bool {
+        self.letter == letter && self.number == number
+    }
+
+    /// Returns true if the command has a parameter with `letter`.
+    pub fn has_param(&self, letter: char) -> bool {
+        self.params.iter().any(|p| p.letter == letter)
+    }
+
+    /// Returns the value of the parameter with `letter`, if it has one.
+    pub fn param(&self, letter: char) -> Option<f64> {
+        self.params
+            .iter()
+            .find(|p| p.letter == letter)
+            .and_then(|p| p.value)
+    }
+}
+
+/// A Klipper style command, such as `SET_VELOCITY_LIMIT ACCEL=1000`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendedCommand {
+    /// The command's name, in upper case.
+    pub name: String,
+    /// The command's `NAME=value` parameters. Names are in upper case.
+    pub params: Vec<(String, String)>,
+}
+
+impl ExtendedCommand {
+    /// Returns the value of the parameter called `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The command on a line of G-code.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Command(Command),
+    Extended(ExtendedCommand),
+}
+
+/// A line of G-code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Line {
+    /// The line's command, or `None` for blank lines and lines that are
+    /// only comments.
+    pub instruction: Option<Instruction>,
+    /// The text of the line's comments, without the `;` or brackets.
+    pub comment: Option<String>,
+}
+
+/// A line of G-code that couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The number of the line, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Splits a line into its code and its comments. Comments run from `;` to
+/// the end of the line, or are wrapped in brackets.
+fn split_comments(text: &str) -> (String, Option<String>) {
+    let mut code = String::new();
+    let mut comments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find([';', '(']) {
+        code.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if rest[start..].starts_with(';') {
+            comments.push(after.trim());
+            rest = "";
+            break;
+        }
+        let end = after.find(')').unwrap_or(after.len());
+        comments.push(after[..end].trim());
+        rest = after.get(end + 1..).unwrap_or("");
+    }
+    code.push_str(rest);
+    let comment = (!comments.is_empty()).then(|| comments.join(" "));
+    (code, comment)
+}
+
+/// Reads a number from the start of `text`, returning it and the rest of
+/// the text.
+///
+/// Exponents aren't allowed, since `E` is a parameter: `X1E5` is `X1` then
+/// `E5`.
+fn take_number(text: &str) -> Option<(f64, &str)> {
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(text.len(), |(i, _)| i);
+    let value = text[..end].parse().ok()?;
+    Some((value, &text[end..]))
+}
+
+fn parse_params(mut text: &str) -> Result<Vec<Param>, String> {
+    let mut params = Vec::new();
+    loop {
+        text = text.trim_start();
+        let Some(c) = text.chars().next() else {
+            return Ok(params);
+        };
+        if !c.is_ascii_alphabetic() {
+            return Err(format!("expected a parameter, found '{}'", c));
+        }
+        let letter = c.to_ascii_uppercase();
+        text = &text[1..];
+        let value = if let Some(quoted) = text.strip_prefix('"') {
+            // RepRapFirmware quotes file names and messages. These aren't
+            // numbers, so only the letter is kept.
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| "unterminated string".to_string())?;
+            text = &quoted[end + 1..];
+            None
+        } else if let Some((value, rest)) = take_number(text) {
+            text = rest;
+            Some(value)
+        } else {
+            None
+        };
+        params.push(Param { letter, value });
+    }
+}
+
+fn parse_extended(code: &str) -> Result<ExtendedCommand, String> {
+    let mut words = code.split_whitespace();
+    let name = words.next().unwrap_or_default().to_ascii_uppercase();
+    let params = words
+        .map(|word| {
+            word.split_once('=')
+                .map(|(name, value)| (name.to_ascii_uppercase(), value.to_string()))
+                .ok_or_else(|| format!("expected NAME=value, found '{}'", word))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(ExtendedCommand { name, params })
+}
+
+fn parse_command(code: &str) -> Result<Instruction, String> {
+    let mut chars = code.chars();
+    let letter = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('G') => CommandLetter::G,
+        Some('M') => CommandLetter::M,
+        Some('T') => CommandLetter::T,
+        _ => return parse_extended(code).map(Instruction::Extended),
+    };
+    let rest = chars.as_str();
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    if digits == 0 {
+        // Words like `SET_FAN_SPEED` start with a command letter too.
+        return parse_extended(code).map(Instruction::Extended);
+    }
+    let number = rest[..digits]
+        .parse()
+        .map_err(|_| format!("invalid command number '{}'", &rest[..digits]))?;
+    let mut rest = &rest[digits..];
+    let mut subcode = None;
+    if let Some(after) = rest.strip_prefix('.') {
+        let digits = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        subcode = Some(
+            after[..digits]
+                .parse()
+                .map_err(|_| "invalid subcode".to_string())?,
+        );
+        rest = &after[digits..];
+    }
+    // Parameters can follow the command without a space, as in `G1X10`.
+    if !(rest.is_empty()
+        || rest.starts_with(|c: char| c.is_ascii_alphabetic() || c.is_whitespace()))
+    {
+        return parse_extended(code).map(Instruction::Extended);
+    }
+    let (params, text) = if letter == CommandLetter::M && TEXT_COMMANDS.contains(&number) {
+        (Vec::new(), Some(rest.trim().to_string()))
+    } else {
+        (parse_params(rest)?, None)
+    };
+    Ok(Instruction::Command(Command {
+        letter,
+        number,
+        subcode,
+        params,
+        text,
+    }))
+}
+
+/// Removes a line number (`N123`) from the start of `code` and a checksum
+/// (`*45`) from the end, which are only used for serial communication.
+fn strip_line_number(code: &str) -> &str {
+    let code = code.trim();
+    let code = match code.split_once('*') {
+        Some((code, checksum)) if checksum.trim().chars().all(|c| c.is_ascii_digit()) => code,
+        _ => code,
+    };
+    let line_number = code
+        .strip_prefix(['N', 'n'])
+        .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()));
+    match line_number {
+        Some(rest) if rest.len() < code.len() - 1 => rest.trim(),
+        _ => code.trim(),
+    }
+}
+
+impl Line {
+    /// Parses a single line of G-code. Errors give the line number as 1.
+    pub fn parse(text: &str) -> Result<Line, ParseError> {
+        let (code, comment) = split_comments(text);
+        let code = strip_line_number(&code);
+        let instruction = if code.is_empty() {
+            None
+        } else {
+            Some(parse_command(code).map_err(|message| ParseError { line: 1, message })?)
+        };
+        Ok(Line {
+            instruction,
+            comment,
+        })
+    }
+
+    /// Returns the line's numbered command, if it has one.
+    pub fn command(&self) -> Option<&Command> {
+        match &self.instruction {
+            Some(Instruction::Command(command)) => Some(command),
+            _ => None,
+        }
+    }
+}
+
+/// Parses each line of a G-code program.
+pub fn parse(text: &str) -> impl Iterator<Item = Result<Line, ParseError>> + '_ {
+    text.lines().enumerate().map(|(i, text)| {
+        Line::parse(text).map_err(|e| ParseError {
+            line: i + 1,
+            message: e.message,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(text: &str) -> Command {
+        Line::parse(text).unwrap().command().unwrap().clone()
+    }
+
+    #[test]
+    fn commands_and_params() {
+        let g1 = command("G1 X10.5 Y-2 E.25 F3000");
+        let packed = command("g1x10.5y-2e.25f3000");
+        let home = command("N12 G28 X Y*81");
+        let subcode = command("G29.1 Z0.2");
+
+        assert!(g1.is(CommandLetter::G, 1));
+        assert_eq!(g1.param('X'), Some(10.5));
+        assert_eq!(g1.param('Y'), Some(-2.0));
+        assert_eq!(g1.param('E'), Some(0.25));
+        assert_eq!(g1.param('F'), Some(3000.0));
+        assert_eq!(g1.param('Z'), None);
+        assert_eq!(packed, g1);
+        assert!(home.is(CommandLetter::G, 28));
+        assert!(home.has_param('X') && home.has_param('Y'));
+        assert_eq!(home.param('X'), None);
+        assert_eq!(subcode.subcode, Some(1));
+        assert_eq!(command("T1").letter, CommandLetter::T);
+    }
+
+    #[test]
+    fn comments_and_text() {
+        let line = Line::parse("G1 X1 (move) Y2 ; to the corner").unwrap();
+        let comment = Line::parse(";LAYER:3").unwrap();
+        let message = command("M117 Printing; 10%");
+        let blank = Line::parse("   ").unwrap();
+
+        assert_eq!(line.comment.as_deref(), Some("move to the corner"));
+        assert_eq!(line.command().unwrap().param('Y'), Some(2.0));
+        assert_eq!(comment.instruction, None);
+        assert_eq!(comment.comment.as_deref(), Some("LAYER:3"));
+        assert_eq!(message.text.as_deref(), Some("Printing"));
+        assert_eq!(blank, Line::default());
+    }
+
+    #[test]
+    fn extended_commands() {
+        let line = Line::parse("SET_VELOCITY_LIMIT ACCEL=1000 square_corner_velocity=5").unwrap();
+        let macro_call = Line::parse("PRINT_START").unwrap();
+
+        let Some(Instruction::Extended(command)) = line.instruction else {
+            panic!("{:?}", line);
+        };
+        assert_eq!(command.name, "SET_VELOCITY_LIMIT");
+        assert_eq!(command.param("ACCEL"), Some("1000"));
+        assert_eq!(command.param("SQUARE_CORNER_VELOCITY"), Some("5"));
+        assert!(matches!(
+            macro_call.instruction,
+            Some(Instruction::Extended(ExtendedCommand { ref name, .. })) if name == "PRINT_START"
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let errors = parse("G1 X1\nG1 X1 #\nBAD_MACRO VALUE")
+            .filter_map(Result::err)
+            .collect::<Vec<_>>();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(
+            errors[1].to_string(),
+            "line 3: expected NAME=value, found 'VALUE'"
+        );
+    }
+}
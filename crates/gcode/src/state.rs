@@ -0,0 +1,384 @@
+use crate::parser::{Command, CommandLetter, ExtendedCommand, Instruction};
+
+/// Whether coordinates are absolute positions or distances from the current
+/// position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CoordinateMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+/// A position along each axis, in mm.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub e: f64,
+}
+
+/// The centre and direction of an arc move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArcCenter {
+    pub x: f64,
+    pub y: f64,
+    pub clockwise: bool,
+}
+
+/// A move made by a G0, G1, G2 or G3 command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    pub from: Position,
+    pub to: Position,
+    /// The requested speed, in mm/s.
+    pub feedrate: f64,
+    /// The centre of the arc for G2 and G3 moves, or `None` for straight
+    /// moves.
+    pub arc: Option<ArcCenter>,
+}
+
+impl Motion {
+    /// The length of filament fed by the move, in mm. This is negative for
+    /// retractions.
+    pub fn extrusion(&self) -> f64 {
+        self.to.e - self.from.e
+    }
+
+    /// Returns true if the nozzle moves in XY.
+    pub fn is_horizontal(&self) -> bool {
+        self.from.x != self.to.x || self.from.y != self.to.y
+    }
+
+    /// Returns true if the move deposits filament: it moves in XY while
+    /// extruding.
+    pub fn is_extruding(&self) -> bool {
+        self.is_horizontal() && self.extrusion() > 0.0
+    }
+}
+
+/// The state of the printer as it runs through a G-code program.
+///
+/// This follows Marlin's behaviour where firmware differs. Commands that
+/// don't affect the state are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineState {
+    pub position: Position,
+    /// The mode of X, Y and Z coordinates, set with G90 and G91.
+    pub positioning: CoordinateMode,
+    /// The mode of E coordinates, set with M82 and M83. E coordinates are
+    /// also relative while `positioning` is relative.
+    pub extrusion_mode: CoordinateMode,
+    /// True if coordinates are in inches (G20) rather than mm (G21).
+    pub inches: bool,
+    /// The speed of moves, in mm/s.
+    pub feedrate: f64,
+    /// The target nozzle temperature, in °C.
+    pub nozzle_temperature: f64,
+    /// The target bed temperature, in °C.
+    pub bed_temperature: f64,
+    /// The part cooling fan speed, from 0 to 1.
+    pub fan_speed: f64,
+    /// The acceleration of extruding moves set by the G-code, in mm/s².
+    pub print_acceleration: Option<f64>,
+    /// The acceleration of travel moves set by the G-code, in mm/s².
+    pub travel_acceleration: Option<f64>,
+}
+
+impl Default for MachineState {
+    fn default() -> Self {
+        Self {
+            position: Position::default(),
+            positioning: CoordinateMode::default(),
+            extrusion_mode: CoordinateMode::default(),
+            inches: false,
+            // Marlin's default feedrate.
+            feedrate: 50.0,
+            nozzle_temperature: 0.0,
+            bed_temperature: 0.0,
+            fan_speed: 0.0,
+            print_acceleration: None,
+            travel_acceleration: None,
+        }
+    }
+}
+
+impl MachineState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts a coordinate in the current units to mm.
+    fn to_mm(&self, value: f64) -> f64 {
+        if self.inches {
+            value * 25.4
+        } else {
+            value
+        }
+    }
+
+    /// Returns the new value of an axis moved to `value`, which is relative
+    /// to `current` in `mode`.
+    fn axis(&self, current: f64, value: Option<f64>, mode: CoordinateMode) -> f64 {
+        match (value, mode) {
+            (None, _) => current,
+            (Some(v), CoordinateMode::Absolute) => self.to_mm(v),
+            (Some(v), CoordinateMode::Relative) => current + self.to_mm(v),
+        }
+    }
+
+    fn e_mode(&self) -> CoordinateMode {
+        if self.positioning == CoordinateMode::Relative {
+            CoordinateMode::Relative
+        } else {
+            self.extrusion_mode
+        }
+    }
+
+    fn motion(&mut self, command: &Command) -> Motion {
+        if let Some(f) = command.param('F') {
+            // Feedrates are in units per minute.
+            self.feedrate = self.to_mm(f) / 60.0;
+        }
+        let from = self.position;
+        let to = Position {
+            x: self.axis(from.x, command.param('X'), self.positioning),
+            y: self.axis(from.y, command.param('Y'), self.positioning),
+            z: self.axis(from.z, command.param('Z'), self.positioning),
+            e: self.axis(from.e, command.param('E'), self.e_mode()),
+        };
+        let arc = (command.number == 2 || command.number == 3).then(|| {
+            // The centre is always relative to the start of the arc.
+            ArcCenter {
+                x: from.x + self.to_mm(command.param('I').unwrap_or(0.0)),
+                y: from.y + self.to_mm(command.param('J').unwrap_or(0.0)),
+                clockwise: command.number == 2,
+            }
+        });
+        self.position = to;
+        Motion {
+            from,
+            to,
+            feedrate: self.feedrate,
+            arc,
+        }
+    }
+
+    /// Returns the axes of `axes` that `command` has parameters for, or all
+    /// of them if it has none.
+    fn given_axes(command: &Command, axes: &[char]) -> Vec<char> {
+        let given = axes
+            .iter()
+            .copied()
+            .filter(|&a| command.has_param(a))
+            .collect::<Vec<_>>();
+        if given.is_empty() {
+            axes.to_vec()
+        } else {
+            given
+        }
+    }
+
+    fn coordinate(&mut self, axis: char) -> &mut f64 {
+        match axis {
+            'X' => &mut self.position.x,
+            'Y' => &mut self.position.y,
+            'Z' => &mut self.position.z,
+            _ => &mut self.position.e,
+        }
+    }
+
+    fn apply_command(&mut self, command: &Command) -> Option<Motion> {
+        match (command.letter, command.number) {
+            (CommandLetter::G, 0..=3) => return Some(self.motion(command)),
+            (CommandLetter::G, 10) if command.has_param('P') => {
+                // RepRapFirmware sets tool temperatures with G10.
+                if let Some(s) = command.param('S') {
+                    self.nozzle_temperature = s;
+                }
+            }
+            (CommandLetter::G, 20) => self.inches = true,
+            (CommandLetter::G, 21) => self.inches = false,
+            // Homing moves the axes to their endstops, which are taken to
+            // be at zero.
+            (CommandLetter::G, 28) => {
+                for axis in Self::given_axes(command, &['X', 'Y', 'Z']) {
+                    *self.coordinate(axis) = 0.0;
+                }
+            }
+            (CommandLetter::G, 90) => self.positioning = CoordinateMode::Absolute,
+            (CommandLetter::G, 91) => self.positioning = CoordinateMode::Relative,
+            (CommandLetter::G, 92) => {
+                for axis in Self::given_axes(command, &['X', 'Y', 'Z', 'E']) {
+                    let value = self.to_mm(command.param(axis).unwrap_or(0.0));
+                    *self.coordinate(axis) = value;
+                }
+            }
+            (CommandLetter::M, 82) => self.extrusion_mode = CoordinateMode::Absolute,
+            (CommandLetter::M, 83) => self.extrusion_mode = CoordinateMode::Relative,
+            (CommandLetter::M, 104 | 109) => {
+                if let Some(s) = command.param('S').or(command.param('R')) {
+                    self.nozzle_temperature = s;
+                }
+            }
+            (CommandLetter::M, 140 | 190) => {
+                if let Some(s) = command.param('S') {
+                    self.bed_temperature = s;
+                }
+            }
+            (CommandLetter::M, 106) => {
+                self.fan_speed = (command.param('S').unwrap_or(255.0) / 255.0).clamp(0.0, 1.0);
+            }
+            (CommandLetter::M, 107) => self.fan_speed = 0.0,
+            (CommandLetter::M, 204) => {
+                if let Some(s) = command.param('S') {
+                    self.print_acceleration = Some(s);
+                    self.travel_acceleration = Some(s);
+                }
+                if let Some(p) = command.param('P') {
+                    self.print_acceleration = Some(p);
+                }
+                if let Some(t) = command.param('T') {
+                    self.travel_acceleration = Some(t);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn apply_extended(&mut self, command: &ExtendedCommand) {
+        let number = |name| command.param(name).and_then(|v| v.parse::<f64>().ok());
+        match command.name.as_str() {
+            "SET_VELOCITY_LIMIT" => {
+                if let Some(accel) = number("ACCEL") {
+                    self.print_acceleration = Some(accel);
+                    self.travel_acceleration = Some(accel);
+                }
+            }
+            "SET_HEATER_TEMPERATURE" => {
+                let target = number("TARGET").unwrap_or(0.0);
+                match command.param("HEATER") {
+                    Some("extruder") => self.nozzle_temperature = target,
+                    Some("heater_bed") => self.bed_temperature = target,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Updates the state for `instruction`, returning the move it makes, if
+    /// any.
+    pub fn apply(&mut self, instruction: &Instruction) -> Option<Motion> {
+        match instruction {
+            Instruction::Command(command) => self.apply_command(command),
+            Instruction::Extended(command) => {
+                self.apply_extended(command);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Line;
+
+    /// Runs `gcode` from the default state, returning the state and the
+    /// moves made.
+    fn run(gcode: &str) -> (MachineState, Vec<Motion>) {
+        let mut state = MachineState::new();
+        let mut moves = Vec::new();
+        for line in gcode.lines() {
+            let line = Line::parse(line).unwrap();
+            if let Some(motion) = line.instruction.as_ref().and_then(|i| state.apply(i)) {
+                moves.push(motion);
+            }
+        }
+        (state, moves)
+    }
+
+    #[test]
+    fn positioning_modes() {
+        let (state, moves) = run("G1 X10 Y10 E1 F600\n\
+             G91\n\
+             G1 X5 E1\n\
+             G90\n\
+             M83\n\
+             G1 X0 E0.5\n\
+             G92 E0\n\
+             G20\n\
+             G1 Y1");
+
+        assert_eq!(moves.len(), 4);
+        assert_eq!(moves[0].feedrate, 10.0);
+        assert_eq!(moves[0].extrusion(), 1.0);
+        assert_eq!(moves[1].to.x, 15.0);
+        assert_eq!(moves[1].to.e, 2.0);
+        // M83 keeps E relative after G90.
+        assert_eq!(moves[2].to.x, 0.0);
+        assert_eq!(moves[2].extrusion(), 0.5);
+        assert!(moves[2].is_extruding());
+        assert_eq!(moves[3].to.y, 25.4);
+        assert_eq!(state.position.e, 0.0);
+        assert!(!moves[3].is_extruding());
+    }
+
+    #[test]
+    fn arcs() {
+        let (_, moves) = run("G1 X10 Y0\nG2 X0 Y10 I-10 J0 E1\nG3 X10 Y0 I0 J-10");
+
+        assert_eq!(
+            moves[1].arc,
+            Some(ArcCenter {
+                x: 0.0,
+                y: 0.0,
+                clockwise: true
+            })
+        );
+        assert!(!moves[2].arc.unwrap().clockwise);
+        assert_eq!(moves[2].arc.unwrap().y, 0.0);
+    }
+
+    #[test]
+    fn temperatures_fan_and_acceleration() {
+        let (marlin, _) = run("M104 S200\nM190 S60\nM106 S127.5\nM204 P1000 T3000");
+        let (klipper, _) = run("SET_HEATER_TEMPERATURE HEATER=extruder TARGET=215\n\
+             SET_VELOCITY_LIMIT ACCEL=2000\n\
+             M106\n\
+             M107");
+        let (rrf, _) = run("G10 P0 S220 R220\nM140 S70\nM204 S500");
+
+        assert_eq!(marlin.nozzle_temperature, 200.0);
+        assert_eq!(marlin.bed_temperature, 60.0);
+        assert_eq!(marlin.fan_speed, 0.5);
+        assert_eq!(marlin.print_acceleration, Some(1000.0));
+        assert_eq!(marlin.travel_acceleration, Some(3000.0));
+        assert_eq!(klipper.nozzle_temperature, 215.0);
+        assert_eq!(klipper.travel_acceleration, Some(2000.0));
+        assert_eq!(klipper.fan_speed, 0.0);
+        assert_eq!(rrf.nozzle_temperature, 220.0);
+        assert_eq!(rrf.bed_temperature, 70.0);
+        assert_eq!(rrf.print_acceleration, Some(500.0));
+    }
+
+    #[test]
+    fn homing() {
+        let (state, _) = run("G1 X10 Y10 Z10 E5\nG28 X\nG1 Y5");
+        let (all, _) = run("G1 X10 Y10 Z10 E5\nG28");
+
+        assert_eq!(
+            state.position,
+            Position {
+                x: 0.0,
+                y: 5.0,
+                z: 10.0,
+                e: 5.0
+            }
+        );
+        assert_eq!(all.position.e, 5.0);
+        assert_eq!(all.position.z, 0.0);
+    }
+}
@@ -0,0 +1,50 @@
+//! Analyzes the G-code that mandoline generates, and checks the report
+//! against what the slicer knows about the print.
+
+use mandoline::{generate_toolpaths, slice_mesh, GcodeWriter, SlicerConfig};
+use mandoline_gcode::analyze;
+use mandoline_mesh::DefaultMesh;
+use mandoline_test_data::STL_CUBE;
+
+#[test]
+fn analyze_sliced_cube() {
+    let config = SlicerConfig::default();
+    let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+    let slices = slice_mesh(mesh, &config);
+    let toolpaths = generate_toolpaths(&slices, &config);
+    let mut writer = GcodeWriter::new(&config);
+    writer.start();
+    for layer in &toolpaths {
+        writer.write_layer(layer, toolpaths.len());
+    }
+    writer.end();
+    let expected = writer.estimate();
+    let gcode = writer.finish();
+
+    let analysis = analyze(&gcode, &config);
+
+    assert!(analysis.warnings.is_empty(), "{:?}", analysis.warnings);
+
+    assert_eq!(analysis.layers.len(), toolpaths.len());
+    for (layer, toolpath) in analysis.layers.iter().zip(&toolpaths) {
+        assert!((layer.z - toolpath.z as f64).abs() < 1e-3);
+    }
+    let bounds = analysis.bounds.unwrap();
+    let [x, y, z] = bounds.size();
+    // The skirt is 6mm out from the 20mm cube.
+    assert!(x > 32.0 && y > 32.0, "{:?}", bounds);
+    assert!(
+        (z - STL_CUBE.model_height as f64 + 0.2).abs() < 0.01,
+        "{:?}",
+        bounds
+    );
+    // The G-code is rounded, and starts with a move to the start position,
+    // so the estimates differ slightly.
+    let estimate = &analysis.estimate;
+    assert!((estimate.time - expected.time).abs() < expected.time * 0.01);
+    assert!((estimate.filament_length - expected.filament_length).abs() < 0.01);
+    assert_eq!(estimate.feature_times.len(), expected.feature_times.len());
+    for (a, b) in analysis.layers.iter().zip(&expected.layer_times) {
+        assert!((a.time - b).abs() < b * 0.02 + 0.1, "{} {}", a.time, b);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::geometry::Vector3;
 use crate::{Triangle, TriangleMesh};
 
+#[derive(Clone)]
 pub struct Facet {
     pub p0: u32,
     pub p1: u32,
@@ -51,6 +52,7 @@ impl<'a> Iterator for Facets<'a> {
 ///  + 3 indices * 4b * 4 facets = 48b
 ///                              =======
 ///                               108b
+#[derive(Clone)]
 pub struct VertexIndex {
     pub points: Vec<Vector3>,
     pub facets: Vec<Facet>,
@@ -0,0 +1,70 @@
+use std::f32::consts::PI;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mandoline::{slice_mesh, SlicerConfig};
+use mandoline_mesh::{DefaultMesh, Triangle, TriangleMesh, Vector3};
+use mandoline_test_data::STL_CALIBRATION_CUBE;
+
+/// A UV sphere sitting on the bed, with `rings` bands of `segments` quads
+/// each, for `2 * rings * segments` triangles.
+fn sphere(radius: f32, rings: usize, segments: usize) -> Vec<Triangle> {
+    let point = |ring: usize, segment: usize| {
+        let polar = PI * ring as f32 / rings as f32;
+        let azimuth = 2.0 * PI * segment as f32 / segments as f32;
+        Vector3::new(
+            radius * polar.sin() * azimuth.cos(),
+            radius * polar.sin() * azimuth.sin(),
+            radius * (1.0 - polar.cos()),
+        )
+    };
+    let mut triangles = Vec::with_capacity(2 * rings * segments);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            // Counter-clockwise when seen from outside, with the rings
+            // running from the bottom of the sphere to the top.
+            let a = point(ring, segment);
+            let b = point(ring, segment + 1);
+            let c = point(ring + 1, segment + 1);
+            let d = point(ring + 1, segment);
+            if ring != rings - 1 {
+                triangles.push(Triangle {
+                    p0: a,
+                    p1: c,
+                    p2: d,
+                });
+            }
+            if ring != 0 {
+                triangles.push(Triangle {
+                    p0: a,
+                    p1: b,
+                    p2: c,
+                });
+            }
+        }
+    }
+    triangles
+}
+
+fn calibration_cube(c: &mut Criterion) {
+    let config = SlicerConfig::default();
+    c.bench_function("slice calibration cube", |b| {
+        b.iter(|| {
+            let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CALIBRATION_CUBE.bytes).unwrap();
+            slice_mesh(mesh, &config)
+        })
+    });
+}
+
+fn high_poly_sphere(c: &mut Criterion) {
+    let config = SlicerConfig::default();
+    let triangles = sphere(25.0, 256, 512);
+    let mut group = c.benchmark_group("slice sphere");
+    group.sample_size(10);
+    group.bench_function("262k triangles", |b| {
+        b.iter(|| slice_mesh(DefaultMesh::from_triangles(triangles.clone()), &config))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, calibration_cube, high_poly_sphere);
+criterion_main!(benches);
@@ -0,0 +1,257 @@
+use std::f32::consts::PI;
+
+use crate::config::{SkirtShape, SlicerConfig};
+use crate::contour::Polyline;
+use crate::infill::line_spacing;
+use crate::region::Region;
+use crate::seam::turn_angles;
+use crate::toolpath::{fill_lines, ExtrusionPath, FeatureType, ToolpathLayer};
+
+/// The density of the lines in the base layers of a raft, as a percentage.
+const RAFT_BASE_DENSITY: f32 = 40.0;
+/// The number of sides used to approximate each brim ear.
+const EAR_SEGMENTS: usize = 24;
+
+/// Returns a closed polyline for every outline of `region`.
+fn loops(region: &Region) -> Vec<Polyline> {
+    region
+        .paths()
+        .iter()
+        .map(|path| {
+            let mut points = path.points().to_vec();
+            points.push(points[0]);
+            Polyline::from_points(points)
+        })
+        .collect()
+}
+
+/// Generates the skirt: loops around the first layer that prime the nozzle
+/// before the model is printed.
+///
+/// `first_layer` is everything printed on the first layer, including any
+/// brim. The innermost loop is `config.adhesion.skirt_distance` from it.
+pub fn generate_skirt(first_layer: &Region, config: &SlicerConfig) -> Vec<Polyline> {
+    let adhesion = &config.adhesion;
+    let width = config.extrusion_width;
+    let outline = match adhesion.skirt_shape {
+        SkirtShape::ConvexHull => first_layer.convex_hull(),
+        SkirtShape::Outline => first_layer.without_holes(),
+    };
+    (0..adhesion.skirt_loops)
+        .flat_map(|i| {
+            let distance = adhesion.skirt_distance + width / 2.0 + i as f32 * width;
+            loops(&outline.offset(distance).without_holes())
+        })
+        .collect()
+}
+
+/// Returns the area covered by ears at the sharp outside corners of
+/// `outline`.
+fn brim_ears(outline: &Region, config: &SlicerConfig) -> Region {
+    let adhesion = &config.adhesion;
+    // A corner is sharp if the angle inside it is less than the limit, so
+    // the path turns by more than the supplement.
+    let min_turn = PI - adhesion.brim_ear_max_angle.to_radians();
+    let ears = outline
+        .paths()
+        .iter()
+        .flat_map(|path| {
+            let points = path.points().to_vec();
+            turn_angles(&points)
+                .into_iter()
+                .zip(points)
+                .filter(|&(turn, _)| turn > min_turn)
+                .map(|(_, corner)| Region::circle(corner, adhesion.brim_width, EAR_SEGMENTS))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    Region::union_all(&ears)
+}
+
+/// Generates a brim: loops around the outside of the first layer that hold
+/// it down to the bed.
+///
+/// With `config.adhesion.brim_ears` set, the brim is only printed around
+/// sharp corners, which are the most likely to lift.
+pub fn generate_brim(first_layer: &Region, config: &SlicerConfig) -> Vec<Polyline> {
+    let adhesion = &config.adhesion;
+    let width = config.extrusion_width;
+    if adhesion.brim_width <= 0.0 || first_layer.is_empty() {
+        return Vec::new();
+    }
+    let outline = first_layer.without_holes();
+    let mut area = outline.offset(adhesion.brim_width).difference(&outline);
+    if adhesion.brim_ears {
+        area = area.intersection(&brim_ears(&outline, config));
+    }
+    let count = (adhesion.brim_width / width).round().max(1.0) as usize;
+    let loops = (0..count)
+        .flat_map(|i| loops(&outline.offset(width / 2.0 + i as f32 * width)))
+        .collect::<Vec<_>>();
+    if adhesion.brim_ears {
+        // Only keep the parts of each loop inside an ear.
+        area.offset(width / 2.0).clip_polylines(&loops)
+    } else {
+        loops
+    }
+}
+
+/// Generates the layers of a raft to print the model on.
+///
+/// The raft covers the first layer of the model, grown by
+/// `config.adhesion.raft_margin`. Its base layers are printed with widely
+/// spaced lines that stick to the bed, and the top
+/// `config.adhesion.raft_interface_layers` are solid to give the model a
+/// flat surface to rest on.
+pub fn generate_raft(first_layer: &Region, config: &SlicerConfig) -> Vec<ToolpathLayer> {
+    let adhesion = &config.adhesion;
+    let width = config.extrusion_width;
+    if adhesion.raft_layers == 0 || first_layer.is_empty() {
+        return Vec::new();
+    }
+    let region = first_layer
+        .without_holes()
+        .offset(adhesion.raft_margin)
+        .without_holes();
+    let interface_layers = adhesion.raft_interface_layers.min(adhesion.raft_layers);
+    let base_layers = adhesion.raft_layers - interface_layers;
+    (0..adhesion.raft_layers)
+        .map(|index| {
+            let (feature, spacing) = if index < base_layers {
+                let spacing = line_spacing(RAFT_BASE_DENSITY, width, 1).unwrap();
+                (FeatureType::RaftBase, spacing)
+            } else {
+                (FeatureType::RaftInterface, width)
+            };
+            // Cross each layer over the one below.
+            let angle = if index.is_multiple_of(2) {
+                PI / 4.0
+            } else {
+                -PI / 4.0
+            };
+            let inside = region.offset(-width / 2.0);
+            let paths = loops(&inside)
+                .into_iter()
+                .chain(fill_lines(&inside, angle, spacing, width))
+                .map(|p| ExtrusionPath::new(feature, p))
+                .collect();
+            ToolpathLayer {
+                index,
+                z: (index + 1) as f32 * config.layer_height,
+                height: config.layer_height,
+                region: region.clone(),
+                paths,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AdhesionConfig;
+    use crate::test_util::{polygon, rect, square};
+    use cgmath::{InnerSpace, Vector2};
+    use float_eq::assert_float_eq;
+
+    fn config(adhesion: AdhesionConfig) -> SlicerConfig {
+        SlicerConfig {
+            extrusion_width: 0.5,
+            adhesion,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn skirt_loops_around_hull() {
+        // Two squares, with a gap between them. The hull bridges the gap.
+        let layer = square(10.0).union(&rect(20.0, 0.0, 30.0, 10.0));
+        let config = config(AdhesionConfig {
+            skirt_loops: 2,
+            skirt_distance: 3.0,
+            ..Default::default()
+        });
+
+        let skirt = generate_skirt(&layer, &config);
+
+        assert_eq!(skirt.len(), 2);
+        // The innermost loop runs 3mm from the model, plus half a line.
+        let bottom = skirt[0]
+            .points()
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::MAX, f32::min);
+        assert_float_eq!(bottom, -3.25, abs <= 0.01);
+        // No point of the skirt sits in the gap between the squares.
+        for p in skirt.iter().flat_map(|s| s.points()) {
+            assert!(!(p.x > 10.0 && p.x < 20.0 && p.y > -1.0 && p.y < 11.0));
+        }
+    }
+
+    #[test]
+    fn brim_surrounds_outline() {
+        let config = config(AdhesionConfig {
+            brim_width: 2.0,
+            ..Default::default()
+        });
+
+        let brim = generate_brim(&square(10.0), &config);
+
+        assert_eq!(brim.len(), 4);
+        assert!(brim.iter().all(|p| p.first() == p.last()));
+    }
+
+    #[test]
+    fn brim_ears_at_sharp_corners() {
+        // A triangle with one sharp corner at (20, 0), and two wider ones.
+        let layer = Region::from_paths(&[polygon(&[(0.0, 0.0), (20.0, 0.0), (0.0, 10.0)])]);
+        let config = config(AdhesionConfig {
+            brim_width: 3.0,
+            brim_ears: true,
+            brim_ear_max_angle: 45.0,
+            ..Default::default()
+        });
+
+        let brim = generate_brim(&layer, &config);
+
+        assert!(!brim.is_empty());
+        let corner = Vector2::new(20.0, 0.0);
+        for p in brim.iter().flat_map(|b| b.points()) {
+            assert!((p - corner).magnitude() < 3.5, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn raft_layers() {
+        let config = config(AdhesionConfig {
+            raft_layers: 3,
+            raft_interface_layers: 1,
+            raft_margin: 2.0,
+            ..Default::default()
+        });
+
+        let raft = generate_raft(&square(10.0), &config);
+
+        assert_eq!(raft.len(), 3);
+        for (i, layer) in raft.iter().enumerate() {
+            let expected = if i < 2 {
+                FeatureType::RaftBase
+            } else {
+                FeatureType::RaftInterface
+            };
+            assert!(layer.paths.iter().all(|p| p.feature == expected));
+            assert_float_eq!(layer.z, 0.2 * (i + 1) as f32, abs <= 0.0001);
+        }
+        // Interface layers are denser than the base.
+        assert!(
+            raft[2].paths.len() > raft[0].paths.len() || {
+                let length =
+                    |l: &ToolpathLayer| l.paths.iter().map(|p| p.path.length()).sum::<f32>();
+                length(&raft[2]) > length(&raft[0])
+            }
+        );
+        let (min, max) = raft[0].region.bounding_box().unwrap();
+        assert_float_eq!(min.x, -2.0, abs <= 0.01);
+        assert_float_eq!(max.y, 12.0, abs <= 0.01);
+    }
+}
@@ -0,0 +1,319 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::ArcFittingConfig;
+
+/// Arcs with a larger radius than this, in mm, are printed as straight
+/// moves. They're so close to straight that rounding the centre to a few
+/// decimal places would move the arc by more than the tolerance.
+const MAX_RADIUS: f32 = 1000.0;
+/// The fewest segments of a path that are replaced by an arc.
+const MIN_SEGMENTS: usize = 3;
+
+/// A move along an extrusion path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathMove {
+    /// A straight line to a point.
+    Line(Vector2<f32>),
+    /// An arc around `center`, ending at `end`.
+    Arc {
+        end: Vector2<f32>,
+        center: Vector2<f32>,
+        clockwise: bool,
+    },
+}
+
+impl PathMove {
+    /// The point the move ends at.
+    pub fn end(&self) -> Vector2<f32> {
+        match *self {
+            PathMove::Line(end) | PathMove::Arc { end, .. } => end,
+        }
+    }
+
+    /// Returns the angle an arc from `from` turns through, in radians. It is
+    /// negative for clockwise arcs.
+    pub fn sweep(&self, from: Vector2<f32>) -> f32 {
+        match *self {
+            PathMove::Line(_) => 0.0,
+            PathMove::Arc {
+                end,
+                center,
+                clockwise,
+            } => {
+                let angle = signed_angle(from - center, end - center);
+                match (clockwise, angle) {
+                    (true, a) if a > 0.0 => a - 2.0 * PI,
+                    (false, a) if a < 0.0 => a + 2.0 * PI,
+                    (_, a) => a,
+                }
+            }
+        }
+    }
+
+    /// Returns the length of the move, starting from `from`.
+    pub fn length(&self, from: Vector2<f32>) -> f32 {
+        match *self {
+            PathMove::Line(end) => (end - from).magnitude(),
+            PathMove::Arc { center, .. } => (from - center).magnitude() * self.sweep(from).abs(),
+        }
+    }
+
+    /// Returns points along the move from `from`, at most `step` mm apart.
+    /// The last point is the end of the move.
+    pub fn points(&self, from: Vector2<f32>, step: f32) -> Vec<Vector2<f32>> {
+        let count = (self.length(from) / step).ceil().max(1.0) as usize;
+        (1..=count)
+            .map(|i| {
+                let t = i as f32 / count as f32;
+                match *self {
+                    _ if i == count => self.end(),
+                    PathMove::Line(end) => from + (end - from) * t,
+                    PathMove::Arc { center, .. } => {
+                        let (sin, cos) = (self.sweep(from) * t).sin_cos();
+                        let r = from - center;
+                        center + Vector2::new(r.x * cos - r.y * sin, r.x * sin + r.y * cos)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Returns the angle from `a` to `b`, between -π and π.
+fn signed_angle(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    cross(a, b).atan2(a.dot(b))
+}
+
+/// Returns the centre and radius of the circle through three points, or
+/// `None` if they're on a line.
+fn circle_through(
+    a: Vector2<f32>,
+    b: Vector2<f32>,
+    c: Vector2<f32>,
+) -> Option<(Vector2<f32>, f32)> {
+    let (ab, ac) = (b - a, c - a);
+    let d = 2.0 * cross(ab, ac);
+    if d.abs() < f32::EPSILON {
+        return None;
+    }
+    let (ab2, ac2) = (ab.magnitude2(), ac.magnitude2());
+    let offset = Vector2::new(ac.y * ab2 - ab.y * ac2, ab.x * ac2 - ac.x * ab2) / d;
+    Some((a + offset, offset.magnitude()))
+}
+
+/// An arc fitted to a run of points.
+struct Arc {
+    center: Vector2<f32>,
+    radius: f32,
+    /// The angle the arc turns through. Negative for clockwise arcs.
+    sweep: f32,
+}
+
+/// Fits an arc through `points`. Returns `None` unless every point, and the
+/// middle of every segment between them, is within `tolerance` of the arc,
+/// and the points turn steadily in one direction.
+fn fit_arc(points: &[Vector2<f32>], tolerance: f32) -> Option<Arc> {
+    let (first, middle, last) = (
+        points[0],
+        points[points.len() / 2],
+        points[points.len() - 1],
+    );
+    let (center, radius) = circle_through(first, middle, last)?;
+    if radius > MAX_RADIUS {
+        return None;
+    }
+    let off_arc = |p: Vector2<f32>| ((p - center).magnitude() - radius).abs() > tolerance;
+    let mut sweep = 0.0;
+    for segment in points.windows(2) {
+        let (p, q) = (segment[0], segment[1]);
+        // The middle of a segment is the point farthest from the arc.
+        if off_arc(q) || off_arc((p + q) / 2.0) {
+            return None;
+        }
+        let step = signed_angle(p - center, q - center);
+        if step == 0.0 || (sweep != 0.0 && step.signum() != f32::signum(sweep)) {
+            return None;
+        }
+        sweep += step;
+    }
+    // Full circles have no unique centre in G-code, so leave them to be
+    // split into several arcs.
+    if sweep.abs() >= 2.0 * PI - 0.01 {
+        return None;
+    }
+    Some(Arc {
+        center,
+        radius,
+        sweep,
+    })
+}
+
+/// Converts the path through `points` into moves, replacing runs of points
+/// that lie on a circle with arcs.
+///
+/// The first point is where the path starts, so the moves lead from it to
+/// each of the following points. Arcs start and end on points of the path,
+/// and stay within `config.tolerance` of it.
+pub fn fit_arcs(points: &[Vector2<f32>], config: &ArcFittingConfig) -> Vec<PathMove> {
+    let mut moves = Vec::new();
+    let mut start = 0;
+    while start + 1 < points.len() {
+        // Grow the arc one point at a time, until the points stop fitting.
+        let mut best = None;
+        let mut end = start + MIN_SEGMENTS;
+        while end < points.len() {
+            match fit_arc(&points[start..=end], config.tolerance) {
+                Some(arc) => best = Some((end, arc)),
+                None => break,
+            }
+            end += 1;
+        }
+        match best {
+            Some((end, arc)) if arc.radius * arc.sweep.abs() >= config.min_arc_length => {
+                moves.push(PathMove::Arc {
+                    end: points[end],
+                    center: arc.center,
+                    clockwise: arc.sweep < 0.0,
+                });
+                start = end;
+            }
+            _ => {
+                moves.push(PathMove::Line(points[start + 1]));
+                start += 1;
+            }
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    fn config(tolerance: f32, min_arc_length: f32) -> ArcFittingConfig {
+        ArcFittingConfig {
+            enabled: true,
+            tolerance,
+            min_arc_length,
+        }
+    }
+
+    /// Points along an arc of `radius` around the origin, from `start` to
+    /// `end` radians.
+    fn arc_points(radius: f32, start: f32, end: f32, segments: usize) -> Vec<Vector2<f32>> {
+        (0..=segments)
+            .map(|i| {
+                let angle = start + (end - start) * i as f32 / segments as f32;
+                Vector2::new(radius * angle.cos(), radius * angle.sin())
+            })
+            .collect()
+    }
+
+    /// Returns points along the path that `moves` trace from `start`, at
+    /// most `step` mm apart.
+    fn trace(start: Vector2<f32>, moves: &[PathMove], step: f32) -> Vec<Vector2<f32>> {
+        let mut points = vec![start];
+        for m in moves {
+            points.extend(m.points(*points.last().unwrap(), step));
+        }
+        points
+    }
+
+    fn distance_to_path(p: Vector2<f32>, path: &[Vector2<f32>]) -> f32 {
+        path.windows(2)
+            .map(|s| {
+                let (a, b) = (s[0], s[1]);
+                let t = ((p - a).dot(b - a) / (b - a).magnitude2()).clamp(0.0, 1.0);
+                (p - (a + (b - a) * t)).magnitude()
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn fits_arc_within_tolerance() {
+        // A 270° counter-clockwise arc followed by a straight line.
+        let mut points = arc_points(10.0, 0.0, 1.5 * PI, 90);
+        points.push(Vector2::new(5.0, -10.0));
+        points.push(Vector2::new(10.0, -10.0));
+        let tolerance = 0.01;
+
+        let moves = fit_arcs(&points, &config(tolerance, 0.5));
+
+        assert!(moves.len() < 5, "{:?}", moves);
+        assert!(matches!(
+            moves[0],
+            PathMove::Arc {
+                clockwise: false,
+                ..
+            }
+        ));
+        assert_eq!(
+            moves.last(),
+            Some(&PathMove::Line(Vector2::new(10.0, -10.0)))
+        );
+        // The arcs follow the original path both ways.
+        let traced = trace(points[0], &moves, 0.05);
+        for &p in &traced {
+            assert!(distance_to_path(p, &points) <= tolerance + 1e-4);
+        }
+        for &p in &points {
+            assert!(distance_to_path(p, &traced) <= tolerance + 1e-4);
+        }
+    }
+
+    #[test]
+    fn clockwise_arc() {
+        let points = arc_points(5.0, PI, 0.0, 30);
+
+        let moves = fit_arcs(&points, &config(0.01, 0.5));
+
+        assert_eq!(moves.len(), 1);
+        let PathMove::Arc {
+            end,
+            center,
+            clockwise,
+        } = moves[0]
+        else {
+            panic!("{:?}", moves[0]);
+        };
+        assert!(clockwise);
+        assert_float_eq!(center.x, 0.0, abs <= 1e-3);
+        assert_float_eq!(center.y, 0.0, abs <= 1e-3);
+        assert_eq!(end, points[30]);
+        assert_float_eq!(moves[0].length(points[0]), 5.0 * PI, abs <= 1e-3);
+    }
+
+    #[test]
+    fn leaves_lines_and_short_arcs() {
+        let line = (0..10)
+            .map(|i| Vector2::new(i as f32, 0.0))
+            .collect::<Vec<_>>();
+        let small = arc_points(0.2, 0.0, PI, 8);
+
+        let lines = fit_arcs(&line, &config(0.01, 0.5));
+        let short = fit_arcs(&small, &config(0.01, 1.0));
+
+        assert!(lines.iter().all(|m| matches!(m, PathMove::Line(_))));
+        assert_eq!(lines.len(), 9);
+        assert!(short.iter().all(|m| matches!(m, PathMove::Line(_))));
+    }
+
+    #[test]
+    fn noisy_points_stay_lines() {
+        // A zig-zag is never within tolerance of an arc.
+        let points = (0..20)
+            .map(|i| Vector2::new(i as f32, if i % 2 == 0 { 0.0 } else { 0.5 }))
+            .collect::<Vec<_>>();
+
+        let moves = fit_arcs(&points, &config(0.01, 0.5));
+
+        assert_eq!(moves.len(), 19);
+    }
+}
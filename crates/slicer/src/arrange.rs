@@ -0,0 +1,403 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use cgmath::Vector2;
+use mandoline_mesh::Triangle;
+
+use crate::config::{ArrangeConfig, SlicerConfig};
+use crate::contour::ClosedPath;
+use crate::plate::Transform;
+use crate::region::Region;
+
+/// Overlaps smaller than this, in mm², come from rounding where shapes only
+/// touch, and are ignored.
+const OVERLAP_TOLERANCE: f32 = 1e-3;
+
+/// How far, in mm, a shape may poke past the edge of the bed's bounding box
+/// through rounding.
+const EDGE_TOLERANCE: f32 = 1e-3;
+
+/// The outline of `triangles` on the bed: the convex hull of their points,
+/// seen from above.
+pub fn footprint(triangles: &[Triangle]) -> Region {
+    let points = triangles
+        .iter()
+        .flat_map(|t| [t.p0, t.p1, t.p2])
+        .map(|p| Vector2::new(p.x, p.y))
+        .collect::<Vec<_>>();
+    Region::convex_hull_of_points(&points)
+}
+
+/// An object to arrange on the bed.
+pub struct ArrangeItem {
+    /// The outline of the object on the bed, usually from `footprint`.
+    pub footprint: Region,
+    /// How many copies of the object to place.
+    pub copies: usize,
+}
+
+/// Where one copy of an item goes on the bed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    /// The index of the item this is a copy of.
+    pub item: usize,
+    /// Moves the item's footprint into place. Only the offset and rotation
+    /// are set.
+    pub transform: Transform,
+}
+
+/// There's no room left on the bed for a copy of an item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArrangeError {
+    /// The index of the item that doesn't fit.
+    pub item: usize,
+}
+
+impl fmt::Display for ArrangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "there's no room on the bed for object {}", self.item)
+    }
+}
+
+impl std::error::Error for ArrangeError {}
+
+/// An item's footprint at one of the rotations that are tried.
+struct Orientation {
+    rotation: f32,
+    outline: Region,
+    /// The outline grown by half the spacing, which mustn't overlap that of
+    /// any other object.
+    padded: Region,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+}
+
+/// A copy of an item that's been given its place.
+struct Placed {
+    item: usize,
+    rotation: f32,
+    offset: Vector2<f32>,
+    outline: Region,
+    padded: Region,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+}
+
+fn orientations(footprint: &Region, config: &ArrangeConfig) -> Vec<Orientation> {
+    let rotations = if config.rotation_step > 0.0 {
+        (0..)
+            .map(|i| i as f32 * config.rotation_step)
+            .take_while(|&r| r < 360.0 - EDGE_TOLERANCE)
+            .collect()
+    } else {
+        vec![0.0]
+    };
+    rotations
+        .into_iter()
+        .filter_map(|rotation| {
+            let (sin, cos) = rotation.to_radians().sin_cos();
+            let outline = footprint
+                .map_points(|p| Vector2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos));
+            let (min, max) = outline.bounding_box()?;
+            Some(Orientation {
+                rotation,
+                padded: outline.offset(config.spacing / 2.0),
+                outline,
+                min,
+                max,
+            })
+        })
+        .collect()
+}
+
+impl Orientation {
+    /// Moves this orientation so its bounding box starts at `corner`.
+    fn place(&self, item: usize, corner: Vector2<f32>) -> Placed {
+        let offset = corner - self.min;
+        Placed {
+            item,
+            rotation: self.rotation,
+            offset,
+            outline: self.outline.map_points(|p| p + offset),
+            padded: self.padded.map_points(|p| p + offset),
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+fn inside(outline: &Region, bed: &Region) -> bool {
+    outline.difference(bed).area() <= OVERLAP_TOLERANCE
+}
+
+fn fits(candidate: &Placed, bed: &Region, placed: &[Placed], spacing: f32) -> bool {
+    inside(&candidate.outline, bed)
+        && placed.iter().all(|p| {
+            // Shapes whose boxes are further apart than the spacing can't
+            // touch, so there's no need to intersect them.
+            let apart = candidate.min.x >= p.max.x + spacing
+                || p.min.x >= candidate.max.x + spacing
+                || candidate.min.y >= p.max.y + spacing
+                || p.min.y >= candidate.max.y + spacing;
+            apart || candidate.padded.intersection(&p.padded).area() <= OVERLAP_TOLERANCE
+        })
+}
+
+/// How good a place is: the area of the bounding box around everything
+/// placed so far, then the lowest and leftmost. Smaller is better.
+type Score = [f32; 3];
+
+fn compare(a: &Score, b: &Score) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.total_cmp(b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Finds the best place for another copy of `item` among the corners made by
+/// the bed and the objects placed so far.
+fn best_place(
+    item: usize,
+    orientations: &[Orientation],
+    placed: &[Placed],
+    bed: &Region,
+    (bed_min, bed_max): (Vector2<f32>, Vector2<f32>),
+    spacing: f32,
+) -> Option<Placed> {
+    let xs = std::iter::once(bed_min.x)
+        .chain(placed.iter().map(|p| p.max.x + spacing))
+        .collect::<Vec<_>>();
+    let ys = std::iter::once(bed_min.y)
+        .chain(placed.iter().map(|p| p.max.y + spacing))
+        .collect::<Vec<_>>();
+    let group = placed.iter().fold(None, |group, p| match group {
+        None => Some((p.min, p.max)),
+        Some((min, max)) => Some((
+            Vector2::new(p.min.x.min(min.x), p.min.y.min(min.y)),
+            Vector2::new(p.max.x.max(max.x), p.max.y.max(max.y)),
+        )),
+    });
+    let score = |corner: Vector2<f32>, size: Vector2<f32>| -> Score {
+        let (min, max) = group.unwrap_or((corner, corner + size));
+        let width = max.x.max(corner.x + size.x) - min.x.min(corner.x);
+        let height = max.y.max(corner.y + size.y) - min.y.min(corner.y);
+        [width * height, corner.y, corner.x]
+    };
+
+    let mut best: Option<(Score, Placed)> = None;
+    for orientation in orientations {
+        let size = orientation.max - orientation.min;
+        let mut candidates = xs
+            .iter()
+            .flat_map(|&x| ys.iter().map(move |&y| Vector2::new(x, y)))
+            .filter(|c| {
+                c.x + size.x <= bed_max.x + EDGE_TOLERANCE
+                    && c.y + size.y <= bed_max.y + EDGE_TOLERANCE
+            })
+            .map(|c| (score(c, size), c))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| compare(&a.0, &b.0));
+        // Candidates are checked from best to worst, so the first that fits
+        // is the best this orientation can do.
+        for (score, corner) in candidates {
+            if best
+                .as_ref()
+                .is_some_and(|(b, _)| compare(b, &score).is_le())
+            {
+                break;
+            }
+            let candidate = orientation.place(item, corner);
+            if fits(&candidate, bed, placed, spacing) {
+                best = Some((score, candidate));
+                break;
+            }
+        }
+    }
+    best.map(|(_, placed)| placed)
+}
+
+/// Packs copies of `items` onto the bed described by `config`, leaving
+/// `arrange.spacing` between them and trying them at every multiple of
+/// `arrange.rotation_step`.
+///
+/// Bigger items are placed first, each copy where it keeps the whole
+/// arrangement most compact, and the arrangement is then centred on the bed.
+/// Placements are returned in the order of `items`.
+///
+/// Returns an error naming the first item that has a copy that doesn't fit.
+pub fn arrange(
+    items: &[ArrangeItem],
+    config: &SlicerConfig,
+) -> Result<Vec<Placement>, ArrangeError> {
+    let corners = config
+        .bed_shape
+        .iter()
+        .map(|&(x, y)| Vector2::new(x, y))
+        .collect();
+    let bed = Region::from_paths(&[ClosedPath::from_points(corners)]);
+    let spacing = config.arrange.spacing;
+
+    // Big items are the hardest to fit, so they go first.
+    let mut order = (0..items.len())
+        .filter(|&i| items[i].copies > 0)
+        .collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let area = |i: usize| items[i].footprint.area();
+        area(b).total_cmp(&area(a))
+    });
+
+    let mut placed: Vec<Placed> = Vec::new();
+    for item in order {
+        let error = ArrangeError { item };
+        let bounds = bed.bounding_box().ok_or(error)?;
+        let orientations = orientations(&items[item].footprint, &config.arrange);
+        for _ in 0..items[item].copies {
+            let place =
+                best_place(item, &orientations, &placed, &bed, bounds, spacing).ok_or(error)?;
+            placed.push(place);
+        }
+    }
+
+    // Move the arrangement to the middle of the bed, if it still fits there.
+    let shift = match (bed.bounding_box(), placed.first()) {
+        (Some((bed_min, bed_max)), Some(first)) => {
+            let (min, max) = placed.iter().fold((first.min, first.max), |(min, max), p| {
+                (
+                    Vector2::new(p.min.x.min(min.x), p.min.y.min(min.y)),
+                    Vector2::new(p.max.x.max(max.x), p.max.y.max(max.y)),
+                )
+            });
+            let shift = (bed_min + bed_max - min - max) / 2.0;
+            let centred = placed
+                .iter()
+                .all(|p| inside(&p.outline.map_points(|q| q + shift), &bed));
+            if centred {
+                shift
+            } else {
+                Vector2::new(0.0, 0.0)
+            }
+        }
+        _ => Vector2::new(0.0, 0.0),
+    };
+
+    placed.sort_by_key(|p| p.item);
+    Ok(placed
+        .into_iter()
+        .map(|p| Placement {
+            item: p.item,
+            transform: Transform {
+                offset: (p.offset.x + shift.x, p.offset.y + shift.y),
+                rotation: p.rotation,
+                ..Default::default()
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rect;
+    use float_eq::assert_float_eq;
+    use mandoline_mesh::{DefaultMesh, TriangleMesh};
+    use mandoline_test_data::STL_CUBE;
+
+    fn config(width: f32, height: f32, rotation_step: f32) -> SlicerConfig {
+        SlicerConfig {
+            bed_shape: vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)],
+            arrange: ArrangeConfig {
+                rotation_step,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn placed(footprint: &Region, placement: &Placement) -> Region {
+        let transform = placement.transform;
+        footprint.map_points(|p| {
+            let p = transform.apply(mandoline_mesh::Vector3::new(p.x, p.y, 0.0));
+            Vector2::new(p.x, p.y)
+        })
+    }
+
+    #[test]
+    fn footprint_of_cube() {
+        let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let triangles = mesh.triangles().collect::<Vec<_>>();
+
+        let footprint = footprint(&triangles);
+
+        assert_float_eq!(footprint.area(), 400.0, abs <= 1e-3);
+    }
+
+    #[test]
+    fn copies_are_spaced_apart() {
+        let config = config(100.0, 100.0, 90.0);
+        let items = [
+            ArrangeItem {
+                footprint: rect(0.0, 0.0, 30.0, 20.0),
+                copies: 3,
+            },
+            ArrangeItem {
+                footprint: Region::circle(Vector2::new(0.0, 0.0), 10.0, 32),
+                copies: 2,
+            },
+        ];
+
+        let placements = arrange(&items, &config).unwrap();
+
+        assert_eq!(
+            placements.iter().map(|p| p.item).collect::<Vec<_>>(),
+            [0, 0, 0, 1, 1]
+        );
+        let bed = rect(0.0, 0.0, 100.0, 100.0);
+        let outlines = placements
+            .iter()
+            .map(|p| placed(&items[p.item].footprint, p))
+            .collect::<Vec<_>>();
+        for (i, a) in outlines.iter().enumerate() {
+            assert!(a.difference(&bed).area() < 1e-2);
+            for b in &outlines[i + 1..] {
+                let gap = config.arrange.spacing / 2.0 - 0.01;
+                assert!(a.offset(gap).intersection(&b.offset(gap)).area() < 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn rotates_to_fit() {
+        let items = [ArrangeItem {
+            footprint: rect(0.0, 0.0, 20.0, 90.0),
+            copies: 1,
+        }];
+
+        let placements = arrange(&items, &config(100.0, 30.0, 90.0)).unwrap();
+        let fixed = arrange(&items, &config(100.0, 30.0, 0.0));
+
+        let rotation = placements[0].transform.rotation;
+        assert!(rotation == 90.0 || rotation == 270.0);
+        let (min, max) = placed(&items[0].footprint, &placements[0])
+            .bounding_box()
+            .unwrap();
+        // The object is centred on the bed.
+        assert_float_eq!(min.x, 5.0, abs <= 1e-3);
+        assert_float_eq!(max.x, 95.0, abs <= 1e-3);
+        assert_float_eq!(min.y, 5.0, abs <= 1e-3);
+        assert_eq!(fixed, Err(ArrangeError { item: 0 }));
+    }
+
+    #[test]
+    fn too_many_copies() {
+        let config = config(50.0, 50.0, 90.0);
+        let item = |copies| ArrangeItem {
+            footprint: rect(0.0, 0.0, 20.0, 20.0),
+            copies,
+        };
+
+        // Two rows of two, 6mm apart, just fit.
+        assert_eq!(arrange(&[item(4)], &config).unwrap().len(), 4);
+        assert_eq!(arrange(&[item(5)], &config), Err(ArrangeError { item: 0 }));
+    }
+}
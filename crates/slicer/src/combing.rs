@@ -0,0 +1,445 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::ResolutionConfig;
+use crate::region::{LineSegment, Region};
+use crate::simplify::simplify_paths;
+
+/// How far inside the region a line has to be to count as inside, so that
+/// lines running along the outline aren't.
+const INTERIOR_TOLERANCE: f32 = 1e-3;
+
+/// The nodes of a visibility graph, and the nodes visible from each.
+type VisibilityGraph = (Vec<Vector2<f32>>, Vec<Vec<usize>>);
+
+/// Finds travel routes that stay inside a layer, so that the nozzle doesn't
+/// cross perimeters and leave marks on the surface of the model.
+///
+/// Routes keep to the layer's boundary: the area inside its perimeters, so
+/// that travels don't drag along the outside of the model either. Travels
+/// that start or end out on the perimeters cross straight over to the
+/// nearest point of the boundary.
+///
+/// Inside the boundary, routes are found on a visibility graph: the nodes
+/// are the corners of the boundary's outline, shrunk slightly so they lie
+/// inside it, and two nodes are connected if the straight line between them
+/// stays inside the boundary. The graph is only built the first time a route
+/// needs it.
+///
+/// Building the graph checks every pair of nodes, so the outline is
+/// simplified first, and only corners that point into the layer are kept.
+/// A shortest route only ever bends around those.
+pub struct CombingRouter {
+    /// The whole area of the layer.
+    layer: Region,
+    /// The area travels must stay inside.
+    boundary: Region,
+    /// `boundary`, shrunk slightly so that it excludes the outline.
+    interior: Region,
+    /// The edges of `boundary`'s outline.
+    edges: Vec<(Vector2<f32>, Vector2<f32>)>,
+    /// The edges of `boundary`'s outline shrunk by `inset`, where travels
+    /// from outside of the boundary join it.
+    entries: Vec<(Vector2<f32>, Vector2<f32>)>,
+    graph: Option<VisibilityGraph>,
+    /// How far inside `boundary` the graph's nodes are placed.
+    inset: f32,
+}
+
+/// An entry in the Dijkstra queue, ordered so that the shortest distance is
+/// popped first.
+#[derive(PartialEq)]
+struct Visit {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returns true if segments `a` and `b` cross each other, not counting
+/// segments that only touch at their ends.
+fn segments_cross(a: (Vector2<f32>, Vector2<f32>), b: (Vector2<f32>, Vector2<f32>)) -> bool {
+    let cross = |o: Vector2<f32>, p: Vector2<f32>, q: Vector2<f32>| {
+        (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x)
+    };
+    const EPSILON: f32 = 1e-6;
+    let d1 = cross(b.0, b.1, a.0);
+    let d2 = cross(b.0, b.1, a.1);
+    let d3 = cross(a.0, a.1, b.0);
+    let d4 = cross(a.0, a.1, b.1);
+    ((d1 > EPSILON && d2 < -EPSILON) || (d1 < -EPSILON && d2 > EPSILON))
+        && ((d3 > EPSILON && d4 < -EPSILON) || (d3 < -EPSILON && d4 > EPSILON))
+}
+
+/// Returns the corners of the closed path `points`, which has the space
+/// travels can use on its left, that point into that space.
+fn reflex_corners(points: &[Vector2<f32>]) -> impl Iterator<Item = Vector2<f32>> + '_ {
+    let n = points.len();
+    (0..n)
+        .filter(move |&i| {
+            let before = points[i] - points[(i + n - 1) % n];
+            let after = points[(i + 1) % n] - points[i];
+            // A right turn.
+            before.x * after.y - before.y * after.x < 0.0
+        })
+        .map(move |i| points[i])
+}
+
+/// Returns the edges of every path of `region`'s outline.
+fn outline_edges(region: &Region) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+    region
+        .paths()
+        .iter()
+        .flat_map(|path| {
+            let points = path.points();
+            (0..points.len()).map(move |i| (points[i], points[(i + 1) % points.len()]))
+        })
+        .collect()
+}
+
+/// Returns the point of the segment from `a` to `b` closest to `p`.
+fn closest_point(p: Vector2<f32>, (a, b): (Vector2<f32>, Vector2<f32>)) -> Vector2<f32> {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    if length2 == 0.0 {
+        return a;
+    }
+    a + ab * ((p - a).dot(ab) / length2).clamp(0.0, 1.0)
+}
+
+impl CombingRouter {
+    /// Creates a router for travels inside of `region`, keeping `margin` mm
+    /// away from its outline where possible. Graph nodes are placed `inset`
+    /// mm further inside.
+    pub fn new(region: &Region, margin: f32, inset: f32) -> Self {
+        let boundary = if margin > 0.0 {
+            region.offset(-margin)
+        } else {
+            region.clone()
+        };
+        Self {
+            layer: region.clone(),
+            interior: boundary.offset(-INTERIOR_TOLERANCE),
+            edges: outline_edges(&boundary),
+            entries: outline_edges(&boundary.offset(-inset)),
+            boundary,
+            graph: None,
+            inset,
+        }
+    }
+
+    /// Returns where a travel from `point` joins the boundary: `point`
+    /// itself if it's inside, or otherwise the nearest point just inside the
+    /// boundary that can be reached in a straight line through the layer.
+    fn entry(&self, point: Vector2<f32>) -> Option<Vector2<f32>> {
+        if self.boundary.contains_point(point) {
+            return Some(point);
+        }
+        let entry = self
+            .entries
+            .iter()
+            .map(|&edge| closest_point(point, edge))
+            .min_by(|a, b| {
+                (a - point)
+                    .magnitude2()
+                    .total_cmp(&(b - point).magnitude2())
+            })?;
+        let hop = LineSegment::new(point, entry);
+        let inside: f32 = self
+            .layer
+            .clip_segments(&[hop])
+            .iter()
+            .map(LineSegment::length)
+            .sum();
+        (inside >= hop.length() * 0.999).then_some(entry)
+    }
+
+    /// Returns true if the straight line from `a` to `b` stays inside the
+    /// boundary.
+    pub fn is_visible(&self, a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        if self.edges.iter().any(|&e| segments_cross((a, b), e)) {
+            return false;
+        }
+        // Without crossings the line can still lie outside, or run along the
+        // outline.
+        let segment = LineSegment::new(a, b);
+        let inside: f32 = self
+            .interior
+            .clip_segments(&[segment])
+            .iter()
+            .map(LineSegment::length)
+            .sum();
+        inside >= segment.length() - 10.0 * INTERIOR_TOLERANCE
+    }
+
+    fn graph(&mut self) -> &VisibilityGraph {
+        if self.graph.is_none() {
+            // Simplifying moves the outline by at most half the inset, so the
+            // nodes stay inside the boundary.
+            let resolution = ResolutionConfig {
+                collinear_tolerance: 0.0,
+                max_deviation: self.inset / 2.0,
+                min_segment_length: self.inset,
+            };
+            let outlines = simplify_paths(&self.boundary.offset(-self.inset).paths(), &resolution);
+            let nodes = outlines
+                .iter()
+                .flat_map(|p| reflex_corners(p.points()))
+                .collect::<Vec<_>>();
+            let mut visible = vec![Vec::new(); nodes.len()];
+            for i in 0..nodes.len() {
+                for j in i + 1..nodes.len() {
+                    if self.is_visible(nodes[i], nodes[j]) {
+                        visible[i].push(j);
+                        visible[j].push(i);
+                    }
+                }
+            }
+            self.graph = Some((nodes, visible));
+        }
+        self.graph.as_ref().unwrap()
+    }
+
+    /// Finds the shortest route from `from` to `to` that stays inside the
+    /// boundary, apart from getting to and from it.
+    ///
+    /// Returns the points to travel through after `from`, ending with `to`,
+    /// or `None` if the points aren't connected inside the boundary.
+    pub fn route(&mut self, from: Vector2<f32>, to: Vector2<f32>) -> Option<Vec<Vector2<f32>>> {
+        let (start, end) = (self.entry(from)?, self.entry(to)?);
+        let mut route = Vec::new();
+        if start != from {
+            route.push(start);
+        }
+        route.extend(self.route_inside(start, end)?);
+        if end != to {
+            route.push(to);
+        }
+        Some(route)
+    }
+
+    /// Finds the shortest route from `from` to `to`, which are both inside
+    /// the boundary, as for `route`.
+    fn route_inside(&mut self, from: Vector2<f32>, to: Vector2<f32>) -> Option<Vec<Vector2<f32>>> {
+        if self.is_visible(from, to) {
+            return Some(vec![to]);
+        }
+        if !self.boundary.contains_point(from) || !self.boundary.contains_point(to) {
+            return None;
+        }
+        // Work out what the endpoints can see before borrowing the graph.
+        let node_count = self.graph().0.len();
+        let (from_visible, to_visible): (Vec<_>, Vec<_>) = (0..node_count)
+            .map(|i| {
+                let node = self.graph.as_ref().unwrap().0[i];
+                (self.is_visible(from, node), self.is_visible(node, to))
+            })
+            .unzip();
+        let (nodes, visible) = self.graph.as_ref().unwrap();
+
+        // Dijkstra's algorithm, with `from` as node `n` and `to` as `n + 1`.
+        let n = nodes.len();
+        let (start, goal) = (n, n + 1);
+        let position = |i: usize| match i {
+            i if i == start => from,
+            i if i == goal => to,
+            i => nodes[i],
+        };
+        let neighbours = |i: usize| -> Vec<usize> {
+            if i == start {
+                (0..n).filter(|&j| from_visible[j]).collect()
+            } else {
+                let mut next = visible[i].clone();
+                if to_visible[i] {
+                    next.push(goal);
+                }
+                next
+            }
+        };
+        let mut distance = vec![f32::MAX; n + 2];
+        let mut previous = vec![usize::MAX; n + 2];
+        let mut queue = BinaryHeap::new();
+        distance[start] = 0.0;
+        queue.push(Visit {
+            distance: 0.0,
+            node: start,
+        });
+        while let Some(Visit { distance: d, node }) = queue.pop() {
+            if node == goal {
+                break;
+            }
+            if d > distance[node] {
+                continue;
+            }
+            for next in neighbours(node) {
+                let nd = d + (position(next) - position(node)).magnitude();
+                if nd < distance[next] {
+                    distance[next] = nd;
+                    previous[next] = node;
+                    queue.push(Visit {
+                        distance: nd,
+                        node: next,
+                    });
+                }
+            }
+        }
+        if previous[goal] == usize::MAX {
+            return None;
+        }
+        let mut route = Vec::new();
+        let mut node = goal;
+        while node != start {
+            route.push(position(node));
+            node = previous[node];
+        }
+        route.reverse();
+        Some(route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::polygon;
+
+    fn route_length(from: Vector2<f32>, route: &[Vector2<f32>]) -> f32 {
+        std::iter::once(from)
+            .chain(route.iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| (w[1] - w[0]).magnitude())
+            .sum()
+    }
+
+    #[test]
+    fn direct_route_when_visible() {
+        let region = Region::from_paths(&[polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+        ])]);
+        let mut router = CombingRouter::new(&region, 0.0, 0.2);
+        let to = Vector2::new(9.0, 9.0);
+        assert_eq!(router.route(Vector2::new(1.0, 1.0), to), Some(vec![to]));
+    }
+
+    #[test]
+    fn route_around_corner() {
+        // An L shape. Going from one arm to the other has to pass the inside
+        // corner at (2, 2).
+        let region = Region::from_paths(&[polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 10.0),
+            (0.0, 10.0),
+        ])]);
+        let mut router = CombingRouter::new(&region, 0.0, 0.2);
+        let (from, to) = (Vector2::new(9.0, 1.0), Vector2::new(1.0, 9.0));
+        assert!(!router.is_visible(from, to));
+
+        let route = router.route(from, to).unwrap();
+
+        assert_eq!(route.last(), Some(&to));
+        let mut position = from;
+        for &point in &route {
+            assert!(router.is_visible(position, point));
+            position = point;
+        }
+        // The route hugs the corner, so it's only a little longer than
+        // going through it.
+        let through_corner = 2.0 * (8.0f32.powi(2) + 1.0).sqrt();
+        assert!(route_length(from, &route) < through_corner + 0.5);
+    }
+
+    #[test]
+    fn no_route_between_islands() {
+        let region = Region::from_paths(&[
+            polygon(&[(0.0, 0.0), (5.0, 0.0), (5.0, 5.0), (0.0, 5.0)]),
+            polygon(&[(10.0, 0.0), (15.0, 0.0), (15.0, 5.0), (10.0, 5.0)]),
+        ]);
+        let mut router = CombingRouter::new(&region, 0.0, 0.2);
+        assert_eq!(
+            router.route(Vector2::new(1.0, 1.0), Vector2::new(11.0, 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn graph_only_has_inner_corners() {
+        // A square with a finely divided round hole in the middle.
+        let square = polygon(&[(0.0, 0.0), (20.0, 0.0), (20.0, 20.0), (0.0, 20.0)]);
+        let hole = Region::circle(Vector2::new(10.0, 10.0), 5.0, 256);
+        let region = Region::from_paths(&[square]).difference(&hole);
+        let mut router = CombingRouter::new(&region, 0.0, 0.2);
+        let (from, to) = (Vector2::new(1.0, 10.0), Vector2::new(19.0, 10.0));
+
+        let route = router.route(from, to).unwrap();
+
+        // None of the square's corners are needed, and the hole is
+        // simplified to far fewer corners.
+        let nodes = &router.graph().0;
+        assert!(nodes.len() < 64, "{} nodes", nodes.len());
+        assert!(nodes
+            .iter()
+            .all(|p| (p - Vector2::new(10.0, 10.0)).magnitude() < 6.0));
+        let mut position = from;
+        for &point in &route {
+            assert!(router.is_visible(position, point));
+            position = point;
+        }
+        // Around the hole is half its circumference, plus the way to it.
+        assert!(route_length(from, &route) < 4.0 + 5.2 * std::f32::consts::PI + 4.0);
+    }
+
+    #[test]
+    fn route_stays_off_outer_perimeters() {
+        // A U shape, travelling between the tips of its arms, which start on
+        // the outer perimeter.
+        let region = Region::from_paths(&[polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (7.0, 10.0),
+            (7.0, 3.0),
+            (3.0, 3.0),
+            (3.0, 10.0),
+            (0.0, 10.0),
+        ])]);
+        let mut router = CombingRouter::new(&region, 0.8, 0.2);
+        let (from, to) = (Vector2::new(1.5, 9.9), Vector2::new(8.5, 9.9));
+
+        let route = router.route(from, to).unwrap();
+
+        assert_eq!(route.last(), Some(&to));
+        // Apart from the hops on and off the ends of the arms, the whole
+        // route is inside the perimeters.
+        let inner = region.offset(-0.8);
+        assert!(route[..route.len() - 1]
+            .iter()
+            .all(|&p| inner.contains_point(p)));
+        let mut position = route[0];
+        for &point in &route[1..route.len() - 1] {
+            assert!(router.is_visible(position, point));
+            position = point;
+        }
+        // The hop onto the boundary goes straight in.
+        assert!((route[0] - Vector2::new(1.5, 9.0)).magnitude() < 0.01);
+    }
+}
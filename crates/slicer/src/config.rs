@@ -1,4 +1,878 @@
-#[derive(Default)]
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::gcode::GcodeFlavor;
+use crate::layer_heights::LayerHeightProfile;
+
+/// The fill pattern used for the interior of a layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InfillType {
+    /// Parallel lines that alternate between 45° and -45° on every layer.
+    #[default]
+    Rectilinear,
+    /// Lines at both 45° and -45° on every layer.
+    Grid,
+    /// Three sets of lines at 0°, 60° and 120° that form a triangular grid.
+    Triangles,
+    /// Wavy lines that trace a gyroid surface, giving similar strength in
+    /// every direction.
+    Gyroid,
+    /// Zig-zag lines that vary with Z to build a 3D honeycomb.
+    Honeycomb,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfillConfig {
+    pub pattern: InfillType,
+    /// Infill density as a percentage, from 0 (hollow) to 100 (solid).
+    pub density: f32,
+}
+
+impl Default for InfillConfig {
+    fn default() -> Self {
+        Self {
+            pattern: InfillType::default(),
+            density: 20.0,
+        }
+    }
+}
+
+/// Where each perimeter loop starts and ends.
+///
+/// The start of a loop leaves a small blemish, the seam, so the position
+/// controls where these show up on the surface of the model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeamPosition {
+    /// As close as possible to the seam on the layer below, so seams line
+    /// up into a single line.
+    #[default]
+    Aligned,
+    /// At the back of the model (+Y).
+    Rear,
+    /// In the sharpest concave corner, where the seam is least visible.
+    Hidden,
+    /// Scattered randomly around each loop.
+    Random,
+}
+
+/// How support material is shaped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupportStyle {
+    /// Columns that drop straight down from each overhang, filled with
+    /// parallel lines.
+    #[default]
+    Grid,
+    /// Thin branches that merge together as they grow down towards the bed
+    /// or the model. These use less material and are easier to remove.
+    Tree,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SupportConfig {
+    pub enabled: bool,
+    pub style: SupportStyle,
+    /// Overhangs steeper than this angle, in degrees from vertical, are
+    /// supported.
+    pub overhang_angle: f32,
+    /// The horizontal gap between support and the model, in mm.
+    pub xy_gap: f32,
+    /// The number of empty layers left between the top of the support and
+    /// the model resting on it.
+    pub z_gap_layers: usize,
+    /// The number of dense layers printed at the top of each support.
+    pub interface_layers: usize,
+    /// Support density as a percentage, from 0 to 100.
+    pub density: f32,
+}
+
+impl Default for SupportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            style: SupportStyle::default(),
+            overhang_angle: 45.0,
+            xy_gap: 0.6,
+            z_gap_layers: 1,
+            interface_layers: 2,
+            density: 15.0,
+        }
+    }
+}
+
+/// Where in each layer the mesh is cut to find the layer's outline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlicingPlane {
+    /// Halfway up the layer, so the outline is an average of the slope
+    /// through the layer.
+    #[default]
+    Middle,
+    /// At the top of the layer, where the nozzle is.
+    Top,
+}
+
+/// Settings for picking the height of each layer from the shape of the model.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveLayerConfig {
+    pub enabled: bool,
+    /// The thinnest layer that will be printed, in mm.
+    pub min_height: f32,
+    /// The thickest layer that will be printed, in mm.
+    pub max_height: f32,
+    /// How far the stair-stepped edges of layers may stick out from the true
+    /// surface of the model, in mm. Smaller values give smoother sloped
+    /// surfaces, with more layers.
+    pub max_deviation: f32,
+}
+
+impl Default for AdaptiveLayerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_height: 0.07,
+            max_height: 0.3,
+            max_deviation: 0.1,
+        }
+    }
+}
+
+/// How closely the outline of each layer follows the mesh.
+///
+/// Dense meshes slice into thousands of tiny segments per layer, which are
+/// slow to process and can stall the printer. Simplifying them removes
+/// detail that is too small to print.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResolutionConfig {
+    /// Points that are within this distance, in mm, of the line through
+    /// their neighbours are removed while joining the sliced segments.
+    pub collinear_tolerance: f32,
+    /// How far a simplified outline may stray from the sliced outline, in
+    /// mm. Zero disables simplification.
+    pub max_deviation: f32,
+    /// Segments shorter than this, in mm, are merged into their neighbours,
+    /// even if that moves the outline by more than `max_deviation`. Zero
+    /// keeps short segments.
+    pub min_segment_length: f32,
+}
+
+impl Default for ResolutionConfig {
+    fn default() -> Self {
+        Self {
+            collinear_tolerance: 0.0001,
+            max_deviation: 0.0125,
+            min_segment_length: 0.05,
+        }
+    }
+}
+
+/// The shape the skirt follows around the first layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkirtShape {
+    /// A convex hull around everything on the first layer.
+    #[default]
+    ConvexHull,
+    /// The outline of each island, so skirts stay close to the model.
+    Outline,
+}
+
+/// Settings for the skirt, brim and raft, which help the first layer stick to
+/// the bed.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdhesionConfig {
+    /// The number of skirt loops. Zero disables the skirt.
+    pub skirt_loops: usize,
+    /// The gap between the skirt and the model, in mm.
+    pub skirt_distance: f32,
+    pub skirt_shape: SkirtShape,
+    /// How far the brim extends out from the model, in mm. Zero disables the
+    /// brim.
+    pub brim_width: f32,
+    /// Only print the brim around sharp corners, as "ears".
+    pub brim_ears: bool,
+    /// Corners with an angle inside the model of less than this, in degrees,
+    /// get a brim ear.
+    pub brim_ear_max_angle: f32,
+    /// The total number of raft layers. Zero disables the raft.
+    pub raft_layers: usize,
+    /// The number of solid raft layers printed at the top of the raft.
+    pub raft_interface_layers: usize,
+    /// How far the raft extends out from the model, in mm.
+    pub raft_margin: f32,
+}
+
+impl Default for AdhesionConfig {
+    fn default() -> Self {
+        Self {
+            skirt_loops: 1,
+            skirt_distance: 6.0,
+            skirt_shape: SkirtShape::default(),
+            brim_width: 0.0,
+            brim_ears: false,
+            brim_ear_max_angle: 125.0,
+            raft_layers: 0,
+            raft_interface_layers: 2,
+            raft_margin: 3.0,
+        }
+    }
+}
+
+/// How extrusion distances are written in G-code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtrusionMode {
+    /// E values are the total length of filament extruded so far (M82).
+    #[default]
+    Absolute,
+    /// E values are the length of filament extruded by each move (M83).
+    Relative,
+}
+
+/// Print speeds for each type of move, in mm/s.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedrateConfig {
+    pub external_perimeter: f32,
+    pub perimeter: f32,
+    pub solid_infill: f32,
+    pub sparse_infill: f32,
+    pub bridge: f32,
+    pub support: f32,
+    pub support_interface: f32,
+    pub skirt_brim: f32,
+    pub raft: f32,
+    /// The speed of non-printing moves.
+    pub travel: f32,
+    /// The speed of moves along Z between layers.
+    pub z: f32,
+}
+
+impl Default for FeedrateConfig {
+    fn default() -> Self {
+        Self {
+            external_perimeter: 25.0,
+            perimeter: 40.0,
+            solid_infill: 40.0,
+            sparse_infill: 60.0,
+            bridge: 25.0,
+            support: 50.0,
+            support_interface: 40.0,
+            skirt_brim: 30.0,
+            raft: 30.0,
+            travel: 150.0,
+            z: 10.0,
+        }
+    }
+}
+
+/// Settings for replacing runs of short moves along curves with arc moves
+/// (G2/G3), which firmware can plan more smoothly.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArcFittingConfig {
+    pub enabled: bool,
+    /// How far an arc may stray from the path it replaces, in mm.
+    pub tolerance: f32,
+    /// Arcs shorter than this, in mm, are printed as straight moves.
+    pub min_arc_length: f32,
+}
+
+impl Default for ArcFittingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tolerance: 0.02,
+            min_arc_length: 0.5,
+        }
+    }
+}
+
+/// A value for each of the printer's axes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AxisLimits {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub e: f32,
+}
+
+impl AxisLimits {
+    /// The values in X, Y, Z, E order.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.e]
+    }
+}
+
+/// How the firmware limits the speed through the corner between two moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorneringModel {
+    /// Slows down so the nozzle could follow a curve that stays within
+    /// `junction_deviation` of the corner, as Marlin 2 and Klipper do.
+    #[default]
+    JunctionDeviation,
+    /// Allows the speed along each axis to change instantly by up to its
+    /// `max_jerk`, as older firmware does.
+    Jerk,
+}
+
+/// The printer's motion limits, as configured in its firmware. These are
+/// used to estimate how long a print takes.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MachineLimitsConfig {
+    /// The fastest speed along each axis, in mm/s.
+    pub max_feedrate: AxisLimits,
+    /// The fastest acceleration along each axis, in mm/s².
+    pub max_acceleration: AxisLimits,
+    /// The acceleration of moves when the G-code doesn't set one, in mm/s².
+    pub acceleration: f32,
+    /// The acceleration of retractions, in mm/s².
+    pub retract_acceleration: f32,
+    pub cornering: CorneringModel,
+    /// The largest instant change in speed along each axis, in mm/s. Only
+    /// used by `CorneringModel::Jerk`.
+    pub max_jerk: AxisLimits,
+    /// How far the path through a corner may cut inside of it, in mm. Only
+    /// used by `CorneringModel::JunctionDeviation`.
+    pub junction_deviation: f32,
+}
+
+impl Default for MachineLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_feedrate: AxisLimits {
+                x: 300.0,
+                y: 300.0,
+                z: 12.0,
+                e: 120.0,
+            },
+            max_acceleration: AxisLimits {
+                x: 3000.0,
+                y: 3000.0,
+                z: 200.0,
+                e: 5000.0,
+            },
+            acceleration: 1500.0,
+            retract_acceleration: 1500.0,
+            cornering: CorneringModel::default(),
+            max_jerk: AxisLimits {
+                x: 8.0,
+                y: 8.0,
+                z: 0.4,
+                e: 5.0,
+            },
+            junction_deviation: 0.013,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GcodeConfig {
+    pub flavor: GcodeFlavor,
+    /// The diameter of the filament, in mm.
+    pub filament_diameter: f32,
+    /// The density of the filament, in g/cm³.
+    pub filament_density: f32,
+    /// The price of the filament per kg.
+    pub filament_cost: f32,
+    pub extrusion_mode: ExtrusionMode,
+    pub feedrates: FeedrateConfig,
+    pub arc_fitting: ArcFittingConfig,
+    /// The acceleration used while printing, in mm/s². If unset the
+    /// firmware's default is used.
+    pub print_acceleration: Option<f32>,
+    /// The acceleration used for travel moves, in mm/s². If unset the
+    /// firmware's default is used.
+    pub travel_acceleration: Option<f32>,
+    /// The nozzle temperature, in °C.
+    pub nozzle_temperature: f32,
+    /// The bed temperature, in °C.
+    pub bed_temperature: f32,
+    /// G-code written before the first layer. This is copied into the output
+    /// as-is.
+    pub start_gcode: String,
+    /// G-code written after the last layer. This is copied into the output
+    /// as-is.
+    pub end_gcode: String,
+}
+
+impl Default for GcodeConfig {
+    fn default() -> Self {
+        Self {
+            flavor: GcodeFlavor::default(),
+            filament_diameter: 1.75,
+            filament_density: 1.24,
+            filament_cost: 0.0,
+            extrusion_mode: ExtrusionMode::default(),
+            feedrates: FeedrateConfig::default(),
+            arc_fitting: ArcFittingConfig::default(),
+            print_acceleration: None,
+            travel_acceleration: None,
+            nozzle_temperature: 210.0,
+            bed_temperature: 60.0,
+            start_gcode: "G28 ; home all axes\nG1 Z5 F3000 ; lift nozzle\n".to_string(),
+            end_gcode: "M104 S0 ; turn off hotend\nM140 S0 ; turn off bed\nM84 ; disable motors\n"
+                .to_string(),
+        }
+    }
+}
+
+/// When and how filament is pulled back out of the nozzle during travels.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetractionConfig {
+    /// The length of filament to retract, in mm. Zero disables retraction.
+    pub length: f32,
+    /// The speed filament is retracted and restored at, in mm/s.
+    pub speed: f32,
+    /// Travels shorter than this, in mm, don't retract.
+    pub min_travel: f32,
+    /// Skip retraction for travels that stay inside the layer, where any
+    /// oozing is hidden inside the model.
+    pub only_when_crossing_perimeters: bool,
+    /// Route travels around the inside of the layer where possible, rather
+    /// than straight across its perimeters.
+    pub avoid_crossing_perimeters: bool,
+    /// How far to lift the nozzle during retracted travels, in mm. Zero
+    /// disables Z-hop.
+    pub z_hop: f32,
+    /// How far to move the nozzle back along the last perimeter before
+    /// retracting, in mm. Zero disables wiping.
+    pub wipe_distance: f32,
+    /// Retract with G10/G11, and leave the length and speed to the firmware.
+    /// They're set once at the start of the print.
+    pub use_firmware_retraction: bool,
+}
+
+impl Default for RetractionConfig {
+    fn default() -> Self {
+        Self {
+            length: 0.8,
+            speed: 35.0,
+            min_travel: 2.0,
+            only_when_crossing_perimeters: true,
+            avoid_crossing_perimeters: true,
+            z_hop: 0.0,
+            wipe_distance: 0.0,
+            use_firmware_retraction: false,
+        }
+    }
+}
+
+/// How layers are cooled: the part cooling fan, and slowing down layers
+/// that print too quickly to cool before the next is put on top.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoolingConfig {
+    /// The fan speed for layers that take `fan_below_layer_time` or longer
+    /// to print, as a percentage.
+    pub min_fan_speed: f32,
+    /// The fan speed for layers that take `min_layer_time` or less to print,
+    /// as a percentage.
+    pub max_fan_speed: f32,
+    /// The fan speed while printing bridges and perimeters over overhangs,
+    /// as a percentage.
+    pub bridge_fan_speed: f32,
+    /// The number of layers at the bottom of the print that are printed with
+    /// the fan off, so they stick to the bed.
+    pub disable_fan_first_layers: usize,
+    /// Layers that print faster than this, in seconds, get more fan, up to
+    /// `max_fan_speed`.
+    pub fan_below_layer_time: f32,
+    /// Layers that print faster than this, in seconds, are slowed down to
+    /// take this long. Zero disables slowing down.
+    pub min_layer_time: f32,
+    /// The slowest speed that layers are slowed down to, in mm/s.
+    pub min_print_speed: f32,
+}
+
+impl Default for CoolingConfig {
+    fn default() -> Self {
+        Self {
+            min_fan_speed: 35.0,
+            max_fan_speed: 100.0,
+            bridge_fan_speed: 100.0,
+            disable_fan_first_layers: 1,
+            fan_below_layer_time: 60.0,
+            min_layer_time: 5.0,
+            min_print_speed: 10.0,
+        }
+    }
+}
+
+/// How objects are placed when they're arranged on the bed automatically.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArrangeConfig {
+    /// The gap left between neighbouring objects, in mm.
+    pub spacing: f32,
+    /// Objects are tried at every multiple of this rotation about the Z
+    /// axis, in degrees. Zero keeps objects at the rotation they're given.
+    pub rotation_step: f32,
+}
+
+impl Default for ArrangeConfig {
+    fn default() -> Self {
+        Self {
+            spacing: 6.0,
+            rotation_step: 90.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SlicerConfig {
+    /// The height of every layer, in mm, unless adaptive layers or a layer
+    /// height profile are used.
     pub layer_height: f32,
+    /// The height of the first layer, in mm. If unset, the first layer is
+    /// the same height as the others.
+    pub first_layer_height: Option<f32>,
+    pub slicing_plane: SlicingPlane,
+    pub adaptive_layers: AdaptiveLayerConfig,
+    pub resolution: ResolutionConfig,
+    /// Layer heights that vary with Z. This takes priority over
+    /// `layer_height` and `adaptive_layers`.
+    pub layer_height_profile: Option<LayerHeightProfile>,
+    /// The diameter of the nozzle, in mm.
+    pub nozzle_diameter: f32,
+    /// The corners of the printable area of the bed, in mm.
+    pub bed_shape: Vec<(f32, f32)>,
+    /// The width of a single extruded line, in mm.
+    pub extrusion_width: f32,
+    /// The number of perimeter loops to generate around every island.
+    pub perimeters: usize,
+    pub seam_position: SeamPosition,
+    /// The number of solid layers printed under the top surfaces.
+    pub top_layers: usize,
+    /// The number of solid layers printed over the bottom surfaces.
+    pub bottom_layers: usize,
+    /// Prints the model as a vase: after the first `bottom_layers` layers,
+    /// only the outside of each layer is printed, as one continuous spiral
+    /// with no infill or top layers.
+    pub spiral_vase: bool,
+    pub infill: InfillConfig,
+    pub support: SupportConfig,
+    pub adhesion: AdhesionConfig,
+    pub gcode: GcodeConfig,
+    pub retraction: RetractionConfig,
+    pub cooling: CoolingConfig,
+    pub machine_limits: MachineLimitsConfig,
+    pub arrange: ArrangeConfig,
+}
+
+impl Default for SlicerConfig {
+    fn default() -> Self {
+        Self {
+            layer_height: 0.2,
+            first_layer_height: None,
+            slicing_plane: SlicingPlane::default(),
+            adaptive_layers: AdaptiveLayerConfig::default(),
+            resolution: ResolutionConfig::default(),
+            layer_height_profile: None,
+            nozzle_diameter: 0.4,
+            bed_shape: vec![(0.0, 0.0), (220.0, 0.0), (220.0, 220.0), (0.0, 220.0)],
+            extrusion_width: 0.45,
+            perimeters: 2,
+            seam_position: SeamPosition::default(),
+            top_layers: 3,
+            bottom_layers: 3,
+            spiral_vase: false,
+            infill: InfillConfig::default(),
+            support: SupportConfig::default(),
+            adhesion: AdhesionConfig::default(),
+            gcode: GcodeConfig::default(),
+            retraction: RetractionConfig::default(),
+            cooling: CoolingConfig::default(),
+            machine_limits: MachineLimitsConfig::default(),
+            arrange: ArrangeConfig::default(),
+        }
+    }
+}
+
+/// The version of the configuration file format.
+///
+/// This only changes when a setting is renamed, removed or changes meaning,
+/// so files written by older versions keep loading the same way.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The largest layer height that can be printed reliably, as a fraction of
+/// the nozzle diameter.
+const MAX_LAYER_HEIGHT_RATIO: f32 = 0.8;
+
+/// A setting with a value that can't be printed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The path of the setting, such as `infill.density`.
+    pub setting: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.setting, self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl SlicerConfig {
+    /// Checks that the settings make sense together, returning every
+    /// problem found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, setting: &str, message: String| {
+            if !ok {
+                errors.push(ValidationError {
+                    setting: setting.to_string(),
+                    message,
+                });
+            }
+        };
+        let positive = |value: f32| value > 0.0 && value.is_finite();
+        let percentage = |value: f32| (0.0..=100.0).contains(&value);
+
+        check(
+            positive(self.nozzle_diameter),
+            "nozzle_diameter",
+            "must be greater than zero".to_string(),
+        );
+        check(
+            self.bed_shape.len() >= 3,
+            "bed_shape",
+            "must have at least 3 corners".to_string(),
+        );
+        let max_height = self.nozzle_diameter * MAX_LAYER_HEIGHT_RATIO;
+        let mut check_height = |height: f32, setting: &str| {
+            if !positive(height) {
+                check(false, setting, "must be greater than zero".to_string());
+            } else {
+                check(
+                    height <= max_height,
+                    setting,
+                    format!(
+                        "{}mm is more than {}% of the {}mm nozzle diameter",
+                        height,
+                        MAX_LAYER_HEIGHT_RATIO * 100.0,
+                        self.nozzle_diameter
+                    ),
+                );
+            }
+        };
+        check_height(self.layer_height, "layer_height");
+        if let Some(height) = self.first_layer_height {
+            check_height(height, "first_layer_height");
+        }
+        if self.adaptive_layers.enabled {
+            check_height(
+                self.adaptive_layers.min_height,
+                "adaptive_layers.min_height",
+            );
+            check_height(
+                self.adaptive_layers.max_height,
+                "adaptive_layers.max_height",
+            );
+        }
+        if let Some(profile) = &self.layer_height_profile {
+            for &(_, height) in profile.points() {
+                check_height(height, "layer_height_profile");
+            }
+        }
+        check(
+            self.adaptive_layers.min_height <= self.adaptive_layers.max_height,
+            "adaptive_layers.min_height",
+            "must not be more than adaptive_layers.max_height".to_string(),
+        );
+        for (value, setting) in [
+            (
+                self.resolution.collinear_tolerance,
+                "resolution.collinear_tolerance",
+            ),
+            (self.resolution.max_deviation, "resolution.max_deviation"),
+            (
+                self.resolution.min_segment_length,
+                "resolution.min_segment_length",
+            ),
+        ] {
+            check(value >= 0.0, setting, "must not be negative".to_string());
+        }
+        check(
+            positive(self.extrusion_width),
+            "extrusion_width",
+            "must be greater than zero".to_string(),
+        );
+        check(
+            percentage(self.infill.density),
+            "infill.density",
+            "must be between 0 and 100".to_string(),
+        );
+        check(
+            percentage(self.support.density),
+            "support.density",
+            "must be between 0 and 100".to_string(),
+        );
+        check(
+            self.support.overhang_angle > 0.0 && self.support.overhang_angle < 90.0,
+            "support.overhang_angle",
+            "must be between 0 and 90 degrees".to_string(),
+        );
+        check(
+            !self.gcode.arc_fitting.enabled || positive(self.gcode.arc_fitting.tolerance),
+            "gcode.arc_fitting.tolerance",
+            "must be greater than zero".to_string(),
+        );
+        check(
+            positive(self.gcode.filament_diameter),
+            "gcode.filament_diameter",
+            "must be greater than zero".to_string(),
+        );
+        for (value, setting) in [
+            (self.gcode.filament_density, "gcode.filament_density"),
+            (self.gcode.filament_cost, "gcode.filament_cost"),
+        ] {
+            check(value >= 0.0, setting, "must not be negative".to_string());
+        }
+        let limits = &self.machine_limits;
+        for (axes, setting) in [
+            (limits.max_feedrate, "machine_limits.max_feedrate"),
+            (limits.max_acceleration, "machine_limits.max_acceleration"),
+        ] {
+            check(
+                axes.to_array().into_iter().all(positive),
+                setting,
+                "must be greater than zero on every axis".to_string(),
+            );
+        }
+        for (value, setting) in [
+            (limits.acceleration, "machine_limits.acceleration"),
+            (
+                limits.retract_acceleration,
+                "machine_limits.retract_acceleration",
+            ),
+        ] {
+            check(
+                positive(value),
+                setting,
+                "must be greater than zero".to_string(),
+            );
+        }
+        check(
+            limits.cornering != CorneringModel::Jerk
+                || limits.max_jerk.to_array().into_iter().all(positive),
+            "machine_limits.max_jerk",
+            "must be greater than zero on every axis".to_string(),
+        );
+        check(
+            limits.cornering != CorneringModel::JunctionDeviation
+                || positive(limits.junction_deviation),
+            "machine_limits.junction_deviation",
+            "must be greater than zero".to_string(),
+        );
+        let retraction = &self.retraction;
+        for (value, setting) in [
+            (retraction.length, "retraction.length"),
+            (retraction.min_travel, "retraction.min_travel"),
+            (retraction.z_hop, "retraction.z_hop"),
+            (retraction.wipe_distance, "retraction.wipe_distance"),
+        ] {
+            check(value >= 0.0, setting, "must not be negative".to_string());
+        }
+        check(
+            positive(retraction.speed),
+            "retraction.speed",
+            "must be greater than zero".to_string(),
+        );
+        let feedrates = &self.gcode.feedrates;
+        for (value, setting) in [
+            (
+                feedrates.external_perimeter,
+                "gcode.feedrates.external_perimeter",
+            ),
+            (feedrates.perimeter, "gcode.feedrates.perimeter"),
+            (feedrates.solid_infill, "gcode.feedrates.solid_infill"),
+            (feedrates.sparse_infill, "gcode.feedrates.sparse_infill"),
+            (feedrates.bridge, "gcode.feedrates.bridge"),
+            (feedrates.support, "gcode.feedrates.support"),
+            (
+                feedrates.support_interface,
+                "gcode.feedrates.support_interface",
+            ),
+            (feedrates.skirt_brim, "gcode.feedrates.skirt_brim"),
+            (feedrates.raft, "gcode.feedrates.raft"),
+            (feedrates.travel, "gcode.feedrates.travel"),
+            (feedrates.z, "gcode.feedrates.z"),
+        ] {
+            check(
+                positive(value),
+                setting,
+                "must be greater than zero".to_string(),
+            );
+        }
+        for (value, setting) in [
+            (self.gcode.print_acceleration, "gcode.print_acceleration"),
+            (self.gcode.travel_acceleration, "gcode.travel_acceleration"),
+        ] {
+            check(
+                value.is_none_or(positive),
+                setting,
+                "must be greater than zero".to_string(),
+            );
+        }
+        let cooling = &self.cooling;
+        for (value, setting) in [
+            (cooling.min_fan_speed, "cooling.min_fan_speed"),
+            (cooling.max_fan_speed, "cooling.max_fan_speed"),
+            (cooling.bridge_fan_speed, "cooling.bridge_fan_speed"),
+        ] {
+            check(
+                percentage(value),
+                setting,
+                "must be between 0 and 100".to_string(),
+            );
+        }
+        check(
+            cooling.min_layer_time >= 0.0,
+            "cooling.min_layer_time",
+            "must not be negative".to_string(),
+        );
+        check(
+            cooling.fan_below_layer_time >= cooling.min_layer_time,
+            "cooling.fan_below_layer_time",
+            "must not be less than cooling.min_layer_time".to_string(),
+        );
+        check(
+            positive(cooling.min_print_speed),
+            "cooling.min_print_speed",
+            "must be greater than zero".to_string(),
+        );
+        check(
+            self.arrange.spacing >= 0.0,
+            "arrange.spacing",
+            "must not be negative".to_string(),
+        );
+        check(
+            (0.0..360.0).contains(&self.arrange.rotation_step),
+            "arrange.rotation_step",
+            "must be at least 0 and less than 360".to_string(),
+        );
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
-use cgmath::Vector2;
+use cgmath::{InnerSpace, Vector2};
+use ordered_float::OrderedFloat;
 
+use crate::config::ResolutionConfig;
+use crate::simplify::simplify_paths;
 use crate::{OrderedVec2, SegmentMap};
 
 pub struct ClosedPath {
@@ -32,6 +35,10 @@ impl ClosedPath {
         Self { path: Vec::new() }
     }
 
+    pub fn from_points(points: Vec<Vector2<f32>>) -> Self {
+        Self { path: points }
+    }
+
     pub fn add_point(&mut self, x: f32, y: f32) {
         self.path.push(Vector2 { x, y })
     }
@@ -44,7 +51,7 @@ impl ClosedPath {
         &mut self.path
     }
 
-    pub fn segments(&self) -> Segments {
+    pub fn segments(&self) -> Segments<'_> {
         let mut iter = self.path.iter();
         let start = iter.next().cloned();
         Segments {
@@ -86,6 +93,56 @@ impl Default for ClosedPath {
     }
 }
 
+/// An open path through a sequence of points.
+///
+/// Unlike a `ClosedPath`, the last point is not connected back to the first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polyline {
+    points: Vec<Vector2<f32>>,
+}
+
+impl Polyline {
+    pub fn new() -> Self {
+        Self { points: Vec::new() }
+    }
+
+    pub fn from_points(points: Vec<Vector2<f32>>) -> Self {
+        Self { points }
+    }
+
+    pub fn add_point(&mut self, x: f32, y: f32) {
+        self.points.push(Vector2 { x, y })
+    }
+
+    pub fn points(&self) -> &[Vector2<f32>] {
+        self.points.as_slice()
+    }
+
+    pub fn points_vec(&mut self) -> &mut Vec<Vector2<f32>> {
+        &mut self.points
+    }
+
+    pub fn first(&self) -> Option<Vector2<f32>> {
+        self.points.first().cloned()
+    }
+
+    pub fn last(&self) -> Option<Vector2<f32>> {
+        self.points.last().cloned()
+    }
+
+    pub fn reverse(&mut self) {
+        self.points.reverse()
+    }
+
+    /// The total length of all segments in this path.
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|w| (w[1] - w[0]).magnitude())
+            .sum()
+    }
+}
+
 pub struct Contour {
     paths: Vec<ClosedPath>,
     // The low/high point in this contour.
@@ -117,6 +174,16 @@ impl Contour {
     pub fn limits_y(&self) -> (f32, f32) {
         self.limits_y
     }
+
+    /// Returns a copy of the contour with points removed that add less
+    /// detail than `resolution` asks for. See `ResolutionConfig`.
+    pub fn simplify(&self, resolution: &ResolutionConfig) -> Contour {
+        Contour {
+            paths: simplify_paths(&self.paths, resolution),
+            limits_x: self.limits_x,
+            limits_y: self.limits_y,
+        }
+    }
 }
 
 impl Default for Contour {
@@ -125,12 +192,38 @@ impl Default for Contour {
     }
 }
 
-fn is_parallel<A: Into<f32>, B: Into<f32>>(v0: Vector2<A>, v1: Vector2<B>) -> bool {
-    ((v0.x.into() * v1.y.into()) - (v0.y.into() * v1.x.into())) == 0.0
+/// Returns true if the point at `offset` from a line's origin is within
+/// `tolerance` of the line, which runs in `direction`.
+fn is_on_line<A: Into<f32>, B: Into<f32>>(
+    direction: Vector2<A>,
+    offset: Vector2<B>,
+    tolerance: f32,
+) -> bool {
+    let direction = Vector2::new(direction.x.into(), direction.y.into());
+    let cross = (direction.x * offset.y.into()) - (direction.y * offset.x.into());
+    cross.abs() <= tolerance * direction.magnitude()
+}
+
+/// Returns true if every point of `points` is within `tolerance` of the line
+/// from `start` to `end`.
+fn all_on_line(
+    start: OrderedVec2,
+    end: OrderedVec2,
+    points: &[OrderedVec2],
+    tolerance: f32,
+) -> bool {
+    points
+        .iter()
+        .all(|&p| is_on_line(end - start, p - start, tolerance))
 }
 
 impl Contour {
-    pub fn from_segment_map(mut map: SegmentMap) -> Self {
+    /// Joins sliced segments into closed paths.
+    ///
+    /// Points are dropped while every one of them stays within
+    /// `collinear_tolerance` of the edge that replaces them, so straight
+    /// edges made up of many segments become a single segment.
+    pub fn from_segment_map(mut map: SegmentMap, collinear_tolerance: f32) -> Self {
         let mut paths = Contour::new();
         if map.is_empty() {
             return paths;
@@ -150,8 +243,10 @@ impl Contour {
         let mut current_path = ClosedPath::new();
         let (mut segment_start, mut segment_end) = take_point(&mut map).unwrap();
 
-        // The vector from p0 -> p1.
-        let mut segment_direction = segment_end - segment_start;
+        // The points dropped since `segment_start`, and the points dropped
+        // between the path's start and its second point.
+        let mut dropped = Vec::new();
+        let mut leading = Vec::new();
         let mut path_start = segment_start;
         current_path.add_point(segment_start.x.0, segment_start.y.0);
         let mut x_limits = (0., 0.);
@@ -168,17 +263,16 @@ impl Contour {
             let p1 = segment_end;
             let p2 = next;
 
-            // And we consider to direction vectors:
-            //    > vp0_p1 - The direction vector from p0 -> p1
-            //    > vp0_p2 - The direction vector from p0 -> p2
-            let vp0_p1 = segment_direction;
-            let vp0_p2 = p2 - p0;
-
-            // We check if points p0, p1, p2 all lie on a line. If they are all colinear then
-            // we can delete point p1 since it will be represented by the line p0->p2. If they
-            // are not parallel then we add p1 to our path and update our segment to now be
-            // p1 -> p2.
-            if !is_parallel(vp0_p1, vp0_p2) {
+            // We check if p1, and every point dropped before it, lies on the line p0->p2. If
+            // so we can delete point p1 since it will be represented by the line p0->p2.
+            // Otherwise we add p1 to our path and update our segment to now be p1 -> p2.
+            dropped.push(p1);
+            if all_on_line(p0, p2, &dropped, collinear_tolerance) {
+                if current_path.points().len() == 1 {
+                    leading.push(p1);
+                }
+            } else {
+                dropped.clear();
                 current_path.add_point(p1.x.0, p1.y.0);
                 // Track the contour limits while assembling.
                 if p1.x.0 < x_limits.0 {
@@ -194,7 +288,6 @@ impl Contour {
                     y_limits.1 = p1.y.0
                 }
                 segment_start = p1;
-                segment_direction = p2 - p1;
             }
             segment_end = p2;
 
@@ -210,9 +303,12 @@ impl Contour {
                 //
                 // Here, p2 is our path start, but that point can be removed if we instead update the path start to
                 // be p1 instead.
-                let vp1_p2 = p2 - p1;
-                let vp3_p2 = current_path.points()[1] - current_path.points()[0];
-                if is_parallel(vp3_p2, vp1_p2) {
+                //
+                // Every point dropped on either side of p2 has to stay on the line too.
+                let p3 = current_path.points()[1].map(OrderedFloat);
+                dropped.push(p2);
+                dropped.append(&mut leading);
+                if all_on_line(segment_start, p3, &dropped, collinear_tolerance) {
                     let last = current_path.points().len() - 1;
                     current_path.points_vec().remove(last);
                     current_path.points_vec()[0] = Vector2 {
@@ -226,7 +322,7 @@ impl Contour {
                 if let Some((start, end)) = take_point(&mut map) {
                     segment_start = start;
                     segment_end = end;
-                    segment_direction = end - start;
+                    dropped.clear();
                     path_start = segment_start;
                     current_path = ClosedPath::new();
                     current_path.add_point(segment_start.x.0, segment_start.y.0);
@@ -245,7 +341,7 @@ impl Contour {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ordered_float::OrderedFloat;
+    use float_eq::assert_float_eq;
 
     #[test]
     fn build_contour() {
@@ -271,7 +367,7 @@ mod tests {
         map.insert(p2, p3);
         map.insert(p3, p0);
 
-        let contour = Contour::from_segment_map(map);
+        let contour = Contour::from_segment_map(map, 0.0);
 
         assert_eq!(contour.paths().len(), 1);
 
@@ -279,6 +375,41 @@ mod tests {
         assert_eq!(segments.len(), 4);
     }
 
+    #[test]
+    fn dropped_points_stay_near_edge() {
+        // The bottom edge wobbles by less than the tolerance from the x axis,
+        // but the points in between don't all lie on the line from its start
+        // to its end.
+        let points = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.09),
+            (10.0, -0.09),
+            (10.0, 10.0),
+            (0.0, 10.0),
+        ];
+        let points = points.map(|(x, y)| Vector2::new(OrderedFloat(x), OrderedFloat(y)));
+        let mut map = SegmentMap::new();
+        for (i, &p) in points.iter().enumerate() {
+            map.insert(p, points[(i + 1) % points.len()]);
+        }
+
+        let contour = Contour::from_segment_map(map, 0.1);
+
+        let path = &contour.paths()[0];
+        for p in points.map(|p| p.map(|c| c.0)) {
+            let distance = path
+                .segments()
+                .map(|(a, b)| {
+                    let (a, b) = (Vector2::new(a.0, a.1), Vector2::new(b.0, b.1));
+                    let t = ((p - a).dot(b - a) / (b - a).magnitude2()).clamp(0.0, 1.0);
+                    (a + (b - a) * t - p).magnitude()
+                })
+                .fold(f32::MAX, f32::min);
+            assert_float_eq!(distance, 0.0, abs <= 0.1);
+        }
+    }
+
     #[test]
     fn segment_iterator() {
         let mut path = ClosedPath::new();
@@ -0,0 +1,140 @@
+use crate::config::CoolingConfig;
+use crate::toolpath::ToolpathLayer;
+
+use super::PrintEstimate;
+
+/// How a single layer is cooled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayerCooling {
+    /// What extrusion speeds are multiplied by so the layer takes long
+    /// enough to cool, from 0 to 1.
+    pub speed_factor: f32,
+    /// The part cooling fan speed, from 0 to 1.
+    pub fan_speed: f32,
+    /// The fan speed while printing bridges and overhangs, from 0 to 1.
+    pub bridge_fan_speed: f32,
+}
+
+impl LayerCooling {
+    /// Works out the cooling for the `index`th layer, which takes `time`
+    /// seconds to print at full speed, `travel_time` of which is spent on
+    /// travels that aren't slowed down.
+    pub fn new(index: usize, time: f32, travel_time: f32, config: &CoolingConfig) -> Self {
+        let extrusion_time = time - travel_time;
+        let speed_factor = if time < config.min_layer_time && extrusion_time > 0.0 {
+            (extrusion_time / (config.min_layer_time - travel_time)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        if index < config.disable_fan_first_layers {
+            return Self {
+                speed_factor,
+                fan_speed: 0.0,
+                bridge_fan_speed: 0.0,
+            };
+        }
+        // The fan speeds up from its minimum to its maximum as the layer time
+        // drops from `fan_below_layer_time` to `min_layer_time`.
+        let fan_speed = if time >= config.fan_below_layer_time {
+            config.min_fan_speed
+        } else if time <= config.min_layer_time {
+            config.max_fan_speed
+        } else {
+            let t = (config.fan_below_layer_time - time)
+                / (config.fan_below_layer_time - config.min_layer_time);
+            config.min_fan_speed + (config.max_fan_speed - config.min_fan_speed) * t
+        };
+        Self {
+            speed_factor,
+            fan_speed: fan_speed / 100.0,
+            bridge_fan_speed: config.bridge_fan_speed / 100.0,
+        }
+    }
+}
+
+/// Works out the cooling for each of `layers`, from `estimate`, an estimate
+/// of how long each layer takes to print at full speed.
+pub fn plan_cooling(
+    layers: &[ToolpathLayer],
+    estimate: &PrintEstimate,
+    config: &CoolingConfig,
+) -> Vec<LayerCooling> {
+    layers
+        .iter()
+        .map(|layer| {
+            let time = estimate.layer_times.get(layer.index).copied();
+            let travel_time = estimate.layer_travel_times.get(layer.index).copied();
+            LayerCooling::new(
+                layer.index,
+                time.unwrap_or(0.0),
+                travel_time.unwrap_or(0.0),
+                config,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn slow_layers_are_not_changed() {
+        let config = CoolingConfig::default();
+
+        let cooling = LayerCooling::new(5, 120.0, 10.0, &config);
+
+        assert_eq!(cooling.speed_factor, 1.0);
+        assert_float_eq!(cooling.fan_speed, 0.35, abs <= 1e-6);
+        assert_float_eq!(cooling.bridge_fan_speed, 1.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn fast_layers_slow_down() {
+        let config = CoolingConfig {
+            min_layer_time: 10.0,
+            ..Default::default()
+        };
+
+        // 4s of extrusion and 1s of travel.
+        let cooling = LayerCooling::new(5, 5.0, 1.0, &config);
+
+        // Extruding for 9s instead of 4s makes the layer take 10s.
+        assert_float_eq!(cooling.speed_factor, 4.0 / 9.0, abs <= 1e-6);
+        assert_float_eq!(cooling.fan_speed, 1.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn fan_ramps_between_thresholds() {
+        let config = CoolingConfig {
+            min_fan_speed: 20.0,
+            max_fan_speed: 80.0,
+            fan_below_layer_time: 30.0,
+            min_layer_time: 10.0,
+            ..Default::default()
+        };
+
+        let cooling = LayerCooling::new(5, 25.0, 0.0, &config);
+
+        assert_eq!(cooling.speed_factor, 1.0);
+        assert_float_eq!(cooling.fan_speed, 0.35, abs <= 1e-6);
+    }
+
+    #[test]
+    fn fan_off_for_first_layers() {
+        let config = CoolingConfig {
+            disable_fan_first_layers: 2,
+            ..Default::default()
+        };
+
+        let first = LayerCooling::new(1, 1.0, 0.0, &config);
+        let third = LayerCooling::new(2, 1.0, 0.0, &config);
+
+        assert_eq!(first.fan_speed, 0.0);
+        assert_eq!(first.bridge_fan_speed, 0.0);
+        assert!(first.speed_factor < 1.0);
+        assert_eq!(third.fan_speed, 1.0);
+    }
+}
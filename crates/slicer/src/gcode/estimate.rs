@@ -0,0 +1,657 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::config::{CorneringModel, MachineLimitsConfig, SlicerConfig};
+use crate::toolpath::{FeatureType, ToolpathLayer};
+
+use super::{extrusion_length, feature_feedrate, format_number};
+
+/// Firmware splits arcs into straight segments of about this length, in mm,
+/// before planning them.
+pub const ARC_SEGMENT_LENGTH: f32 = 1.0;
+
+/// A straight move, as the firmware's motion planner sees it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PlannedMove {
+    /// The distance moved along each axis, in X, Y, Z, E order, in mm.
+    delta: [f32; 4],
+    /// The requested speed, in mm/s.
+    feedrate: f32,
+    /// The acceleration set by the G-code, in mm/s².
+    acceleration: f32,
+    /// What the move prints, or `None` for travels and retractions.
+    feature: Option<FeatureType>,
+    /// The layer the move is part of, or `None` before the first layer.
+    layer: Option<usize>,
+}
+
+/// A move once its speed limits are known.
+struct Block {
+    length: f32,
+    /// The direction of the move along each axis, as a fraction of
+    /// `length`.
+    unit: [f32; 4],
+    /// The fastest the move can go, in mm/s.
+    nominal_speed: f32,
+    acceleration: f32,
+}
+
+impl Block {
+    /// Returns `None` for moves that don't go anywhere.
+    fn new(m: &PlannedMove, limits: &MachineLimitsConfig) -> Option<Self> {
+        let [x, y, z, e] = m.delta;
+        let xyz = (x * x + y * y + z * z).sqrt();
+        // Moves of the extruder alone are measured along E.
+        let length = if xyz > 0.0 { xyz } else { e.abs() };
+        if length <= 0.0 {
+            return None;
+        }
+        let unit = m.delta.map(|d| d / length);
+        let mut nominal_speed = m.feedrate;
+        let mut acceleration = if xyz > 0.0 {
+            m.acceleration
+        } else {
+            limits.retract_acceleration
+        };
+        // Each axis moves at its share of the speed and acceleration of the
+        // whole move, so the axis limits scale up by the inverse of that
+        // share.
+        let max_feedrate = limits.max_feedrate.to_array();
+        let max_acceleration = limits.max_acceleration.to_array();
+        for (axis, &u) in unit.iter().enumerate() {
+            if u != 0.0 {
+                nominal_speed = nominal_speed.min(max_feedrate[axis] / u.abs());
+                acceleration = acceleration.min(max_acceleration[axis] / u.abs());
+            }
+        }
+        Some(Self {
+            length,
+            unit,
+            nominal_speed,
+            acceleration,
+        })
+    }
+
+    /// The direction of the move in XYZ, or `None` for moves of the
+    /// extruder alone.
+    fn direction(&self) -> Option<[f32; 3]> {
+        let [x, y, z, _] = self.unit;
+        let length = (x * x + y * y + z * z).sqrt();
+        (length > 0.0).then(|| [x / length, y / length, z / length])
+    }
+}
+
+/// Returns the fastest speed the nozzle can pass from `from` into `to` at,
+/// where either may be `None` to start or finish at rest.
+fn junction_speed(from: Option<&Block>, to: Option<&Block>, limits: &MachineLimitsConfig) -> f32 {
+    let nominal_speed = match (from, to) {
+        (Some(a), Some(b)) => a.nominal_speed.min(b.nominal_speed),
+        (Some(block), None) | (None, Some(block)) => block.nominal_speed,
+        (None, None) => return 0.0,
+    };
+    match limits.cornering {
+        CorneringModel::Jerk => {
+            // Every axis can jump between speeds by up to its jerk limit.
+            let unit = |block: Option<&Block>| block.map_or([0.0; 4], |b| b.unit);
+            let (from, to) = (unit(from), unit(to));
+            let max_jerk = limits.max_jerk.to_array();
+            (0..4)
+                .filter(|&axis| from[axis] != to[axis])
+                .map(|axis| max_jerk[axis] / (to[axis] - from[axis]).abs())
+                .fold(nominal_speed, f32::min)
+        }
+        CorneringModel::JunctionDeviation => {
+            let (Some(from), Some(to)) = (from, to) else {
+                return 0.0;
+            };
+            let (Some(a), Some(b)) = (from.direction(), to.direction()) else {
+                return 0.0;
+            };
+            let cos_theta = -(0..3).map(|i| a[i] * b[i]).sum::<f32>();
+            if cos_theta > 0.999_999 {
+                // The move reverses direction, so has to stop.
+                return 0.0;
+            }
+            if cos_theta < -0.999_999 {
+                // The moves are in a straight line.
+                return nominal_speed;
+            }
+            // The speed around a circle that turns from one move to the
+            // other, and passes within `junction_deviation` of the corner.
+            let sin_theta_d2 = (0.5 * (1.0 - cos_theta)).sqrt();
+            let speed = (to.acceleration * limits.junction_deviation * sin_theta_d2
+                / (1.0 - sin_theta_d2))
+                .sqrt();
+            speed.min(nominal_speed)
+        }
+    }
+}
+
+/// Returns the time taken to move `length` mm at up to `nominal_speed`,
+/// entering at `entry` and leaving at `exit` mm/s, and changing speed at
+/// `acceleration`.
+fn trapezoid_time(
+    entry: f32,
+    exit: f32,
+    nominal_speed: f32,
+    acceleration: f32,
+    length: f32,
+) -> f32 {
+    let accelerate = (nominal_speed.powi(2) - entry.powi(2)) / (2.0 * acceleration);
+    let decelerate = (nominal_speed.powi(2) - exit.powi(2)) / (2.0 * acceleration);
+    if accelerate + decelerate <= length {
+        let cruise = length - accelerate - decelerate;
+        (nominal_speed - entry) / acceleration
+            + (nominal_speed - exit) / acceleration
+            + cruise / nominal_speed
+    } else {
+        // The move never reaches its nominal speed, so the speed peaks where
+        // acceleration turns into deceleration.
+        let peak = ((2.0 * acceleration * length + entry.powi(2) + exit.powi(2)) / 2.0).sqrt();
+        (peak - entry) / acceleration + (peak - exit) / acceleration
+    }
+}
+
+/// The estimated time and material used by a print.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrintEstimate {
+    /// The total print time, in seconds.
+    pub time: f32,
+    /// The time spent printing each feature, in seconds, in the order they
+    /// are first printed.
+    pub feature_times: Vec<(FeatureType, f32)>,
+    /// The time spent on travels, retractions and moves between layers, in
+    /// seconds.
+    pub travel_time: f32,
+    /// The time each layer takes, in seconds, by layer index.
+    pub layer_times: Vec<f32>,
+    /// The part of `layer_times` spent on travels and retractions, in
+    /// seconds.
+    pub layer_travel_times: Vec<f32>,
+    /// The length of filament used, in mm.
+    pub filament_length: f32,
+    /// The weight of filament used, in g.
+    pub filament_weight: f32,
+    /// The price of the filament used.
+    pub filament_cost: f32,
+}
+
+/// Formats a time in seconds like `1h 2m 3s`.
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+impl PrintEstimate {
+    /// Returns the estimate as G-code comments, for the top of the file.
+    pub fn header(&self) -> String {
+        let mut lines = vec![format!(
+            "; estimated printing time = {}",
+            format_duration(self.time)
+        )];
+        for (feature, time) in &self.feature_times {
+            lines.push(format!(
+                "; estimated time for {} = {}",
+                feature,
+                format_duration(*time)
+            ));
+        }
+        lines.push(format!(
+            "; estimated time for travel = {}",
+            format_duration(self.travel_time)
+        ));
+        // A time for every layer would make one very long line, so only
+        // their range is given.
+        if !self.layer_times.is_empty() {
+            let times = &self.layer_times;
+            let min = times.iter().copied().fold(f32::MAX, f32::min);
+            let max = times.iter().copied().fold(0.0, f32::max);
+            let average = times.iter().sum::<f32>() / times.len() as f32;
+            lines.push(format!(
+                "; layer times [s] = min {}, max {}, average {}",
+                format_number(min, 1),
+                format_number(max, 1),
+                format_number(average, 1)
+            ));
+        }
+        lines.push(format!(
+            "; filament used [mm] = {}",
+            format_number(self.filament_length, 2)
+        ));
+        lines.push(format!(
+            "; filament used [g] = {}",
+            format_number(self.filament_weight, 2)
+        ));
+        lines.push(format!(
+            "; filament cost = {}",
+            format_number(self.filament_cost, 2)
+        ));
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+}
+
+/// Estimates how long a print takes by simulating the firmware's motion
+/// planner.
+///
+/// Moves are planned like Marlin and Klipper do: each one accelerates up to
+/// its speed, cruises, then slows down to a speed it can take the corner
+/// into the next move at, giving a trapezoidal speed profile. Unlike
+/// firmware, which only looks a few moves ahead, the whole print is planned
+/// at once.
+pub struct PrintEstimator<'a> {
+    config: &'a SlicerConfig,
+    moves: Vec<PlannedMove>,
+    acceleration: f32,
+    layer: Option<usize>,
+}
+
+impl<'a> PrintEstimator<'a> {
+    pub fn new(config: &'a SlicerConfig) -> Self {
+        Self {
+            config,
+            moves: Vec::new(),
+            acceleration: config.machine_limits.acceleration,
+            layer: None,
+        }
+    }
+
+    /// Sets the acceleration of the following moves, in mm/s².
+    pub fn set_acceleration(&mut self, acceleration: f32) {
+        self.acceleration = acceleration;
+    }
+
+    /// Marks the following moves as part of layer `index`.
+    pub fn set_layer(&mut self, index: usize) {
+        self.layer = Some(index);
+    }
+
+    /// Adds a straight move at `feedrate` mm/s, going `delta` mm along each
+    /// axis in X, Y, Z, E order.
+    ///
+    /// `feature` is the feature being printed, or `None` for travels and
+    /// retractions.
+    pub fn add_move(&mut self, delta: [f32; 4], feedrate: f32, feature: Option<FeatureType>) {
+        self.moves.push(PlannedMove {
+            delta,
+            feedrate,
+            acceleration: self.acceleration,
+            feature,
+            layer: self.layer,
+        });
+    }
+
+    /// Plans every move added so far and totals up the time and filament
+    /// they take.
+    pub fn estimate(&self) -> PrintEstimate {
+        let limits = &self.config.machine_limits;
+        let (moves, blocks): (Vec<&PlannedMove>, Vec<Block>) = self
+            .moves
+            .iter()
+            .filter_map(|m| Some((m, Block::new(m, limits)?)))
+            .unzip();
+
+        // The fastest each move can start at, given the corner before it.
+        let mut entry = (0..blocks.len())
+            .map(|i| {
+                junction_speed(
+                    i.checked_sub(1).map(|j| &blocks[j]),
+                    Some(&blocks[i]),
+                    limits,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut exit = blocks
+            .last()
+            .map_or(0.0, |last| junction_speed(Some(last), None, limits));
+        let max_speed = |speed: f32, block: &Block| {
+            (speed.powi(2) + 2.0 * block.acceleration * block.length).sqrt()
+        };
+        // Work backwards, so every move can slow down in time for the next,
+        // then forwards, so every move can speed up to its exit speed.
+        let mut next = exit;
+        for (speed, block) in entry.iter_mut().zip(&blocks).rev() {
+            *speed = speed.min(max_speed(next, block));
+            next = *speed;
+        }
+        for i in 0..blocks.len() {
+            let reachable = max_speed(entry[i], &blocks[i]);
+            match entry.get_mut(i + 1) {
+                Some(speed) => *speed = speed.min(reachable),
+                None => exit = exit.min(reachable),
+            }
+        }
+
+        let mut time = 0.0f64;
+        let mut travel_time = 0.0f64;
+        let mut feature_times: Vec<(FeatureType, f64)> = Vec::new();
+        let mut layer_times: Vec<f64> = Vec::new();
+        let mut layer_travel_times: Vec<f64> = Vec::new();
+        for (i, (m, block)) in moves.iter().zip(&blocks).enumerate() {
+            let exit = entry.get(i + 1).copied().unwrap_or(exit);
+            let t = trapezoid_time(
+                entry[i],
+                exit,
+                block.nominal_speed,
+                block.acceleration,
+                block.length,
+            ) as f64;
+            time += t;
+            match m.feature {
+                Some(feature) => match feature_times.iter_mut().find(|(f, _)| *f == feature) {
+                    Some((_, total)) => *total += t,
+                    None => feature_times.push((feature, t)),
+                },
+                None => travel_time += t,
+            }
+            if let Some(layer) = m.layer {
+                if layer >= layer_times.len() {
+                    layer_times.resize(layer + 1, 0.0);
+                    layer_travel_times.resize(layer + 1, 0.0);
+                }
+                layer_times[layer] += t;
+                if m.feature.is_none() {
+                    layer_travel_times[layer] += t;
+                }
+            }
+        }
+
+        let gcode = &self.config.gcode;
+        let filament_length = self.moves.iter().map(|m| m.delta[3] as f64).sum::<f64>() as f32;
+        let filament_area = PI * (gcode.filament_diameter / 2.0).powi(2);
+        // mm³ to cm³.
+        let filament_weight = filament_length * filament_area / 1000.0 * gcode.filament_density;
+        PrintEstimate {
+            time: time as f32,
+            feature_times: feature_times
+                .into_iter()
+                .map(|(f, t)| (f, t as f32))
+                .collect(),
+            travel_time: travel_time as f32,
+            layer_times: layer_times.into_iter().map(|t| t as f32).collect(),
+            layer_travel_times: layer_travel_times.into_iter().map(|t| t as f32).collect(),
+            filament_length,
+            filament_weight,
+            filament_cost: filament_weight / 1000.0 * gcode.filament_cost,
+        }
+    }
+}
+
+/// Adds a move from `position` to `to` to `estimator`, and moves `position`
+/// along.
+fn add_move_to(
+    estimator: &mut PrintEstimator,
+    position: &mut Vector3<f32>,
+    to: Vector3<f32>,
+    e: f32,
+    feedrate: f32,
+    feature: Option<FeatureType>,
+) {
+    let delta = to - *position;
+    estimator.add_move([delta.x, delta.y, delta.z, e], feedrate, feature);
+    *position = to;
+}
+
+/// Estimates how long `layers` take to print at full speed, straight from
+/// the toolpaths.
+///
+/// This is rougher than estimating the G-code that's written for them:
+/// travels go straight to the start of each path, without combing,
+/// retracting or wiping, and arcs are left as the lines they were fitted
+/// to. It's quick enough to plan cooling with before any G-code is written.
+pub fn estimate_toolpaths(layers: &[ToolpathLayer], config: &SlicerConfig) -> PrintEstimate {
+    let gcode = &config.gcode;
+    let mut estimator = PrintEstimator::new(config);
+    let mut position = Vector3::new(0.0, 0.0, 0.0);
+    for layer in layers {
+        estimator.set_layer(layer.index);
+        for path in &layer.paths {
+            let points = path.path.points();
+            let point = |i: usize| {
+                let z = path.z.as_ref().map_or(layer.z, |z| z[i]);
+                points[i].extend(z)
+            };
+            if points.is_empty() {
+                continue;
+            }
+            if let Some(acceleration) = gcode.travel_acceleration {
+                estimator.set_acceleration(acceleration);
+            }
+            let travel = gcode.feedrates.travel;
+            add_move_to(&mut estimator, &mut position, point(0), 0.0, travel, None);
+
+            if let Some(acceleration) = gcode.print_acceleration {
+                estimator.set_acceleration(acceleration);
+            }
+            let speed = feature_feedrate(&gcode.feedrates, path.feature);
+            for i in 1..points.len() {
+                let to = point(i);
+                let length = (to - position).truncate().magnitude();
+                let e = extrusion_length(
+                    config.extrusion_width,
+                    layer.height,
+                    length,
+                    gcode.filament_diameter,
+                );
+                add_move_to(
+                    &mut estimator,
+                    &mut position,
+                    to,
+                    e,
+                    speed,
+                    Some(path.feature),
+                );
+            }
+        }
+    }
+    estimator.estimate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contour::Polyline;
+    use crate::gcode::GcodeWriter;
+    use crate::region::Region;
+    use crate::toolpath::ExtrusionPath;
+    use cgmath::Vector2;
+    use float_eq::assert_float_eq;
+
+    fn config(cornering: CorneringModel) -> SlicerConfig {
+        let mut config = SlicerConfig::default();
+        config.machine_limits.cornering = cornering;
+        config.machine_limits.acceleration = 1000.0;
+        config
+    }
+
+    fn time(config: &SlicerConfig, moves: &[[f32; 4]], feedrate: f32) -> f32 {
+        let mut estimator = PrintEstimator::new(config);
+        for &delta in moves {
+            estimator.add_move(delta, feedrate, None);
+        }
+        estimator.estimate().time
+    }
+
+    #[test]
+    fn trapezoidal_moves() {
+        let config = config(CorneringModel::JunctionDeviation);
+
+        // Accelerates to 50mm/s over 1.25mm, cruises, then slows down.
+        let long = time(&config, &[[100.0, 0.0, 0.0, 0.0]], 50.0);
+        // Too short to reach 100mm/s, so peaks at √(1000 × 1) mm/s.
+        let short = time(&config, &[[1.0, 0.0, 0.0, 0.0]], 100.0);
+        // Straight on through a junction doesn't slow down.
+        let split = time(
+            &config,
+            &[[50.0, 0.0, 0.0, 0.0], [50.0, 0.0, 0.0, 0.0]],
+            50.0,
+        );
+
+        assert_float_eq!(long, 0.1 + 97.5 / 50.0, abs <= 1e-4);
+        assert_float_eq!(short, 2.0 * 1000f32.sqrt() / 1000.0, abs <= 1e-4);
+        assert_float_eq!(split, long, abs <= 1e-4);
+    }
+
+    #[test]
+    fn corners_slow_down() {
+        // A zig-zag with shallow corners.
+        let zig_zag = [
+            [20.0, 5.0, 0.0, 0.0],
+            [20.0, -5.0, 0.0, 0.0],
+            [20.0, 5.0, 0.0, 0.0],
+            [20.0, -5.0, 0.0, 0.0],
+        ];
+        let line = [[80.0, 0.0, 0.0, 0.0]];
+
+        for cornering in [CorneringModel::JunctionDeviation, CorneringModel::Jerk] {
+            let config = config(cornering);
+            let corners = time(&config, &zig_zag, 100.0);
+            let straight = time(&config, &line, 100.0);
+            let stopping = zig_zag
+                .iter()
+                .map(|&m| time(&config, &[m], 100.0))
+                .sum::<f32>();
+            // Corners slow down, but don't stop.
+            assert!(straight < corners, "{:?}", cornering);
+            assert!(corners < stopping, "{:?}", cornering);
+        }
+    }
+
+    #[test]
+    fn axis_limits() {
+        let config = config(CorneringModel::JunctionDeviation);
+
+        // Z is limited to 12mm/s and 200mm/s².
+        let z = time(&config, &[[0.0, 0.0, 12.0, 0.0]], 100.0);
+        // Retractions move E alone, limited to 120mm/s.
+        let retract = time(&config, &[[0.0, 0.0, 0.0, -1.0]], 35.0);
+
+        assert_float_eq!(z, 24.0 / 200.0 + (12.0 - 0.72) / 12.0, abs <= 1e-4);
+        let accelerating = 35.0 * 35.0 / 1500.0;
+        assert_float_eq!(
+            retract,
+            70.0 / 1500.0 + (1.0 - accelerating) / 35.0,
+            abs <= 1e-4
+        );
+    }
+
+    #[test]
+    fn totals() {
+        let mut config = config(CorneringModel::JunctionDeviation);
+        config.gcode.filament_density = 1.25;
+        config.gcode.filament_cost = 20.0;
+        let mut estimator = PrintEstimator::new(&config);
+        estimator.add_move([10.0, 10.0, 0.0, 0.0], 100.0, None);
+        estimator.set_layer(0);
+        estimator.add_move([10.0, 0.0, 0.0, 10.0], 20.0, Some(FeatureType::Perimeter));
+        estimator.add_move([0.0, 0.0, 0.0, -1.0], 35.0, None);
+        estimator.set_layer(1);
+        estimator.add_move([0.0, 0.0, 0.2, 0.0], 10.0, None);
+        estimator.add_move([0.0, 0.0, 0.0, 1.0], 35.0, None);
+        estimator.add_move(
+            [0.0, 10.0, 0.0, 10.0],
+            20.0,
+            Some(FeatureType::SparseInfill),
+        );
+        estimator.add_move([-10.0, 0.0, 0.0, 10.0], 20.0, Some(FeatureType::Perimeter));
+
+        let estimate = estimator.estimate();
+
+        let features = estimate
+            .feature_times
+            .iter()
+            .map(|&(f, _)| f)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            features,
+            [FeatureType::Perimeter, FeatureType::SparseInfill]
+        );
+        assert_eq!(estimate.layer_times.len(), 2);
+        let feature_time = estimate.feature_times.iter().map(|&(_, t)| t).sum::<f32>();
+        assert_float_eq!(
+            estimate.time,
+            feature_time + estimate.travel_time,
+            abs <= 1e-4
+        );
+        // The first travel comes before the first layer.
+        let layer_time = estimate.layer_times.iter().sum::<f32>();
+        assert!(layer_time < estimate.time);
+        let layer_travel_time = estimate.layer_travel_times.iter().sum::<f32>();
+        assert_float_eq!(layer_time - layer_travel_time, feature_time, abs <= 1e-4);
+        assert_float_eq!(estimate.filament_length, 30.0, abs <= 1e-4);
+        // 30mm of 1.75mm filament is 72.16mm³.
+        let volume = 30.0 * PI * 0.875 * 0.875 / 1000.0;
+        assert_float_eq!(estimate.filament_weight, volume * 1.25, abs <= 1e-5);
+        assert_float_eq!(estimate.filament_cost, volume * 1.25 * 0.02, abs <= 1e-6);
+    }
+
+    #[test]
+    fn header() {
+        let estimate = PrintEstimate {
+            time: 3723.4,
+            feature_times: vec![(FeatureType::ExternalPerimeter, 62.0)],
+            travel_time: 5.0,
+            layer_times: vec![1.24, 2.0],
+            layer_travel_times: vec![0.5, 0.5],
+            filament_length: 1234.567,
+            filament_weight: 3.712,
+            filament_cost: 0.0742,
+        };
+
+        assert_eq!(
+            estimate.header(),
+            "; estimated printing time = 1h 2m 3s\n\
+             ; estimated time for ExternalPerimeter = 1m 2s\n\
+             ; estimated time for travel = 5s\n\
+             ; layer times [s] = min 1.2, max 2, average 1.6\n\
+             ; filament used [mm] = 1234.57\n\
+             ; filament used [g] = 3.71\n\
+             ; filament cost = 0.07\n"
+        );
+    }
+
+    #[test]
+    fn toolpath_estimate_matches_gcode() {
+        let config = config(CorneringModel::JunctionDeviation);
+        let square = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        let layer = ToolpathLayer {
+            index: 0,
+            z: 0.2,
+            height: 0.2,
+            region: Region::new(),
+            paths: vec![ExtrusionPath::new(
+                FeatureType::ExternalPerimeter,
+                Polyline::from_points(square.iter().map(|&(x, y)| Vector2::new(x, y)).collect()),
+            )],
+        };
+        let mut writer = GcodeWriter::new(&config);
+        writer.write_layer(&layer, 1);
+
+        let estimate = estimate_toolpaths(std::slice::from_ref(&layer), &config);
+
+        let written = writer.estimate();
+        assert_float_eq!(
+            estimate.layer_times[0],
+            written.layer_times[0],
+            r2nd <= 0.02
+        );
+        assert_float_eq!(
+            estimate.filament_length,
+            written.filament_length,
+            r2nd <= 1e-4
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use super::format_number;
+
+/// The firmware that G-code is generated for.
+///
+/// Most commands are common to every firmware, but each has its own way of
+/// waiting for temperatures, retracting, setting acceleration and reporting
+/// progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GcodeFlavor {
+    #[default]
+    Marlin,
+    Klipper,
+    RepRapFirmware,
+}
+
+impl GcodeFlavor {
+    /// Commands that set the bed temperature without waiting for it.
+    pub fn set_bed_temperature(&self, temperature: f32) -> String {
+        format!("M140 S{}", format_number(temperature, 0))
+    }
+
+    /// Commands that wait for the bed to reach `temperature`.
+    pub fn wait_bed_temperature(&self, temperature: f32) -> String {
+        format!("M190 S{}", format_number(temperature, 0))
+    }
+
+    /// Commands that set the nozzle temperature without waiting for it.
+    pub fn set_nozzle_temperature(&self, temperature: f32) -> String {
+        let t = format_number(temperature, 0);
+        match self {
+            GcodeFlavor::Marlin | GcodeFlavor::Klipper => format!("M104 S{}", t),
+            // RepRapFirmware sets tool temperatures with G10, and selects
+            // the tool to turn its heater on.
+            GcodeFlavor::RepRapFirmware => format!("G10 P0 S{} R{}\nT0", t, t),
+        }
+    }
+
+    /// Commands that wait for the nozzle to reach `temperature`.
+    pub fn wait_nozzle_temperature(&self, temperature: f32) -> String {
+        match self {
+            GcodeFlavor::Marlin | GcodeFlavor::Klipper => {
+                format!("M109 S{}", format_number(temperature, 0))
+            }
+            // The temperature was already set with G10 by
+            // `set_nozzle_temperature`, so this only has to wait for it.
+            GcodeFlavor::RepRapFirmware => "M116 P0".to_string(),
+        }
+    }
+
+    /// Commands that set how far, in mm, and how fast, in mm/s, firmware
+    /// retraction pulls the filament back, for printers that retract with
+    /// G10/G11.
+    pub fn retraction_settings(&self, length: f32, speed: f32) -> String {
+        match self {
+            GcodeFlavor::Marlin | GcodeFlavor::RepRapFirmware => format!(
+                "M207 S{} F{}",
+                format_number(length, 3),
+                format_number(speed * 60.0, 0)
+            ),
+            GcodeFlavor::Klipper => format!(
+                "SET_RETRACTION RETRACT_LENGTH={} RETRACT_SPEED={}",
+                format_number(length, 3),
+                format_number(speed, 0)
+            ),
+        }
+    }
+
+    /// Commands that set the acceleration of all following moves, in
+    /// mm/s².
+    pub fn set_acceleration(&self, acceleration: f32) -> String {
+        let a = format_number(acceleration, 0);
+        match self {
+            GcodeFlavor::Marlin => format!("M204 S{}", a),
+            GcodeFlavor::Klipper => format!("SET_VELOCITY_LIMIT ACCEL={}", a),
+            GcodeFlavor::RepRapFirmware => format!("M204 P{} T{}", a, a),
+        }
+    }
+
+    /// Commands that report print progress to the printer's display.
+    ///
+    /// Returns `None` for firmware that tracks progress itself.
+    pub fn progress(&self, percent: u32) -> Option<String> {
+        match self {
+            GcodeFlavor::Marlin | GcodeFlavor::Klipper => Some(format!("M73 P{}", percent)),
+            // RepRapFirmware estimates progress from the file position.
+            GcodeFlavor::RepRapFirmware => None,
+        }
+    }
+
+    /// Comments and commands that mark the start of a new layer.
+    ///
+    /// Marlin and RepRapFirmware hosts look for Cura style `;LAYER:` comments,
+    /// while Klipper is given PrusaSlicer style comments and told the layer
+    /// number directly, so it can show it in its print status.
+    pub fn layer_marker(&self, index: usize, z: f32) -> String {
+        match self {
+            GcodeFlavor::Marlin | GcodeFlavor::RepRapFirmware => format!(";LAYER:{}", index),
+            GcodeFlavor::Klipper => format!(
+                ";LAYER_CHANGE\n;Z:{}\nSET_PRINT_STATS_INFO CURRENT_LAYER={}",
+                format_number(z, 3),
+                index + 1
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_commands() {
+        assert_eq!(
+            GcodeFlavor::Marlin.wait_nozzle_temperature(210.0),
+            "M109 S210"
+        );
+        assert_eq!(GcodeFlavor::Klipper.wait_bed_temperature(60.0), "M190 S60");
+        assert_eq!(
+            GcodeFlavor::RepRapFirmware.set_nozzle_temperature(210.0),
+            "G10 P0 S210 R210\nT0"
+        );
+        assert_eq!(
+            GcodeFlavor::RepRapFirmware.wait_nozzle_temperature(210.0),
+            "M116 P0"
+        );
+    }
+
+    #[test]
+    fn retraction_settings() {
+        assert_eq!(
+            GcodeFlavor::Marlin.retraction_settings(0.8, 35.0),
+            "M207 S0.8 F2100"
+        );
+        assert_eq!(
+            GcodeFlavor::Klipper.retraction_settings(0.8, 35.0),
+            "SET_RETRACTION RETRACT_LENGTH=0.8 RETRACT_SPEED=35"
+        );
+    }
+
+    #[test]
+    fn acceleration_commands() {
+        assert_eq!(GcodeFlavor::Marlin.set_acceleration(1000.0), "M204 S1000");
+        assert_eq!(
+            GcodeFlavor::Klipper.set_acceleration(1000.0),
+            "SET_VELOCITY_LIMIT ACCEL=1000"
+        );
+        assert_eq!(
+            GcodeFlavor::RepRapFirmware.set_acceleration(1000.0),
+            "M204 P1000 T1000"
+        );
+    }
+}
@@ -0,0 +1,1173 @@
+mod cooling;
+mod estimate;
+mod flavor;
+
+pub use cooling::*;
+pub use estimate::*;
+pub use flavor::*;
+
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::arc::{fit_arcs, PathMove};
+use crate::combing::CombingRouter;
+use crate::config::{ExtrusionMode, FeedrateConfig, SlicerConfig};
+use crate::progress::{Cancelled, Progress, Stage};
+use crate::region::{LineSegment, Region};
+use crate::toolpath::{ExtrusionPath, FeatureType, ToolpathLayer};
+
+/// Returns the cross sectional area of an extruded line, in mm².
+///
+/// Lines are modelled as a rectangle with semicircular sides, which is
+/// roughly the shape filament takes when squashed between the nozzle and
+/// the layer below.
+pub fn extrusion_area(width: f32, height: f32) -> f32 {
+    (width - height) * height + PI * (height / 2.0).powi(2)
+}
+
+/// Returns the length of filament that needs to be fed into the extruder to
+/// print a line `length` mm long.
+pub fn extrusion_length(width: f32, height: f32, length: f32, filament_diameter: f32) -> f32 {
+    let filament_area = PI * (filament_diameter / 2.0).powi(2);
+    extrusion_area(width, height) * length / filament_area
+}
+
+/// Returns the print speed for `feature`, in mm/s.
+pub fn feature_feedrate(feedrates: &FeedrateConfig, feature: FeatureType) -> f32 {
+    match feature {
+        FeatureType::ExternalPerimeter => feedrates.external_perimeter,
+        FeatureType::Perimeter => feedrates.perimeter,
+        FeatureType::SolidInfill => feedrates.solid_infill,
+        FeatureType::SparseInfill => feedrates.sparse_infill,
+        FeatureType::Bridge => feedrates.bridge,
+        FeatureType::Support => feedrates.support,
+        FeatureType::SupportInterface => feedrates.support_interface,
+        FeatureType::Skirt | FeatureType::Brim => feedrates.skirt_brim,
+        FeatureType::RaftBase | FeatureType::RaftInterface => feedrates.raft,
+    }
+}
+
+/// Combed travels that are more than this many times longer than the direct
+/// route retract and go direct instead.
+const MAX_COMBING_DETOUR: f32 = 3.0;
+
+/// Whatever is left of a retraction after wiping is only retracted
+/// separately if it's longer than this, in mm.
+const MIN_RETRACTION: f32 = 1e-5;
+
+/// The length of a travel from `from` through each point of `route`.
+fn route_length(from: Vector2<f32>, route: &[Vector2<f32>]) -> f32 {
+    let mut position = from;
+    let mut length = 0.0;
+    for &point in route {
+        length += (point - position).magnitude();
+        position = point;
+    }
+    length
+}
+
+/// Formats `value` with at most `precision` decimal places, dropping any
+/// trailing zeros.
+fn format_number(value: f32, precision: usize) -> String {
+    let s = format!("{:.*}", precision, value);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    match s {
+        "-0" => "0".to_string(),
+        s => s.to_string(),
+    }
+}
+
+/// Writes G-code for a sequence of moves.
+///
+/// The writer keeps track of the position of the nozzle and the extruder so
+/// that only the values that change are written for each move.
+pub struct GcodeWriter<'a> {
+    config: &'a SlicerConfig,
+    output: String,
+    position: Option<Vector2<f32>>,
+    /// The height of the current layer.
+    z: f32,
+    /// True if filament has been retracted and not yet restored.
+    retracted: bool,
+    /// Routes travels around the inside of the current layer. Built on the
+    /// first travel that needs it.
+    router: Option<CombingRouter>,
+    /// The points to wipe the nozzle along before the next retraction,
+    /// following on from the last perimeter printed.
+    wipe_path: Vec<Vector2<f32>>,
+    /// The current extruder position. Only used for absolute extrusion.
+    e: f64,
+    /// The last feedrate written, in mm/min.
+    feedrate: Option<u32>,
+    /// The last acceleration written, in mm/s².
+    acceleration: Option<f32>,
+    /// The height of the nozzle, including any Z-hop.
+    nozzle_z: f32,
+    /// The feature of the path being printed.
+    feature: Option<FeatureType>,
+    /// Records every move, to estimate how long they take.
+    estimator: PrintEstimator<'a>,
+    /// How the current layer is cooled, or `None` to leave the fan alone
+    /// and print at full speed.
+    cooling: Option<LayerCooling>,
+    /// The last fan speed set, from 0 to 1.
+    fan_speed: f32,
+}
+
+impl<'a> GcodeWriter<'a> {
+    pub fn new(config: &'a SlicerConfig) -> Self {
+        Self {
+            config,
+            output: String::new(),
+            position: None,
+            z: 0.0,
+            retracted: false,
+            router: None,
+            wipe_path: Vec::new(),
+            e: 0.0,
+            feedrate: None,
+            acceleration: None,
+            nozzle_z: 0.0,
+            feature: None,
+            estimator: PrintEstimator::new(config),
+            cooling: None,
+            fan_speed: 0.0,
+        }
+    }
+
+    fn flavor(&self) -> GcodeFlavor {
+        self.config.gcode.flavor
+    }
+
+    /// Writes a line of G-code, followed by a newline.
+    pub fn line(&mut self, line: &str) {
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    pub fn comment(&mut self, comment: &str) {
+        self.line(&format!("; {}", comment));
+    }
+
+    /// Returns the `F` parameter for a move at `speed` mm/s, or an empty
+    /// string if the feedrate hasn't changed.
+    fn feedrate_param(&mut self, speed: f32) -> String {
+        let feedrate = (speed * 60.0).round() as u32;
+        if self.feedrate == Some(feedrate) {
+            return String::new();
+        }
+        self.feedrate = Some(feedrate);
+        format!(" F{}", feedrate)
+    }
+
+    /// Sets the acceleration for the following moves, if it has changed.
+    fn set_acceleration(&mut self, acceleration: Option<f32>) {
+        let Some(acceleration) = acceleration else {
+            return;
+        };
+        if self.acceleration == Some(acceleration) {
+            return;
+        }
+        self.acceleration = Some(acceleration);
+        self.estimator.set_acceleration(acceleration);
+        let command = self.flavor().set_acceleration(acceleration);
+        self.line(&command);
+    }
+
+    /// Heats the bed and nozzle, then writes the configured start block and
+    /// sets up the extrusion mode.
+    pub fn start(&mut self) {
+        let flavor = self.flavor();
+        let gcode = &self.config.gcode;
+        let heat = [
+            flavor.set_bed_temperature(gcode.bed_temperature),
+            flavor.set_nozzle_temperature(gcode.nozzle_temperature),
+            flavor.wait_bed_temperature(gcode.bed_temperature),
+            flavor.wait_nozzle_temperature(gcode.nozzle_temperature),
+        ];
+        for command in heat {
+            self.line(&command);
+        }
+        let start = self.config.gcode.start_gcode.clone();
+        self.output.push_str(&start);
+        if !start.is_empty() && !start.ends_with('\n') {
+            self.output.push('\n');
+        }
+        self.line("G21 ; millimeter units");
+        self.line("G90 ; absolute coordinates");
+        match self.config.gcode.extrusion_mode {
+            ExtrusionMode::Absolute => {
+                self.line("M82 ; absolute extrusion");
+                self.reset_extruder();
+            }
+            ExtrusionMode::Relative => self.line("M83 ; relative extrusion"),
+        }
+        let retraction = &self.config.retraction;
+        if retraction.use_firmware_retraction {
+            let command = flavor.retraction_settings(retraction.length, retraction.speed);
+            self.line(&command);
+        }
+    }
+
+    /// Writes the configured end block.
+    pub fn end(&mut self) {
+        self.set_fan_speed(0.0);
+        if let Some(progress) = self.flavor().progress(100) {
+            self.line(&progress);
+        }
+        let end = self.config.gcode.end_gcode.clone();
+        self.output.push_str(&end);
+        if !end.is_empty() && !end.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
+
+    /// Resets the extruder position to zero. This keeps E values small, so
+    /// they don't lose precision over long prints.
+    fn reset_extruder(&mut self) {
+        self.e = 0.0;
+        self.line("G92 E0");
+    }
+
+    /// Sets how the following layers are cooled.
+    pub fn set_cooling(&mut self, cooling: LayerCooling) {
+        self.cooling = Some(cooling);
+    }
+
+    /// Sets the part cooling fan to `speed`, from 0 to 1, if it has changed.
+    fn set_fan_speed(&mut self, speed: f32) {
+        if speed == self.fan_speed {
+            return;
+        }
+        self.fan_speed = speed;
+        if speed > 0.0 {
+            self.line(&format!("M106 S{}", format_number(speed * 255.0, 0)));
+        } else {
+            self.line("M107");
+        }
+    }
+
+    /// Moves the nozzle up to the height of `layer`, the `layer.index`th of
+    /// `layer_count` layers.
+    pub fn layer_change(&mut self, layer: &ToolpathLayer, layer_count: usize) {
+        let flavor = self.flavor();
+        self.line(&flavor.layer_marker(layer.index, layer.z));
+        self.estimator.set_layer(layer.index);
+        let percent = layer.index * 100 / layer_count.max(1);
+        if let Some(progress) = flavor.progress(percent as u32) {
+            self.line(&progress);
+        }
+        // A layer of a spiral starts at the height the layer below finished
+        // at, so the nozzle doesn't need to move.
+        let z = layer
+            .paths
+            .first()
+            .and_then(|p| p.z.as_ref())
+            .map_or(layer.z, |z| z[0]);
+        self.z = z;
+        if z != self.nozzle_z {
+            self.move_z(z);
+        }
+        // The last perimeter is on the layer below now, so it can't be wiped
+        // along.
+        self.wipe_path.clear();
+        self.router = None;
+        if self.config.gcode.extrusion_mode == ExtrusionMode::Absolute {
+            self.reset_extruder();
+        }
+    }
+
+    fn move_z(&mut self, z: f32) {
+        let speed = self.config.gcode.feedrates.z;
+        let feedrate = self.feedrate_param(speed);
+        self.line(&format!("G1 Z{}{}", format_number(z, 3), feedrate));
+        self.estimator
+            .add_move([0.0, 0.0, z - self.nozzle_z, 0.0], speed, None);
+        self.nozzle_z = z;
+    }
+
+    /// Records a move in XY and E from `from` to `to`, for the estimate.
+    fn record_move(
+        &mut self,
+        from: Vector2<f32>,
+        to: Vector2<f32>,
+        e: f32,
+        speed: f32,
+        feature: Option<FeatureType>,
+    ) {
+        let delta = to - from;
+        self.estimator
+            .add_move([delta.x, delta.y, 0.0, e], speed, feature);
+    }
+
+    /// Picks the way to travel from `from` to `point`, returning the points
+    /// to travel through and whether they stay inside `region`.
+    fn travel_route(
+        &mut self,
+        from: Vector2<f32>,
+        point: Vector2<f32>,
+        region: &Region,
+    ) -> (Vec<Vector2<f32>>, bool) {
+        let config = self.config;
+        let retraction = &config.retraction;
+        let length = (point - from).magnitude();
+        let clipped: f32 = region
+            .clip_segments(&[LineSegment::new(from, point)])
+            .iter()
+            .map(LineSegment::length)
+            .sum();
+        if clipped >= length * 0.999 {
+            return (vec![point], true);
+        }
+        if retraction.avoid_crossing_perimeters && length >= retraction.min_travel {
+            // Keep inside the innermost perimeter, away from the outside of
+            // the model.
+            let width = config.extrusion_width;
+            let margin = config.perimeters as f32 * width;
+            let router = self
+                .router
+                .get_or_insert_with(|| CombingRouter::new(region, margin, width));
+            if let Some(combed) = router.route(from, point) {
+                if route_length(from, &combed) <= length * MAX_COMBING_DETOUR {
+                    return (combed, true);
+                }
+            }
+        }
+        (vec![point], false)
+    }
+
+    /// Travels to `point`, retracting first if the travel is long enough to
+    /// string.
+    ///
+    /// `region` is the area of the current layer. If configured, travels
+    /// that stay inside of it don't retract.
+    pub fn travel(&mut self, point: Vector2<f32>, region: &Region) {
+        let Some(from) = self.position else {
+            self.travel_to(point);
+            return;
+        };
+        let config = self.config;
+        let retraction = &config.retraction;
+        let (mut route, inside) = self.travel_route(from, point, region);
+
+        if retraction.length <= 0.0
+            || route_length(from, &route) < retraction.min_travel
+            || (retraction.only_when_crossing_perimeters && inside)
+        {
+            for point in route {
+                self.travel_to(point);
+            }
+            return;
+        }
+
+        let wiped = self.wipe();
+        if let Some(position) = self.position.filter(|&p| p != from) {
+            // The wipe moved the nozzle, so the travel starts from there.
+            route = self.travel_route(position, point, region).0;
+        }
+        if !self.retracted {
+            let remaining = retraction.length - wiped;
+            if remaining > MIN_RETRACTION {
+                self.retract(remaining, retraction.speed);
+            }
+            self.retracted = true;
+        }
+        if retraction.z_hop > 0.0 {
+            self.move_z(self.z + retraction.z_hop);
+        }
+        for point in route {
+            self.travel_to(point);
+        }
+        if retraction.z_hop > 0.0 {
+            self.move_z(self.z);
+        }
+    }
+
+    /// Moves the nozzle back over the end of the last perimeter, so that any
+    /// ooze is left on the perimeter rather than strung across the travel.
+    ///
+    /// Unless the filament is already retracted, or the firmware retracts
+    /// with G10, the retraction is spread over the wipe so that nothing oozes
+    /// out at its end. Returns the length of filament retracted.
+    fn wipe(&mut self) -> f32 {
+        let wipe_path = std::mem::take(&mut self.wipe_path);
+        let (Some(mut from), Some(&first)) = (self.position, wipe_path.first()) else {
+            return 0.0;
+        };
+        let retraction = &self.config.retraction;
+        if first != from || retraction.wipe_distance <= 0.0 {
+            return 0.0;
+        }
+        let retract = !self.retracted && !retraction.use_firmware_retraction;
+        let mut remaining = retraction.wipe_distance;
+        let mut retracted = 0.0;
+        for &point in &wipe_path[1..] {
+            if remaining <= 0.0 {
+                break;
+            }
+            let length = (point - from).magnitude();
+            let to = if length > remaining {
+                from + (point - from) * (remaining / length)
+            } else {
+                point
+            };
+            let moved = length.min(remaining);
+            remaining -= length;
+            let amount = if retract {
+                retraction.length * moved / retraction.wipe_distance
+            } else {
+                0.0
+            };
+            let speed = self.config.gcode.feedrates.travel;
+            self.record_move(from, to, -amount, speed, None);
+            let e = if amount > 0.0 {
+                format!(" E{}", format_number(self.extruder_e(-amount), 5))
+            } else {
+                String::new()
+            };
+            let feedrate = self.feedrate_param(speed);
+            self.line(&format!(
+                "G1 X{} Y{}{}{} ; wipe",
+                format_number(to.x, 3),
+                format_number(to.y, 3),
+                e,
+                feedrate
+            ));
+            retracted += amount;
+            from = to;
+        }
+        self.position = Some(from);
+        retracted
+    }
+
+    /// Moves the nozzle to `point` without extruding.
+    pub fn travel_to(&mut self, point: Vector2<f32>) {
+        if self.position == Some(point) {
+            return;
+        }
+        self.set_acceleration(self.config.gcode.travel_acceleration);
+        // The first travel starts from wherever homing left the nozzle,
+        // which is assumed to be the origin.
+        let from = self.position.unwrap_or(Vector2::new(0.0, 0.0));
+        let speed = self.config.gcode.feedrates.travel;
+        self.record_move(from, point, 0.0, speed, None);
+        let feedrate = self.feedrate_param(speed);
+        self.line(&format!(
+            "G0 X{} Y{}{}",
+            format_number(point.x, 3),
+            format_number(point.y, 3),
+            feedrate
+        ));
+        self.position = Some(point);
+    }
+
+    /// Restores any retracted filament, and returns the `E` value for
+    /// extruding `amount` mm of filament.
+    fn extrusion_e(&mut self, amount: f32) -> f32 {
+        if self.retracted {
+            self.retracted = false;
+            let retraction = &self.config.retraction;
+            self.unretract(retraction.length, retraction.speed);
+        }
+        self.extruder_e(amount)
+    }
+
+    /// Moves the extruder by `length` mm of filament, and returns the `E`
+    /// value for the move.
+    fn extruder_e(&mut self, length: f32) -> f32 {
+        match self.config.gcode.extrusion_mode {
+            ExtrusionMode::Absolute => {
+                self.e += length as f64;
+                self.e as f32
+            }
+            ExtrusionMode::Relative => length,
+        }
+    }
+
+    /// Extrudes a line from the current position to `point`.
+    pub fn extrude_to(&mut self, point: Vector2<f32>, width: f32, height: f32, speed: f32) {
+        let Some(from) = self.position else {
+            self.travel_to(point);
+            return;
+        };
+        let length = (point - from).magnitude();
+        if length <= 0.0 {
+            return;
+        }
+        let amount = extrusion_length(width, height, length, self.config.gcode.filament_diameter);
+        let e = self.extrusion_e(amount);
+        self.set_acceleration(self.config.gcode.print_acceleration);
+        self.record_move(from, point, amount, speed, self.feature);
+        let feedrate = self.feedrate_param(speed);
+        self.line(&format!(
+            "G1 X{} Y{} E{}{}",
+            format_number(point.x, 3),
+            format_number(point.y, 3),
+            format_number(e, 5),
+            feedrate
+        ));
+        self.position = Some(point);
+    }
+
+    /// Extrudes a line from the current position to `point`, while moving
+    /// the nozzle to the height `z`.
+    pub fn extrude_to_z(
+        &mut self,
+        point: Vector2<f32>,
+        z: f32,
+        width: f32,
+        height: f32,
+        speed: f32,
+    ) {
+        let Some(from) = self.position else {
+            self.travel_to(point);
+            return;
+        };
+        let length = (point - from).magnitude();
+        if length <= 0.0 {
+            return;
+        }
+        let amount = extrusion_length(width, height, length, self.config.gcode.filament_diameter);
+        let e = self.extrusion_e(amount);
+        self.set_acceleration(self.config.gcode.print_acceleration);
+        let delta = point - from;
+        self.estimator.add_move(
+            [delta.x, delta.y, z - self.nozzle_z, amount],
+            speed,
+            self.feature,
+        );
+        let feedrate = self.feedrate_param(speed);
+        self.line(&format!(
+            "G1 X{} Y{} Z{} E{}{}",
+            format_number(point.x, 3),
+            format_number(point.y, 3),
+            format_number(z, 3),
+            format_number(e, 5),
+            feedrate
+        ));
+        self.position = Some(point);
+        self.nozzle_z = z;
+        self.z = z;
+    }
+
+    /// Extrudes an arc from the current position to `point`, around
+    /// `center`.
+    pub fn extrude_arc_to(
+        &mut self,
+        point: Vector2<f32>,
+        center: Vector2<f32>,
+        clockwise: bool,
+        width: f32,
+        height: f32,
+        speed: f32,
+    ) {
+        let Some(from) = self.position else {
+            self.travel_to(point);
+            return;
+        };
+        let arc = PathMove::Arc {
+            end: point,
+            center,
+            clockwise,
+        };
+        let length = arc.length(from);
+        let amount = extrusion_length(width, height, length, self.config.gcode.filament_diameter);
+        let e = self.extrusion_e(amount);
+        self.set_acceleration(self.config.gcode.print_acceleration);
+        // Firmware plans arcs as a series of short straight moves.
+        let mut start = from;
+        for end in arc.points(from, ARC_SEGMENT_LENGTH) {
+            let share = (end - start).magnitude() / length;
+            self.record_move(start, end, amount * share, speed, self.feature);
+            start = end;
+        }
+        let feedrate = self.feedrate_param(speed);
+        // I and J are the offset from the start of the arc to its centre.
+        self.line(&format!(
+            "{} X{} Y{} I{} J{} E{}{}",
+            if clockwise { "G2" } else { "G3" },
+            format_number(point.x, 3),
+            format_number(point.y, 3),
+            format_number(center.x - from.x, 3),
+            format_number(center.y - from.y, 3),
+            format_number(e, 5),
+            feedrate
+        ));
+        self.position = Some(point);
+    }
+
+    /// Pulls `length` mm of filament back out of the nozzle at `speed` mm/s,
+    /// to stop it oozing during a travel.
+    ///
+    /// With `retraction.use_firmware_retraction` the firmware is sent G10
+    /// instead, and uses the length and speed it was given at the start.
+    pub fn retract(&mut self, length: f32, speed: f32) {
+        self.move_extruder("G10 ; retract", -length, speed);
+    }
+
+    /// Undoes a `retract` of `length` mm, at `speed` mm/s.
+    pub fn unretract(&mut self, length: f32, speed: f32) {
+        self.move_extruder("G11 ; unretract", length, speed);
+    }
+
+    fn move_extruder(&mut self, firmware_command: &str, length: f32, speed: f32) {
+        self.estimator
+            .add_move([0.0, 0.0, 0.0, length], speed, None);
+        if self.config.retraction.use_firmware_retraction {
+            self.line(firmware_command);
+            return;
+        }
+        let e = self.extruder_e(length);
+        let feedrate = self.feedrate_param(speed);
+        self.line(&format!("G1 E{}{}", format_number(e, 5), feedrate));
+    }
+
+    /// Prints `path` as part of `layer`, travelling to its start first.
+    pub fn extrude_path(&mut self, path: &ExtrusionPath, layer: &ToolpathLayer) {
+        let points = path.path.points();
+        let Some(&start) = points.first() else {
+            return;
+        };
+        let mut speed = feature_feedrate(&self.config.gcode.feedrates, path.feature);
+        let width = self.config.extrusion_width;
+        self.travel(start, &layer.region);
+        self.feature = Some(path.feature);
+        if let Some(cooling) = self.cooling {
+            let fan_speed = if path.feature == FeatureType::Bridge || path.overhang {
+                cooling.bridge_fan_speed
+            } else {
+                cooling.fan_speed
+            };
+            self.set_fan_speed(fan_speed);
+            // Slowing down never takes a path below the minimum speed, unless
+            // it was already printed slower than that.
+            let min_speed = self.config.cooling.min_print_speed.min(speed);
+            speed = (speed * cooling.speed_factor).max(min_speed);
+        }
+        if let Some(z) = &path.z {
+            for (&point, &z) in points[1..].iter().zip(&z[1..]) {
+                self.extrude_to_z(point, z, width, layer.height, speed);
+            }
+        } else if self.config.gcode.arc_fitting.enabled {
+            for m in fit_arcs(points, &self.config.gcode.arc_fitting) {
+                match m {
+                    PathMove::Line(point) => self.extrude_to(point, width, layer.height, speed),
+                    PathMove::Arc {
+                        end,
+                        center,
+                        clockwise,
+                    } => self.extrude_arc_to(end, center, clockwise, width, layer.height, speed),
+                }
+            }
+        } else {
+            for &point in &points[1..] {
+                self.extrude_to(point, width, layer.height, speed);
+            }
+        }
+        // Wipe forwards around closed loops, since that keeps the nozzle over
+        // the perimeter, or back along open paths.
+        self.wipe_path = match path.feature {
+            FeatureType::ExternalPerimeter | FeatureType::Perimeter if path.is_closed() => {
+                std::iter::once(points[points.len() - 1])
+                    .chain(points[1..].iter().copied())
+                    .collect()
+            }
+            FeatureType::ExternalPerimeter | FeatureType::Perimeter => {
+                points.iter().rev().copied().collect()
+            }
+            _ => Vec::new(),
+        };
+    }
+
+    /// Writes all of the paths in `layer`, in order.
+    pub fn write_layer(&mut self, layer: &ToolpathLayer, layer_count: usize) {
+        self.layer_change(layer, layer_count);
+        let mut feature = None;
+        for path in &layer.paths {
+            if feature != Some(path.feature) {
+                feature = Some(path.feature);
+                self.line(&format!(";TYPE:{}", path.feature));
+            }
+            self.extrude_path(path, layer);
+        }
+    }
+
+    /// Estimates how long the moves written so far take to print, and how
+    /// much filament they use.
+    pub fn estimate(&self) -> PrintEstimate {
+        self.estimator.estimate()
+    }
+
+    /// Returns the G-code written so far.
+    pub fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Generates a complete G-code program that prints `layers`.
+///
+/// Paths are printed in the order they appear within each layer. Layers
+/// that print too quickly to cool are slowed down, and the fan speed is set
+/// for each layer, as set by `config.cooling`. The program starts with
+/// comments giving the estimated print time and filament use.
+pub fn generate_gcode(layers: &[ToolpathLayer], config: &SlicerConfig) -> String {
+    // Without a cancellation token, this can't fail.
+    generate_gcode_with_progress(layers, config, Progress::default()).unwrap()
+}
+
+/// Generates G-code like `generate_gcode`, reporting progress to `progress`
+/// as each layer is written.
+///
+/// Returns `Err(Cancelled)` if `progress`'s cancellation token is cancelled
+/// before all of the layers are written.
+pub fn generate_gcode_with_progress(
+    layers: &[ToolpathLayer],
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<String, Cancelled> {
+    // How much each layer needs cooling depends on how long it takes to
+    // print, so the layers are timed at full speed first.
+    let cooling = plan_cooling(layers, &estimate_toolpaths(layers, config), &config.cooling);
+
+    let mut writer = GcodeWriter::new(config);
+    writer.start();
+    for (i, (layer, cooling)) in layers.iter().zip(cooling).enumerate() {
+        progress.check()?;
+        writer.set_cooling(cooling);
+        writer.write_layer(layer, layers.len());
+        progress.report(Stage::Gcode, i + 1, layers.len());
+    }
+    writer.end();
+    let mut gcode = writer.estimate().header();
+    gcode.push_str(&writer.finish());
+    Ok(gcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CoolingConfig, GcodeConfig, RetractionConfig};
+    use crate::contour::Polyline;
+    use crate::test_util::{polygon, square};
+    use float_eq::assert_float_eq;
+
+    fn line(feature: FeatureType, points: &[(f32, f32)]) -> ExtrusionPath {
+        ExtrusionPath::new(
+            feature,
+            Polyline::from_points(points.iter().map(|&(x, y)| Vector2::new(x, y)).collect()),
+        )
+    }
+
+    fn two_layers() -> Vec<ToolpathLayer> {
+        (0..2)
+            .map(|index| ToolpathLayer {
+                index,
+                z: 0.2 * (index + 1) as f32,
+                height: 0.2,
+                region: Region::new(),
+                paths: vec![
+                    line(FeatureType::ExternalPerimeter, &[(0.0, 0.0), (10.0, 0.0)]),
+                    line(FeatureType::SparseInfill, &[(10.0, 0.0), (10.0, 10.0)]),
+                ],
+            })
+            .collect()
+    }
+
+    fn config(extrusion_mode: ExtrusionMode) -> SlicerConfig {
+        SlicerConfig {
+            extrusion_width: 0.4,
+            gcode: GcodeConfig {
+                extrusion_mode,
+                start_gcode: "; start".to_string(),
+                end_gcode: "; end\n".to_string(),
+                ..Default::default()
+            },
+            // The test layers are tiny, and would otherwise be slowed down.
+            cooling: CoolingConfig {
+                min_layer_time: 0.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Returns the E values of every extruding move.
+    fn e_values(gcode: &str) -> Vec<f32> {
+        gcode
+            .lines()
+            .filter(|l| l.starts_with("G1 X"))
+            .filter_map(|l| l.split(' ').find_map(|w| w.strip_prefix('E')))
+            .map(|e| e.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn extrusion_length_matches_volume() {
+        // A 0.4x0.2 line has an area of 0.2 * 0.2 + pi * 0.1², and all of
+        // that volume comes from the filament.
+        let area = 0.04 + PI * 0.01;
+        let filament = PI * 0.875f32.powi(2);
+        assert_float_eq!(
+            extrusion_length(0.4, 0.2, 10.0, 1.75),
+            area * 10.0 / filament,
+            abs <= 0.00001
+        );
+    }
+
+    #[test]
+    fn format_numbers() {
+        assert_eq!(format_number(10.0, 3), "10");
+        assert_eq!(format_number(1.25, 3), "1.25");
+        assert_eq!(format_number(0.12345678, 5), "0.12346");
+        assert_eq!(format_number(-0.0001, 3), "0");
+    }
+
+    #[test]
+    fn absolute_extrusion() {
+        let gcode = generate_gcode(&two_layers(), &config(ExtrusionMode::Absolute));
+
+        assert!(gcode.contains("M82"));
+        let step = extrusion_length(0.4, 0.2, 10.0, 1.75);
+        let expected = [step, 2.0 * step, step, 2.0 * step];
+        let actual = e_values(&gcode);
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert_float_eq!(*a, e, abs <= 0.00001);
+        }
+        // The extruder is reset on every layer.
+        assert_eq!(gcode.matches("G92 E0").count(), 3);
+    }
+
+    #[test]
+    fn relative_extrusion() {
+        let gcode = generate_gcode(&two_layers(), &config(ExtrusionMode::Relative));
+
+        assert!(gcode.contains("M83"));
+        assert!(!gcode.contains("G92"));
+        let step = extrusion_length(0.4, 0.2, 10.0, 1.75);
+        for e in e_values(&gcode) {
+            assert_float_eq!(e, step, abs <= 0.00001);
+        }
+    }
+
+    #[test]
+    fn print_estimate() {
+        let config = config(ExtrusionMode::Relative);
+        let layers = two_layers();
+        let mut writer = GcodeWriter::new(&config);
+        writer.start();
+        for layer in &layers {
+            writer.write_layer(layer, layers.len());
+        }
+
+        let estimate = writer.estimate();
+        let gcode = generate_gcode(&layers, &config);
+
+        assert!(gcode.starts_with(&estimate.header()));
+        assert_eq!(estimate.layer_times.len(), 2);
+        let features = estimate
+            .feature_times
+            .iter()
+            .map(|&(f, _)| f)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            features,
+            [FeatureType::ExternalPerimeter, FeatureType::SparseInfill]
+        );
+        // Each 10mm line at 25mm/s or 60mm/s takes a little longer than it
+        // would at full speed.
+        let (_, perimeter) = estimate.feature_times[0];
+        assert!(perimeter > 2.0 * 10.0 / 25.0 && perimeter < 2.0 * 10.0 / 25.0 + 0.5);
+        let e = e_values(&gcode).iter().sum::<f32>();
+        assert_float_eq!(estimate.filament_length, e, abs <= 1e-4);
+    }
+
+    #[test]
+    fn feedrate_per_feature() {
+        let config = config(ExtrusionMode::Relative);
+        let gcode = generate_gcode(&two_layers(), &config);
+
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let perimeter = lines
+            .iter()
+            .position(|l| l.starts_with("G1 X10 Y0"))
+            .unwrap();
+        let infill = lines
+            .iter()
+            .position(|l| l.starts_with("G1 X10 Y10"))
+            .unwrap();
+        assert!(lines[perimeter].ends_with(" F1500"));
+        assert!(lines[infill].ends_with(" F3600"));
+        assert!(lines.contains(&";TYPE:SparseInfill"));
+    }
+
+    #[test]
+    fn layer_changes() {
+        let gcode = generate_gcode(&two_layers(), &config(ExtrusionMode::Relative));
+
+        let z_moves = gcode
+            .lines()
+            .filter(|l| l.starts_with("G1 Z"))
+            .collect::<Vec<_>>();
+        assert_eq!(z_moves, vec!["G1 Z0.2 F600", "G1 Z0.4 F600"]);
+        assert!(gcode.contains(";LAYER:1\n"));
+    }
+
+    #[test]
+    fn retraction() {
+        let mut config = config(ExtrusionMode::Absolute);
+        let mut writer = GcodeWriter::new(&config);
+        writer.retract(0.8, 35.0);
+        writer.unretract(0.8, 35.0);
+        assert_eq!(writer.finish(), "G1 E-0.8 F2100\nG1 E0\n");
+
+        config.retraction.use_firmware_retraction = true;
+        let mut writer = GcodeWriter::new(&config);
+        writer.retract(0.8, 35.0);
+        writer.unretract(0.8, 35.0);
+        assert_eq!(writer.finish(), "G10 ; retract\nG11 ; unretract\n");
+    }
+
+    fn travel_config() -> SlicerConfig {
+        SlicerConfig {
+            retraction: RetractionConfig {
+                length: 1.0,
+                speed: 40.0,
+                min_travel: 2.0,
+                only_when_crossing_perimeters: true,
+                avoid_crossing_perimeters: false,
+                z_hop: 0.5,
+                wipe_distance: 0.0,
+                use_firmware_retraction: false,
+            },
+            ..config(ExtrusionMode::Relative)
+        }
+    }
+
+    #[test]
+    fn short_travel_does_not_retract() {
+        let config = travel_config();
+        let mut writer = GcodeWriter::new(&config);
+        writer.travel_to(Vector2::new(0.0, 0.0));
+        writer.travel(Vector2::new(1.0, 1.0), &Region::new());
+        assert_eq!(writer.finish(), "G0 X0 Y0 F9000\nG0 X1 Y1\n");
+    }
+
+    #[test]
+    fn travel_inside_region_does_not_retract() {
+        let config = travel_config();
+        let mut writer = GcodeWriter::new(&config);
+        writer.travel_to(Vector2::new(1.0, 1.0));
+        writer.travel(Vector2::new(9.0, 9.0), &square(10.0));
+        assert_eq!(writer.finish(), "G0 X1 Y1 F9000\nG0 X9 Y9\n");
+    }
+
+    #[test]
+    fn long_travel_retracts_and_hops() {
+        let config = travel_config();
+        let mut writer = GcodeWriter::new(&config);
+        writer.z = 0.2;
+        writer.travel_to(Vector2::new(1.0, 1.0));
+        writer.travel(Vector2::new(20.0, 1.0), &square(10.0));
+        writer.extrude_to(Vector2::new(21.0, 1.0), 0.4, 0.2, 40.0);
+
+        let gcode = writer.finish();
+        let lines = gcode.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..7],
+            [
+                "G0 X1 Y1 F9000",
+                "G1 E-1 F2400",
+                "G1 Z0.7 F600",
+                "G0 X20 Y1 F9000",
+                "G1 Z0.2 F600",
+                "G1 E1 F2400",
+                // The feedrate is already 40mm/s from the unretract.
+                "G1 X21 Y1 E0.02969",
+            ]
+        );
+    }
+
+    #[test]
+    fn combed_travel_does_not_retract() {
+        let mut config = travel_config();
+        config.retraction.avoid_crossing_perimeters = true;
+        // An L shape, so there's no straight route from one arm to the
+        // other.
+        let region = Region::from_paths(&[polygon(&[
+            (0., 0.),
+            (10., 0.),
+            (10., 4.),
+            (4., 4.),
+            (4., 10.),
+            (0., 10.),
+        ])]);
+        let mut writer = GcodeWriter::new(&config);
+        writer.travel_to(Vector2::new(9.0, 1.0));
+        writer.travel(Vector2::new(1.0, 9.0), &region);
+
+        let gcode = writer.finish();
+        assert!(!gcode.contains(" E"));
+        let travels = gcode.lines().filter(|l| l.starts_with("G0")).count();
+        assert!(travels > 2);
+        assert!(gcode.ends_with("G0 X1 Y9\n"));
+    }
+
+    #[test]
+    fn arc_fitting() {
+        let mut config = config(ExtrusionMode::Relative);
+        config.gcode.arc_fitting.enabled = true;
+        // A counter-clockwise half circle of radius 5 around (10, 10),
+        // followed by a straight line.
+        let mut points = (0..=36)
+            .map(|i| {
+                let angle = -PI / 2.0 + PI * i as f32 / 36.0;
+                Vector2::new(10.0 + 5.0 * angle.cos(), 10.0 + 5.0 * angle.sin())
+            })
+            .collect::<Vec<_>>();
+        points.push(Vector2::new(0.0, 15.0));
+        let layer = ToolpathLayer {
+            index: 0,
+            z: 0.2,
+            height: 0.2,
+            region: Region::new(),
+            paths: vec![ExtrusionPath::new(
+                FeatureType::ExternalPerimeter,
+                Polyline::from_points(points),
+            )],
+        };
+        let mut writer = GcodeWriter::new(&config);
+        writer.write_layer(&layer, 1);
+
+        let gcode = writer.finish();
+        let arc = gcode.lines().find(|l| l.starts_with("G3")).unwrap();
+        assert!(arc.starts_with("G3 X10 Y15 I0 J5 E"), "{}", arc);
+        assert!(!gcode.lines().any(|l| l.starts_with("G2")));
+        // The arc extrudes as much as the half circle's length.
+        let e = arc.split(' ').find_map(|w| w.strip_prefix('E')).unwrap();
+        let expected = extrusion_length(0.4, 0.2, 5.0 * PI, config.gcode.filament_diameter);
+        assert_float_eq!(e.parse::<f32>().unwrap(), expected, abs <= 1e-4);
+        assert_eq!(e_values(&gcode).len(), 1);
+    }
+
+    #[test]
+    fn spiral_path_climbs() {
+        let config = config(ExtrusionMode::Relative);
+        let mut layers = two_layers();
+        let mut spiral = line(
+            FeatureType::ExternalPerimeter,
+            &[(10.0, 10.0), (0.0, 10.0), (0.0, 0.0)],
+        );
+        spiral.z = Some(vec![0.2, 0.3, 0.4]);
+        layers[1].paths = vec![spiral];
+
+        let gcode = generate_gcode(&layers, &config);
+
+        // The spiral carries on from the end of the first layer, so the
+        // nozzle doesn't move up between layers.
+        assert_eq!(gcode.lines().filter(|l| l.starts_with("G1 Z")).count(), 1);
+        let spiral = gcode
+            .lines()
+            .filter(|l| l.starts_with("G1 X") && l.contains(" Z"))
+            .collect::<Vec<_>>();
+        assert_eq!(spiral.len(), 2);
+        assert!(spiral[0].starts_with("G1 X0 Y10 Z0.3 E"), "{}", spiral[0]);
+        assert!(spiral[1].starts_with("G1 X0 Y0 Z0.4 E"), "{}", spiral[1]);
+    }
+
+    #[test]
+    fn cooling() {
+        let mut config = config(ExtrusionMode::Relative);
+        config.cooling = CoolingConfig {
+            min_fan_speed: 20.0,
+            max_fan_speed: 50.0,
+            bridge_fan_speed: 100.0,
+            disable_fan_first_layers: 1,
+            fan_below_layer_time: 200.0,
+            min_layer_time: 100.0,
+            min_print_speed: 5.0,
+        };
+        let mut layers = two_layers();
+        layers[1]
+            .paths
+            .push(line(FeatureType::Bridge, &[(10.0, 10.0), (0.0, 10.0)]));
+
+        let gcode = generate_gcode(&layers, &config);
+
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let fan = lines
+            .iter()
+            .filter(|l| l.starts_with("M106") || l.starts_with("M107"))
+            .copied()
+            .collect::<Vec<_>>();
+        // Off for the first layer, up to the maximum for the short second
+        // layer and all the way for the bridge, then off at the end.
+        assert_eq!(fan, ["M106 S128", "M106 S255", "M107"]);
+        let second_layer = lines.iter().position(|l| *l == ";LAYER:1").unwrap();
+        let fan_on = lines.iter().position(|l| *l == "M106 S128").unwrap();
+        assert!(fan_on > second_layer);
+        // Both layers are slowed down as far as they can go. The second
+        // layer starts with a travel, so sets the feedrate again.
+        let feedrates = lines
+            .iter()
+            .filter(|l| l.starts_with("G1 X"))
+            .filter_map(|l| l.split(' ').find_map(|w| w.strip_prefix('F')))
+            .collect::<Vec<_>>();
+        assert_eq!(feedrates, ["300", "300"]);
+    }
+
+    #[test]
+    fn wipe_along_perimeter() {
+        let mut config = travel_config();
+        config.retraction.wipe_distance = 1.5;
+        config.retraction.z_hop = 0.0;
+        let layer = ToolpathLayer {
+            index: 0,
+            z: 0.2,
+            height: 0.2,
+            region: Region::new(),
+            paths: vec![
+                line(
+                    FeatureType::ExternalPerimeter,
+                    &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+                ),
+                line(FeatureType::ExternalPerimeter, &[(20.0, 0.0), (21.0, 0.0)]),
+            ],
+        };
+        let mut writer = GcodeWriter::new(&config);
+        writer.write_layer(&layer, 1);
+
+        let gcode = writer.finish();
+        let wipes = gcode
+            .lines()
+            .filter(|l| l.ends_with("; wipe"))
+            .collect::<Vec<_>>();
+        // The nozzle continues around the loop past its start, retracting
+        // as it goes.
+        assert_eq!(
+            wipes,
+            [
+                "G1 X1 Y0 E-0.66667 F9000 ; wipe",
+                "G1 X1 Y0.5 E-0.33333 ; wipe"
+            ]
+        );
+        let wipe = gcode.find("; wipe").unwrap();
+        assert!(!gcode[wipe..].contains("G1 E-"));
+        // The travel starts from the end of the wipe.
+        let travel = gcode[wipe..].lines().find(|l| l.starts_with("G0")).unwrap();
+        assert_eq!(travel, "G0 X20 Y0");
+    }
+
+    #[test]
+    fn start_and_end_blocks() {
+        let gcode = generate_gcode(&two_layers(), &config(ExtrusionMode::Relative));
+
+        assert!(gcode.contains("M109 S210\n; start\n"));
+        assert!(gcode.ends_with("; end\n"));
+    }
+}
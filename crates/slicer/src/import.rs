@@ -0,0 +1,799 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::config::SlicerConfig;
+use crate::profile::{ConfigError, Profile, ProfileKind, INHERITS_KEY, KIND_KEY, NAME_KEY};
+
+/// A setting from another slicer that has no equivalent here, or a value
+/// that can't be converted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnmappedSetting {
+    /// The INI section the setting was in, if any.
+    pub section: Option<String>,
+    pub key: String,
+    pub value: String,
+}
+
+impl fmt::Display for UnmappedSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(section) = &self.section {
+            write!(f, "[{}] ", section)?;
+        }
+        write!(f, "{} = {}", self.key, self.value)
+    }
+}
+
+/// Settings imported from another slicer.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedConfig {
+    /// Settings that aren't part of any profile.
+    pub settings: Map<String, Value>,
+    /// Profiles from the sections of a PrusaSlicer config bundle.
+    pub profiles: Vec<Profile>,
+    /// Settings that weren't imported.
+    pub unmapped: Vec<UnmappedSetting>,
+}
+
+impl ImportedConfig {
+    /// Builds a configuration from the imported settings that aren't part of
+    /// a profile. Settings that weren't imported keep their defaults.
+    pub fn config(&self) -> Result<SlicerConfig, ConfigError> {
+        SlicerConfig::from_settings(self.settings.clone())
+    }
+
+    fn report_unmapped(&mut self, section: Option<&str>, key: &str, value: &str) {
+        self.unmapped.push(UnmappedSetting {
+            section: section.map(str::to_string),
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+}
+
+/// Sets the setting at a dotted `path`, creating sections as needed.
+fn insert(settings: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((section, rest)) => {
+            let section = settings
+                .entry(section)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(section) = section {
+                insert(section, rest, value);
+            }
+        }
+        None => {
+            settings.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// Reads a value that other slicers store once per extruder, as a comma
+/// separated list. Only lists where every extruder has the same value can
+/// be imported.
+fn single(value: &str) -> Option<&str> {
+    let mut values = value.split(',').map(str::trim);
+    let first = values.next()?;
+    values.all(|v| v == first).then_some(first)
+}
+
+fn number(value: &str) -> Option<Value> {
+    let x = single(value)?.parse::<f64>().ok()?;
+    serde_json::Number::from_f64(x).map(Value::Number)
+}
+
+/// Reads a number where zero means "automatic", which has no equivalent.
+fn nonzero(value: &str) -> Option<Value> {
+    number(value).filter(|n| n.as_f64() != Some(0.0))
+}
+
+fn integer(value: &str) -> Option<Value> {
+    let x = single(value)?.parse::<f64>().ok()?;
+    (x >= 0.0 && x.fract() == 0.0).then(|| Value::from(x as u64))
+}
+
+/// Reads a percentage written with a `%` sign.
+fn percentage(value: &str) -> Option<Value> {
+    number(single(value)?.strip_suffix('%')?)
+}
+
+fn boolean(value: &str) -> Option<Value> {
+    match single(value)? {
+        "1" | "true" | "True" => Some(Value::Bool(true)),
+        "0" | "false" | "False" => Some(Value::Bool(false)),
+        _ => None,
+    }
+}
+
+/// Maps one of a fixed set of values.
+fn choice<T: Copy + Into<Value>>(value: &str, choices: &[(&str, T)]) -> Option<Value> {
+    let value = single(value)?;
+    choices
+        .iter()
+        .find(|(from, _)| *from == value)
+        .map(|&(_, to)| to.into())
+}
+
+/// Reads custom G-code. Other slicers fill in placeholders in their G-code,
+/// which mandoline doesn't, so G-code using them can't be imported.
+fn gcode(value: &str, placeholders: &[char]) -> Option<Value> {
+    (!value.contains(placeholders)).then(|| Value::from(value))
+}
+
+/// Reads a PrusaSlicer bed shape: a list of `XxY` corners.
+fn bed_shape(value: &str) -> Option<Value> {
+    value
+        .split(',')
+        .map(|corner| {
+            let (x, y) = corner.trim().split_once('x')?;
+            Some(Value::from(vec![number(x)?, number(y)?]))
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(Value::from)
+}
+
+/// Undoes the escaping of a PrusaSlicer INI value, which may be quoted and
+/// has newlines written as `\n`.
+fn unescape(value: &str) -> String {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some(next @ ('\\' | '"'))) => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Converts a PrusaSlicer or SuperSlicer setting into the setting it maps
+/// to. Returns `None` if it can't be converted.
+fn prusa_setting(key: &str, value: &str) -> Option<(&'static str, Value)> {
+    let setting = match key {
+        "layer_height" => ("layer_height", number(value)?),
+        "first_layer_height" => ("first_layer_height", number(value)?),
+        "nozzle_diameter" => ("nozzle_diameter", number(value)?),
+        "bed_shape" => ("bed_shape", bed_shape(value)?),
+        "extrusion_width" => ("extrusion_width", nonzero(value)?),
+        "perimeters" => ("perimeters", integer(value)?),
+        "seam_position" => (
+            "seam_position",
+            choice(
+                value,
+                &[
+                    ("aligned", "aligned"),
+                    ("rear", "rear"),
+                    ("random", "random"),
+                ],
+            )?,
+        ),
+        "top_solid_layers" => ("top_layers", integer(value)?),
+        "bottom_solid_layers" => ("bottom_layers", integer(value)?),
+        "spiral_vase" => ("spiral_vase", boolean(value)?),
+        "fill_density" => ("infill.density", percentage(value)?),
+        "fill_pattern" => (
+            "infill.pattern",
+            choice(
+                value,
+                &[
+                    ("rectilinear", "rectilinear"),
+                    ("grid", "grid"),
+                    ("triangles", "triangles"),
+                    ("gyroid", "gyroid"),
+                    ("3dhoneycomb", "honeycomb"),
+                ],
+            )?,
+        ),
+        "support_material" => ("support.enabled", boolean(value)?),
+        "support_material_style" => (
+            "support.style",
+            choice(value, &[("grid", "grid"), ("organic", "tree")])?,
+        ),
+        // PrusaSlicer measures overhangs from horizontal, and zero picks the
+        // angle automatically.
+        "support_material_threshold" => {
+            let angle = nonzero(value)?.as_f64()?;
+            ("support.overhang_angle", Value::from(90.0 - angle))
+        }
+        "support_material_xy_spacing" => ("support.xy_gap", number(value)?),
+        "support_material_interface_layers" => ("support.interface_layers", integer(value)?),
+        "skirts" => ("adhesion.skirt_loops", integer(value)?),
+        "skirt_distance" => ("adhesion.skirt_distance", number(value)?),
+        "brim_width" => ("adhesion.brim_width", number(value)?),
+        "raft_layers" => ("adhesion.raft_layers", integer(value)?),
+        "external_perimeter_speed" => ("gcode.feedrates.external_perimeter", number(value)?),
+        "perimeter_speed" => ("gcode.feedrates.perimeter", number(value)?),
+        "solid_infill_speed" => ("gcode.feedrates.solid_infill", number(value)?),
+        "infill_speed" => ("gcode.feedrates.sparse_infill", number(value)?),
+        "bridge_speed" => ("gcode.feedrates.bridge", number(value)?),
+        "support_material_speed" => ("gcode.feedrates.support", number(value)?),
+        "support_material_interface_speed" => ("gcode.feedrates.support_interface", number(value)?),
+        "travel_speed" => ("gcode.feedrates.travel", number(value)?),
+        "travel_speed_z" => ("gcode.feedrates.z", nonzero(value)?),
+        "gcode_flavor" => (
+            "gcode.flavor",
+            choice(
+                value,
+                &[
+                    ("marlin", "marlin"),
+                    ("marlin2", "marlin"),
+                    ("klipper", "klipper"),
+                    ("reprapfirmware", "rep_rap_firmware"),
+                ],
+            )?,
+        ),
+        "use_relative_e_distances" => (
+            "gcode.extrusion_mode",
+            choice(value, &[("0", "absolute"), ("1", "relative")])?,
+        ),
+        "default_acceleration" => ("gcode.print_acceleration", nonzero(value)?),
+        "travel_acceleration" => ("gcode.travel_acceleration", nonzero(value)?),
+        "start_gcode" => ("gcode.start_gcode", gcode(&unescape(value), &['[', '{'])?),
+        "end_gcode" => ("gcode.end_gcode", gcode(&unescape(value), &['[', '{'])?),
+        "filament_diameter" => ("gcode.filament_diameter", number(value)?),
+        "filament_density" => ("gcode.filament_density", number(value)?),
+        "filament_cost" => ("gcode.filament_cost", number(value)?),
+        "temperature" => ("gcode.nozzle_temperature", number(value)?),
+        "bed_temperature" => ("gcode.bed_temperature", number(value)?),
+        "min_fan_speed" => ("cooling.min_fan_speed", number(value)?),
+        "max_fan_speed" => ("cooling.max_fan_speed", number(value)?),
+        "bridge_fan_speed" => ("cooling.bridge_fan_speed", number(value)?),
+        "disable_fan_first_layers" => ("cooling.disable_fan_first_layers", integer(value)?),
+        "fan_below_layer_time" => ("cooling.fan_below_layer_time", number(value)?),
+        "slowdown_below_layer_time" => ("cooling.min_layer_time", number(value)?),
+        "min_print_speed" => ("cooling.min_print_speed", number(value)?),
+        "retract_length" => ("retraction.length", number(value)?),
+        "retract_speed" => ("retraction.speed", number(value)?),
+        "retract_before_travel" => ("retraction.min_travel", number(value)?),
+        "retract_lift" => ("retraction.z_hop", number(value)?),
+        "only_retract_when_crossing_perimeters" => {
+            ("retraction.only_when_crossing_perimeters", boolean(value)?)
+        }
+        "avoid_crossing_perimeters" => ("retraction.avoid_crossing_perimeters", boolean(value)?),
+        "use_firmware_retraction" => ("retraction.use_firmware_retraction", boolean(value)?),
+        _ => return None,
+    };
+    Some(setting)
+}
+
+/// The settings in a section of an INI file.
+struct Section {
+    /// The section's header, or `None` for settings before the first
+    /// header.
+    header: Option<String>,
+    /// The kind of profile in the section, or `None` if it isn't a profile.
+    kind: Option<ProfileKind>,
+    settings: Map<String, Value>,
+}
+
+impl Section {
+    fn new(header: Option<&str>) -> Self {
+        let kind = header
+            .and_then(|h| h.split_once(':'))
+            .and_then(|(kind, _)| match kind {
+                "printer" => Some(ProfileKind::Printer),
+                "filament" => Some(ProfileKind::Filament),
+                "print" => Some(ProfileKind::Print),
+                _ => None,
+            });
+        Self {
+            header: header.map(str::to_string),
+            kind,
+            settings: Map::new(),
+        }
+    }
+
+    /// Returns true if settings in this section are imported. Settings at
+    /// the top of the file are imported as they are, settings in profiles
+    /// become part of the profile, and other sections are skipped.
+    fn is_imported(&self) -> bool {
+        self.header.is_none() || self.kind.is_some()
+    }
+}
+
+impl ImportedConfig {
+    fn add_prusa_setting(&mut self, section: &mut Section, key: &str, value: &str) {
+        let header = section.header.as_deref();
+        if !section.is_imported() {
+            self.report_unmapped(header, key, value);
+            return;
+        }
+        if key == "inherits" && section.kind.is_some() {
+            // PrusaSlicer profiles can inherit from a list of profiles, but
+            // only one is supported here.
+            let parents = value
+                .split(';')
+                .map(|p| p.trim().trim_matches('"'))
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>();
+            match parents.as_slice() {
+                [] => {}
+                [parent] => {
+                    section
+                        .settings
+                        .insert(INHERITS_KEY.to_string(), Value::from(*parent));
+                }
+                _ => self.report_unmapped(header, key, value),
+            }
+            return;
+        }
+        match prusa_setting(key, value) {
+            Some((path, value))
+                if section
+                    .kind
+                    .is_none_or(|k| ProfileKind::of_setting(path) == k) =>
+            {
+                insert(&mut section.settings, path, value);
+            }
+            _ => self.report_unmapped(header, key, value),
+        }
+    }
+
+    fn finish_prusa_section(&mut self, section: Section) -> Result<(), ConfigError> {
+        match (&section.header, section.kind) {
+            (None, _) => self.settings = section.settings,
+            (Some(header), Some(kind)) => {
+                let (_, name) = header.split_once(':').unwrap();
+                let mut settings = section.settings;
+                settings.insert(NAME_KEY.to_string(), Value::from(name.trim()));
+                settings.insert(KIND_KEY.to_string(), Value::from(kind.name()));
+                self.profiles.push(Profile::from_settings(settings)?);
+            }
+            (Some(_), None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Imports a PrusaSlicer or SuperSlicer INI file.
+///
+/// Both exported configurations and config bundles can be imported.
+/// Settings at the top of the file are used by `ImportedConfig::config`,
+/// and the `[printer:...]`, `[filament:...]` and `[print:...]` sections of
+/// a bundle become profiles. Settings that can't be imported are listed in
+/// `ImportedConfig::unmapped`.
+pub fn import_prusa_ini(text: &str) -> Result<ImportedConfig, ConfigError> {
+    let mut import = ImportedConfig::default();
+    let mut section = Section::new(None);
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let finished = std::mem::replace(&mut section, Section::new(Some(header)));
+            import.finish_prusa_section(finished)?;
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            ConfigError::Parse(format!("line {}: expected 'key = value'", number + 1))
+        })?;
+        import.add_prusa_setting(&mut section, key.trim(), value.trim());
+    }
+    import.finish_prusa_section(section)?;
+    Ok(import)
+}
+
+/// Imports a Cura definition or a PrusaSlicer INI file, telling them apart
+/// by their content: Cura definitions are JSON objects.
+pub fn import_config(text: &str) -> Result<ImportedConfig, ConfigError> {
+    if text.trim_start().starts_with('{') {
+        return import_cura_definition(text);
+    }
+    import_prusa_ini(text).map_err(|e| match e {
+        ConfigError::Parse(message) => ConfigError::Parse(format!(
+            "not a Cura definition or PrusaSlicer config: {}",
+            message
+        )),
+        e => e,
+    })
+}
+
+/// Converts a Cura setting into the setting it maps to. Returns `None` if
+/// it can't be converted.
+fn cura_setting(key: &str, value: &str) -> Option<(&'static str, Value)> {
+    let setting = match key {
+        "layer_height" => ("layer_height", number(value)?),
+        "layer_height_0" => ("first_layer_height", number(value)?),
+        "machine_nozzle_size" => ("nozzle_diameter", number(value)?),
+        "line_width" => ("extrusion_width", number(value)?),
+        "wall_line_count" => ("perimeters", integer(value)?),
+        "z_seam_type" => (
+            "seam_position",
+            choice(
+                value,
+                &[
+                    ("back", "rear"),
+                    ("sharpest_corner", "hidden"),
+                    ("random", "random"),
+                ],
+            )?,
+        ),
+        "top_layers" => ("top_layers", integer(value)?),
+        "bottom_layers" => ("bottom_layers", integer(value)?),
+        "magic_spiralize" => ("spiral_vase", boolean(value)?),
+        "infill_sparse_density" => ("infill.density", number(value)?),
+        "infill_pattern" => (
+            "infill.pattern",
+            choice(
+                value,
+                &[
+                    ("lines", "rectilinear"),
+                    ("grid", "grid"),
+                    ("triangles", "triangles"),
+                    ("gyroid", "gyroid"),
+                ],
+            )?,
+        ),
+        "support_enable" => ("support.enabled", boolean(value)?),
+        "support_structure" => (
+            "support.style",
+            choice(value, &[("normal", "grid"), ("tree", "tree")])?,
+        ),
+        "support_angle" => ("support.overhang_angle", number(value)?),
+        "support_xy_distance" => ("support.xy_gap", number(value)?),
+        "support_infill_rate" => ("support.density", number(value)?),
+        "skirt_line_count" => ("adhesion.skirt_loops", integer(value)?),
+        "skirt_gap" => ("adhesion.skirt_distance", number(value)?),
+        "brim_width" => ("adhesion.brim_width", number(value)?),
+        "speed_wall_0" => ("gcode.feedrates.external_perimeter", number(value)?),
+        "speed_wall_x" => ("gcode.feedrates.perimeter", number(value)?),
+        "speed_topbottom" => ("gcode.feedrates.solid_infill", number(value)?),
+        "speed_infill" => ("gcode.feedrates.sparse_infill", number(value)?),
+        "speed_support" => ("gcode.feedrates.support", number(value)?),
+        "speed_support_interface" => ("gcode.feedrates.support_interface", number(value)?),
+        "speed_travel" => ("gcode.feedrates.travel", number(value)?),
+        "machine_gcode_flavor" => (
+            "gcode.flavor",
+            choice(
+                value,
+                &[
+                    ("RepRap (Marlin/Sprinter)", "marlin"),
+                    ("RepRap (RepRap)", "rep_rap_firmware"),
+                ],
+            )?,
+        ),
+        "relative_extrusion" => (
+            "gcode.extrusion_mode",
+            choice(value, &[("false", "absolute"), ("true", "relative")])?,
+        ),
+        "machine_start_gcode" => ("gcode.start_gcode", gcode(value, &['{'])?),
+        "machine_end_gcode" => ("gcode.end_gcode", gcode(value, &['{'])?),
+        "material_diameter" => ("gcode.filament_diameter", number(value)?),
+        "material_print_temperature" => ("gcode.nozzle_temperature", number(value)?),
+        "material_bed_temperature" => ("gcode.bed_temperature", number(value)?),
+        "cool_fan_speed_min" => ("cooling.min_fan_speed", number(value)?),
+        "cool_fan_speed_max" => ("cooling.max_fan_speed", number(value)?),
+        "cool_min_layer_time" => ("cooling.min_layer_time", number(value)?),
+        "cool_min_speed" => ("cooling.min_print_speed", number(value)?),
+        "retraction_amount" => ("retraction.length", number(value)?),
+        "retraction_speed" => ("retraction.speed", number(value)?),
+        "retraction_min_travel" => ("retraction.min_travel", number(value)?),
+        "retraction_combing" => (
+            "retraction.avoid_crossing_perimeters",
+            Value::Bool(single(value)? != "off"),
+        ),
+        _ => return None,
+    };
+    Some(setting)
+}
+
+/// Returns the value of a setting override in a Cura definition as text,
+/// or `None` if it's a formula.
+///
+/// Overrides give either a `default_value`, or a `value` which is a Python
+/// expression that takes priority. Only expressions that are plain values
+/// can be imported.
+fn cura_value(setting: &Value) -> Option<String> {
+    if let Some(Value::String(expression)) = setting.get("value") {
+        let expression = expression.trim();
+        return match expression {
+            "True" => Some("true".to_string()),
+            "False" => Some("false".to_string()),
+            _ if expression.parse::<f64>().is_ok() => Some(expression.to_string()),
+            _ => {
+                let quoted = |q| expression.strip_prefix(q).and_then(|e| e.strip_suffix(q));
+                quoted('\'').or_else(|| quoted('"')).map(str::to_string)
+            }
+        };
+    }
+    match setting.get("default_value")? {
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Imports the setting overrides of a Cura `.def.json` machine definition.
+///
+/// Settings from the definitions it inherits from aren't imported, so the
+/// `inherits` key is reported in `ImportedConfig::unmapped`, along with any
+/// other settings that can't be imported.
+pub fn import_cura_definition(text: &str) -> Result<ImportedConfig, ConfigError> {
+    let definition: Map<String, Value> =
+        serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+    let mut import = ImportedConfig::default();
+    let mut overrides = Map::new();
+    for (key, value) in definition {
+        match (key.as_str(), value) {
+            ("version" | "name" | "metadata", _) => {}
+            ("overrides", Value::Object(o)) => overrides = o,
+            (_, value) => import.report_unmapped(None, &key, &value.to_string()),
+        }
+    }
+
+    // The bed size is split across several settings.
+    let mut size = |key| {
+        let value = overrides.remove(key).as_ref().and_then(cura_value);
+        value.as_deref().and_then(number).and_then(|n| n.as_f64())
+    };
+    let (width, depth) = (size("machine_width"), size("machine_depth"));
+    let centered = overrides
+        .remove("machine_center_is_zero")
+        .as_ref()
+        .and_then(cura_value)
+        .is_some_and(|v| v == "true");
+    let retraction_enable = overrides.remove("retraction_enable");
+    match (width, depth) {
+        (Some(width), Some(depth)) => {
+            let (x, y) = if centered {
+                (-width / 2.0, -depth / 2.0)
+            } else {
+                (0.0, 0.0)
+            };
+            let corners = [
+                (x, y),
+                (x + width, y),
+                (x + width, y + depth),
+                (x, y + depth),
+            ];
+            let corners = corners.map(|(x, y)| Value::from(vec![x, y]));
+            insert(
+                &mut import.settings,
+                "bed_shape",
+                Value::from(corners.to_vec()),
+            );
+        }
+        (None, None) => {}
+        _ => import.report_unmapped(
+            None,
+            "machine_width",
+            "only one of the bed's dimensions is set",
+        ),
+    }
+
+    for (key, setting) in overrides {
+        let value = cura_value(&setting);
+        match value.as_deref().and_then(|v| cura_setting(&key, v)) {
+            Some((path, value)) => insert(&mut import.settings, path, value),
+            None => {
+                let value = value.unwrap_or_else(|| setting.to_string());
+                import.report_unmapped(None, &key, &value);
+            }
+        }
+    }
+
+    // Retraction is on by default, so only turning it off changes anything.
+    // Turning it off wins over `retraction_amount`.
+    if let Some(setting) = retraction_enable {
+        let value = cura_value(&setting);
+        match value.as_deref().and_then(boolean) {
+            Some(Value::Bool(false)) => {
+                insert(&mut import.settings, "retraction.length", Value::from(0.0));
+            }
+            Some(_) => {}
+            None => {
+                let value = value.unwrap_or_else(|| setting.to_string());
+                import.report_unmapped(None, "retraction_enable", &value);
+            }
+        }
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{InfillType, SeamPosition};
+    use crate::gcode::GcodeFlavor;
+    use crate::profile::ProfileLibrary;
+    use float_eq::assert_float_eq;
+
+    fn unmapped_keys(import: &ImportedConfig) -> Vec<&str> {
+        import.unmapped.iter().map(|s| s.key.as_str()).collect()
+    }
+
+    #[test]
+    fn prusa_config() {
+        let import = import_prusa_ini(
+            "# generated by PrusaSlicer 2.6.0
+layer_height = 0.15
+first_layer_height = 75%
+perimeters = 3
+fill_density = 15%
+fill_pattern = gyroid
+seam_position = rear
+temperature = 215,215
+retract_length = 0.6
+slowdown_below_layer_time = 8
+bed_shape = 0x0,250x0,250x210,0x210
+gcode_flavor = marlin2
+start_gcode = G28 ; home\\nM109 S[first_layer_temperature]
+end_gcode = M84\\n
+ironing = 1
+",
+        )
+        .unwrap();
+
+        let config = import.config().unwrap();
+        assert_float_eq!(config.layer_height, 0.15, abs <= 1e-6);
+        assert_eq!(config.perimeters, 3);
+        assert_float_eq!(config.infill.density, 15.0, abs <= 1e-6);
+        assert_eq!(config.infill.pattern, InfillType::Gyroid);
+        assert_eq!(config.seam_position, SeamPosition::Rear);
+        assert_float_eq!(config.gcode.nozzle_temperature, 215.0, abs <= 1e-6);
+        assert_float_eq!(config.retraction.length, 0.6, abs <= 1e-6);
+        assert_float_eq!(config.cooling.min_layer_time, 8.0, abs <= 1e-6);
+        assert_eq!(config.bed_shape[2], (250.0, 210.0));
+        assert_eq!(config.gcode.flavor, GcodeFlavor::Marlin);
+        assert_eq!(config.gcode.end_gcode, "M84\n");
+        // Relative first layer heights and G-code placeholders can't be
+        // imported.
+        assert_eq!(
+            unmapped_keys(&import),
+            ["first_layer_height", "start_gcode", "ironing"]
+        );
+    }
+
+    #[test]
+    fn prusa_nearest_seam_is_unmapped() {
+        // There's no seam position that picks the nearest point.
+        let import = import_prusa_ini("seam_position = nearest\n").unwrap();
+
+        assert_eq!(unmapped_keys(&import), ["seam_position"]);
+        assert_eq!(
+            import.config().unwrap().seam_position,
+            SeamPosition::Aligned
+        );
+    }
+
+    #[test]
+    fn prusa_bundle() {
+        let import = import_prusa_ini(
+            "[presets]
+print = Fine
+
+[printer:*common*]
+nozzle_diameter = 0.6
+retract_length = 2
+
+[printer:Direct]
+inherits = *common*
+retract_length = 0.8
+temperature = 200
+
+[filament:PETG]
+temperature = 240
+bed_temperature = 80
+
+[print:Fine]
+layer_height = 0.1
+",
+        )
+        .unwrap();
+
+        assert_eq!(import.profiles.len(), 4);
+        // A printer profile can't set the nozzle temperature.
+        assert_eq!(
+            import.unmapped[1],
+            UnmappedSetting {
+                section: Some("printer:Direct".to_string()),
+                key: "temperature".to_string(),
+                value: "200".to_string(),
+            }
+        );
+        assert_eq!(unmapped_keys(&import), ["print", "temperature"]);
+
+        let mut library = ProfileLibrary::new();
+        for profile in import.profiles {
+            assert_eq!(Profile::from_toml(&profile.to_toml()).unwrap(), profile);
+            library.add(profile);
+        }
+        let config = library.config(&["Direct", "PETG", "Fine"]).unwrap();
+        assert_float_eq!(config.nozzle_diameter, 0.6, abs <= 1e-6);
+        assert_float_eq!(config.retraction.length, 0.8, abs <= 1e-6);
+        assert_float_eq!(config.gcode.nozzle_temperature, 240.0, abs <= 1e-6);
+        assert_float_eq!(config.layer_height, 0.1, abs <= 1e-6);
+    }
+
+    #[test]
+    fn cura_definition() {
+        let import = import_cura_definition(
+            r#"{
+                "version": 2,
+                "name": "Example",
+                "inherits": "creality_base",
+                "metadata": { "visible": true },
+                "overrides": {
+                    "machine_width": { "default_value": 230 },
+                    "machine_depth": { "default_value": 200 },
+                    "machine_center_is_zero": { "default_value": true },
+                    "machine_nozzle_size": { "default_value": 0.6 },
+                    "layer_height": { "value": "0.3" },
+                    "infill_pattern": { "value": "'grid'" },
+                    "support_enable": { "value": "True" },
+                    "speed_infill": { "value": "speed_print" },
+                    "retraction_amount": { "default_value": 2 },
+                    "retraction_enable": { "default_value": false },
+                    "machine_start_gcode": { "default_value": "G28\nM104 S{material_print_temperature}" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let config = import.config().unwrap();
+        assert_eq!(
+            config.bed_shape,
+            [
+                (-115.0, -100.0),
+                (115.0, -100.0),
+                (115.0, 100.0),
+                (-115.0, 100.0)
+            ]
+        );
+        assert_float_eq!(config.nozzle_diameter, 0.6, abs <= 1e-6);
+        assert_float_eq!(config.layer_height, 0.3, abs <= 1e-6);
+        assert_eq!(config.infill.pattern, InfillType::Grid);
+        assert!(config.support.enabled);
+        assert_float_eq!(config.retraction.length, 0.0, abs <= 1e-6);
+        let mut unmapped = unmapped_keys(&import);
+        unmapped.sort();
+        assert_eq!(
+            unmapped,
+            ["inherits", "machine_start_gcode", "speed_infill"]
+        );
+    }
+
+    #[test]
+    fn cura_retraction_enabled() {
+        let import = import_cura_definition(
+            r#"{
+                "overrides": {
+                    "retraction_amount": { "default_value": 2 },
+                    "retraction_enable": { "value": "True" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let config = import.config().unwrap();
+        assert_float_eq!(config.retraction.length, 2.0, abs <= 1e-6);
+        assert!(import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn format_from_content() {
+        let cura =
+            import_config("\n{ \"overrides\": { \"layer_height\": { \"default_value\": 0.1 } } }")
+                .unwrap();
+        assert_float_eq!(cura.config().unwrap().layer_height, 0.1, abs <= 1e-6);
+        let prusa = import_config("layer_height = 0.15\n").unwrap();
+        assert_float_eq!(prusa.config().unwrap().layer_height, 0.15, abs <= 1e-6);
+        match import_config("solid cube\n  facet normal 0 0 1\n") {
+            Err(ConfigError::Parse(message)) => {
+                assert!(message.starts_with("not a Cura definition or PrusaSlicer config"))
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use cgmath::Vector2;
+
+use crate::contour::Polyline;
+use crate::infill::InfillPattern;
+use crate::region::Region;
+
+/// The number of samples taken per line spacing when tracing the surface.
+const SAMPLES_PER_SPACING: f32 = 8.0;
+
+/// Identifies an edge of the sampling grid.
+///
+/// `(i, j, false)` is the horizontal edge from sample `(i, j)` to
+/// `(i + 1, j)`, and `(i, j, true)` is the vertical edge from `(i, j)` to
+/// `(i, j + 1)`.
+type EdgeKey = (i64, i64, bool);
+
+/// Infill that follows the cross section of a gyroid surface.
+///
+/// The gyroid is the triply periodic surface
+///
+/// ```text
+/// sin(x)cos(y) + sin(y)cos(z) + sin(z)cos(x) = 0
+/// ```
+///
+/// Slicing it at every layer gives wavy lines that shift with Z. Stacked up,
+/// they form a continuous surface that is equally strong in every direction.
+pub struct Gyroid {
+    spacing: f32,
+}
+
+impl Gyroid {
+    pub fn new(spacing: f32) -> Self {
+        Self { spacing }
+    }
+
+    /// The scale factor applied to model coordinates before evaluating the
+    /// surface. Every period of the surface contains two sheets, so a period
+    /// of `2 * spacing` puts adjacent lines roughly `spacing` apart.
+    fn frequency(&self) -> f32 {
+        PI / self.spacing
+    }
+
+    fn value(&self, x: f32, y: f32, z: f32) -> f32 {
+        let k = self.frequency();
+        let (x, y, z) = (x * k, y * k, z * k);
+        x.sin() * y.cos() + y.sin() * z.cos() + z.sin() * x.cos()
+    }
+}
+
+impl InfillPattern for Gyroid {
+    fn generate(&self, region: &Region, _layer: usize, z: f32) -> Vec<Polyline> {
+        let Some((min, max)) = region.bounding_box() else {
+            return Vec::new();
+        };
+        // Align the sampling grid to the origin so every layer and island is
+        // traced from the same samples.
+        let step = self.spacing / SAMPLES_PER_SPACING;
+        let i0 = (min.x / step).floor() as i64 - 1;
+        let i1 = (max.x / step).ceil() as i64 + 1;
+        let j0 = (min.y / step).floor() as i64 - 1;
+        let j1 = (max.y / step).ceil() as i64 + 1;
+        let width = (i1 - i0 + 1) as usize;
+        let position = |i: i64, j: i64| Vector2::new(i as f32 * step, j as f32 * step);
+
+        let mut samples = Vec::with_capacity(width * (j1 - j0 + 1) as usize);
+        for j in j0..=j1 {
+            for i in i0..=i1 {
+                let p = position(i, j);
+                samples.push(self.value(p.x, p.y, z));
+            }
+        }
+        let sample = |i: i64, j: i64| samples[(j - j0) as usize * width + (i - i0) as usize];
+
+        // Where the surface crosses a grid edge.
+        let crossing = |(i, j, vertical): EdgeKey| {
+            let (i2, j2) = if vertical { (i, j + 1) } else { (i + 1, j) };
+            let (a, b) = (sample(i, j), sample(i2, j2));
+            let t = a / (a - b);
+            let (p0, p1) = (position(i, j), position(i2, j2));
+            p0 + (p1 - p0) * t
+        };
+
+        // Marching squares: find the segments of the zero contour within
+        // every grid cell.
+        let mut segments: Vec<(EdgeKey, EdgeKey)> = Vec::new();
+        for j in j0..j1 {
+            for i in i0..i1 {
+                let corners = [
+                    sample(i, j),
+                    sample(i + 1, j),
+                    sample(i + 1, j + 1),
+                    sample(i, j + 1),
+                ];
+                // Edges in the same order as the corners they start from.
+                let edges = [
+                    (i, j, false),
+                    (i + 1, j, true),
+                    (i, j + 1, false),
+                    (i, j, true),
+                ];
+                let crosses = |e: usize| (corners[e] >= 0.0) != (corners[(e + 1) % 4] >= 0.0);
+                let crossed = (0..4).filter(|&e| crosses(e)).collect::<Vec<_>>();
+                match crossed.len() {
+                    2 => segments.push((edges[crossed[0]], edges[crossed[1]])),
+                    4 => {
+                        // A saddle: use the center of the cell to decide which
+                        // pairs of edges are connected.
+                        let center = corners.iter().sum::<f32>() / 4.0;
+                        if (center >= 0.0) == (corners[0] >= 0.0) {
+                            segments.push((edges[0], edges[1]));
+                            segments.push((edges[2], edges[3]));
+                        } else {
+                            segments.push((edges[3], edges[0]));
+                            segments.push((edges[1], edges[2]));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let polylines = chain_segments(&segments)
+            .into_iter()
+            .map(|edges| Polyline::from_points(edges.into_iter().map(crossing).collect()))
+            .collect::<Vec<_>>();
+        region.clip_polylines(&polylines)
+    }
+}
+
+/// Joins segments that share an edge into chains of edges.
+fn chain_segments(segments: &[(EdgeKey, EdgeKey)]) -> Vec<Vec<EdgeKey>> {
+    let mut by_edge: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+    for (index, (a, b)) in segments.iter().enumerate() {
+        by_edge.entry(*a).or_default().push(index);
+        by_edge.entry(*b).or_default().push(index);
+    }
+
+    let mut used = vec![false; segments.len()];
+    // Follows the chain from `edge`, leaving segment `from`, until it ends or
+    // loops back on itself.
+    let walk = |mut from: usize, mut edge: EdgeKey, used: &mut Vec<bool>| {
+        let mut chain = Vec::new();
+        while let Some(&next) = by_edge[&edge].iter().find(|&&s| s != from && !used[s]) {
+            used[next] = true;
+            let (a, b) = segments[next];
+            edge = if a == edge { b } else { a };
+            chain.push(edge);
+            from = next;
+        }
+        chain
+    };
+
+    let mut chains = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (a, b) = segments[start];
+        let forward = walk(start, b, &mut used);
+        let backward = walk(start, a, &mut used);
+        let mut chain = backward.into_iter().rev().collect::<Vec<_>>();
+        chain.push(a);
+        chain.push(b);
+        chain.extend(forward);
+        chains.push(chain);
+    }
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infill::tests::assert_inside_square;
+    use crate::test_util::square;
+
+    #[test]
+    fn gyroid_follows_surface() {
+        let gyroid = Gyroid::new(2.0);
+        let region = square(10.0);
+        let z = 1.3;
+
+        let paths = gyroid.generate(&region, 0, z);
+
+        assert!(!paths.is_empty());
+        assert_inside_square(&paths, 10.0);
+        for p in paths.iter().flat_map(|p| p.points()) {
+            assert!(gyroid.value(p.x, p.y, z).abs() < 0.05, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn gyroid_paths_are_joined() {
+        // Tracing produces one tiny segment per grid cell; these should be
+        // joined into long continuous paths.
+        let gyroid = Gyroid::new(2.0);
+        let paths = gyroid.generate(&square(10.0), 0, 0.6);
+        let points: usize = paths.iter().map(|p| p.points().len()).sum();
+        assert!(points > paths.len() * 10);
+    }
+
+    #[test]
+    fn gyroid_changes_with_z() {
+        let gyroid = Gyroid::new(2.0);
+        let region = square(10.0);
+        assert_ne!(
+            gyroid.generate(&region, 0, 0.2),
+            gyroid.generate(&region, 1, 0.4)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use cgmath::Vector2;
+
+use crate::contour::Polyline;
+use crate::infill::InfillPattern;
+use crate::region::Region;
+
+/// Infill that builds a 3D honeycomb out of stacked zig-zag lines.
+///
+/// Every layer is a set of zig-zag rows whose flat sections grow and shrink
+/// with Z. Neighbouring rows meet along their flats, closing off hexagonal
+/// cells, and the rows switch between running along X and along Y every
+/// cycle. Stacked up, the layers form an interlocking lattice that is light
+/// but stiff in every direction.
+pub struct Honeycomb {
+    spacing: f32,
+}
+
+impl Honeycomb {
+    pub fn new(spacing: f32) -> Self {
+        Self { spacing }
+    }
+
+    /// The height of one cycle, after which the row direction flips.
+    fn cycle_height(&self) -> f32 {
+        2.0 * self.spacing
+    }
+
+    /// Builds one zig-zag row along the X axis, centered on `y`.
+    ///
+    /// The row repeats every `2 * spacing` and is made of alternating high
+    /// and low flats of length `flat`, joined by diagonal ramps. The flats
+    /// lie half way to the next row, so a row `spacing` away that is
+    /// `flip`ped runs along the same flats.
+    fn row(&self, y: f32, x_range: (f32, f32), flat: f32, flip: bool) -> Vec<Vector2<f32>> {
+        let s = self.spacing;
+        let amplitude = if flip { -s / 2.0 } else { s / 2.0 };
+        let period = 2.0 * s;
+        let first = (x_range.0 / period).floor() as i64;
+        let last = (x_range.1 / period).ceil() as i64;
+        let mut points = Vec::new();
+        for k in first..last {
+            let x = k as f32 * period;
+            points.push(Vector2::new(x, y + amplitude));
+            points.push(Vector2::new(x + flat, y + amplitude));
+            points.push(Vector2::new(x + s, y - amplitude));
+            points.push(Vector2::new(x + s + flat, y - amplitude));
+        }
+        points.push(Vector2::new(last as f32 * period, y + amplitude));
+        points
+    }
+}
+
+impl InfillPattern for Honeycomb {
+    fn generate(&self, region: &Region, _layer: usize, z: f32) -> Vec<Polyline> {
+        let Some((min, max)) = region.bounding_box() else {
+            return Vec::new();
+        };
+        let cycle = z / self.cycle_height();
+        let phase = cycle.fract();
+        // A triangle wave from 0 at the start of a cycle, to 1 half way, back
+        // down to 0 at the end.
+        let wave = 1.0 - (2.0 * phase - 1.0).abs();
+        let flat = wave * self.spacing / 2.0;
+        // On odd cycles the rows run along Y. Build them along X in a swapped
+        // coordinate system, and swap back afterwards.
+        let along_y = (cycle.floor() as i64).rem_euclid(2) == 1;
+        let (min, max) = if along_y {
+            (Vector2::new(min.y, min.x), Vector2::new(max.y, max.x))
+        } else {
+            (min, max)
+        };
+
+        let first = (min.y / self.spacing).floor() as i64;
+        let last = (max.y / self.spacing).ceil() as i64;
+        let rows = (first..=last)
+            .map(|j| {
+                let y = j as f32 * self.spacing;
+                let mut points = self.row(y, (min.x, max.x), flat, j.rem_euclid(2) == 1);
+                if along_y {
+                    points.iter_mut().for_each(|p| *p = Vector2::new(p.y, p.x));
+                }
+                Polyline::from_points(points)
+            })
+            .collect::<Vec<_>>();
+        region.clip_polylines(&rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infill::tests::assert_inside_square;
+    use crate::test_util::square;
+
+    /// True if more of the flats in `paths` run along X than along Y.
+    fn mostly_along_x(paths: &[Polyline]) -> bool {
+        let (dx, dy) = paths
+            .iter()
+            .flat_map(|p| p.points().windows(2).map(|w| w[1] - w[0]))
+            .fold((0.0, 0.0), |(x, y), d| match (d.x.abs(), d.y.abs()) {
+                (dx, dy) if dy < 1e-5 => (x + dx, y),
+                (dx, dy) if dx < 1e-5 => (x, y + dy),
+                _ => (x, y),
+            });
+        dx > dy
+    }
+
+    #[test]
+    fn honeycomb_direction_alternates() {
+        let honeycomb = Honeycomb::new(2.0);
+        let region = square(10.0);
+
+        let first_cycle = honeycomb.generate(&region, 0, 1.0);
+        let second_cycle = honeycomb.generate(&region, 0, 5.0);
+
+        assert_inside_square(&first_cycle, 10.0);
+        assert_inside_square(&second_cycle, 10.0);
+        assert!(mostly_along_x(&first_cycle));
+        assert!(!mostly_along_x(&second_cycle));
+    }
+
+    /// The horizontal segments of `points` at height `y`.
+    fn flats_at(points: &[Vector2<f32>], y: f32) -> Vec<(f32, f32)> {
+        points
+            .windows(2)
+            .filter(|w| (w[0].y - y).abs() < 1e-5 && (w[1].y - y).abs() < 1e-5)
+            .map(|w| (w[0].x, w[1].x))
+            .collect()
+    }
+
+    #[test]
+    fn honeycomb_rows_share_flats() {
+        // Adjacent rows are mirrored so that they meet along their flats,
+        // which closes off the hexagonal cells between them.
+        let honeycomb = Honeycomb::new(2.0);
+        let a = honeycomb.row(0.0, (0.0, 8.0), 0.5, false);
+        let b = honeycomb.row(2.0, (0.0, 8.0), 0.5, true);
+
+        let shared = flats_at(&a, 1.0);
+
+        assert_eq!(shared.len(), 2);
+        assert_eq!(shared, flats_at(&b, 1.0));
+        assert!(shared.iter().all(|(x0, x1)| (x1 - x0 - 0.5).abs() < 1e-5));
+        // Nothing else of either row crosses between them.
+        assert!(a.iter().all(|p| p.y <= 1.0 + 1e-5));
+        assert!(b.iter().all(|p| p.y >= 1.0 - 1e-5));
+    }
+}
@@ -0,0 +1,165 @@
+use std::f32::consts::PI;
+
+use cgmath::Vector2;
+
+use crate::contour::Polyline;
+use crate::infill::InfillPattern;
+use crate::region::{LineSegment, Region};
+
+/// Generates a family of parallel lines at `angle` that covers `region`.
+///
+/// Lines are anchored to the origin rather than to the region so that the
+/// same lines are produced for every layer and for every island, which keeps
+/// infill aligned from one layer to the next.
+pub(crate) fn hatch(region: &Region, angle: f32, spacing: f32) -> Vec<LineSegment> {
+    let Some((min, max)) = region.bounding_box() else {
+        return Vec::new();
+    };
+    let direction = Vector2::new(angle.cos(), angle.sin());
+    let normal = Vector2::new(-direction.y, direction.x);
+    let corners = [
+        Vector2::new(min.x, min.y),
+        Vector2::new(max.x, min.y),
+        Vector2::new(max.x, max.y),
+        Vector2::new(min.x, max.y),
+    ];
+    let project = |axis: Vector2<f32>| {
+        corners
+            .iter()
+            .map(|c| c.x * axis.x + c.y * axis.y)
+            .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)))
+    };
+    let (along_min, along_max) = project(direction);
+    let (across_min, across_max) = project(normal);
+
+    let first = (across_min / spacing).ceil() as i64;
+    let last = (across_max / spacing).floor() as i64;
+    let lines = (first..=last)
+        .map(|i| {
+            let offset = normal * (i as f32 * spacing);
+            LineSegment::new(
+                offset + direction * along_min,
+                offset + direction * along_max,
+            )
+        })
+        .collect::<Vec<_>>();
+    region.clip_segments(&lines)
+}
+
+fn hatch_all(region: &Region, angles: &[f32], spacing: f32) -> Vec<Polyline> {
+    angles
+        .iter()
+        .flat_map(|&angle| hatch(region, angle, spacing))
+        .map(|s| Polyline::from_points(vec![s.start, s.end]))
+        .collect()
+}
+
+/// Parallel lines that alternate between 45° and -45° on every layer.
+pub struct Rectilinear {
+    spacing: f32,
+}
+
+impl Rectilinear {
+    pub fn new(spacing: f32) -> Self {
+        Self { spacing }
+    }
+}
+
+impl InfillPattern for Rectilinear {
+    fn generate(&self, region: &Region, layer: usize, _z: f32) -> Vec<Polyline> {
+        let angle = if layer.is_multiple_of(2) {
+            PI / 4.0
+        } else {
+            -PI / 4.0
+        };
+        hatch_all(region, &[angle], self.spacing)
+    }
+}
+
+/// Lines at both 45° and -45° on every layer.
+pub struct Grid {
+    spacing: f32,
+}
+
+impl Grid {
+    pub fn new(spacing: f32) -> Self {
+        Self { spacing }
+    }
+}
+
+impl InfillPattern for Grid {
+    fn generate(&self, region: &Region, _layer: usize, _z: f32) -> Vec<Polyline> {
+        hatch_all(region, &[PI / 4.0, -PI / 4.0], self.spacing)
+    }
+}
+
+/// Lines at 0°, 60° and 120° on every layer, forming a triangular grid.
+pub struct Triangles {
+    spacing: f32,
+}
+
+impl Triangles {
+    pub fn new(spacing: f32) -> Self {
+        Self { spacing }
+    }
+}
+
+impl InfillPattern for Triangles {
+    fn generate(&self, region: &Region, _layer: usize, _z: f32) -> Vec<Polyline> {
+        hatch_all(region, &[0.0, PI / 3.0, 2.0 * PI / 3.0], self.spacing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::square;
+    use float_eq::assert_float_eq;
+
+    fn direction(p: &Polyline) -> Vector2<f32> {
+        let d = p.last().unwrap() - p.first().unwrap();
+        d / p.length()
+    }
+
+    #[test]
+    fn rectilinear_alternates_direction() {
+        let region = square(10.0);
+        let pattern = Rectilinear::new(2.5);
+
+        let even = pattern.generate(&region, 0, 0.2);
+        let odd = pattern.generate(&region, 1, 0.4);
+
+        assert!(!even.is_empty());
+        assert!(!odd.is_empty());
+        for p in &even {
+            let d = direction(p);
+            assert_float_eq!(d.x.abs(), d.y.abs(), abs <= 0.001);
+            assert!(d.x * d.y > 0.0);
+        }
+        for p in &odd {
+            assert!(direction(p).x * direction(p).y < 0.0);
+        }
+    }
+
+    #[test]
+    fn solid_infill_covers_region() {
+        // With lines one extrusion width apart the total line length times
+        // the extrusion width should roughly equal the area being filled.
+        let region = square(10.0);
+        let infill = Rectilinear::new(0.5).generate(&region, 0, 0.2);
+        let length: f32 = infill.iter().map(Polyline::length).sum();
+        assert_float_eq!(length * 0.5, 100.0, rmax <= 0.05);
+    }
+
+    #[test]
+    fn triangles_use_three_directions() {
+        let region = square(10.0);
+        let infill = Triangles::new(3.0).generate(&region, 0, 0.2);
+        let horizontal = infill
+            .iter()
+            .filter(|p| direction(p).y.abs() < 0.001)
+            .count();
+        assert!(horizontal > 0);
+        assert!(horizontal < infill.len());
+    }
+}
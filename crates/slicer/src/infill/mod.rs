@@ -0,0 +1,266 @@
+mod gyroid;
+mod honeycomb;
+mod lines;
+
+pub use gyroid::*;
+pub use honeycomb::*;
+pub use lines::*;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::{InfillType, SlicerConfig};
+use crate::contour::Polyline;
+use crate::region::{LineSegment, Region};
+
+/// A generator for one style of infill.
+///
+/// Patterns are a function of the layer so that they can vary through the
+/// height of the model. Implementations must clip their output to the
+/// region they are given.
+pub trait InfillPattern {
+    /// Generates paths that fill `region`, the area inside the innermost
+    /// perimeter of layer number `layer` whose top is at height `z`.
+    fn generate(&self, region: &Region, layer: usize, z: f32) -> Vec<Polyline>;
+}
+
+/// Computes the distance between adjacent infill lines.
+///
+/// A density of 100% means lines are placed one extrusion width apart. When
+/// a pattern draws several line families on the same layer each family is
+/// spaced further apart, so that the total amount of material matches the
+/// requested density. Returns `None` for a density of 0%.
+pub fn line_spacing(density: f32, extrusion_width: f32, families: usize) -> Option<f32> {
+    let density = density.clamp(0.0, 100.0) / 100.0;
+    if density <= 0.0 || extrusion_width <= 0.0 {
+        return None;
+    }
+    Some(extrusion_width * families as f32 / density)
+}
+
+/// Builds the pattern generator for `pattern` at the given density.
+///
+/// Returns `None` if the density is too low to produce any infill.
+pub fn infill_pattern(
+    pattern: InfillType,
+    density: f32,
+    extrusion_width: f32,
+) -> Option<Box<dyn InfillPattern>> {
+    let spacing = |families| line_spacing(density, extrusion_width, families);
+    Some(match pattern {
+        InfillType::Rectilinear => Box::new(Rectilinear::new(spacing(1)?)),
+        InfillType::Grid => Box::new(Grid::new(spacing(2)?)),
+        InfillType::Triangles => Box::new(Triangles::new(spacing(3)?)),
+        InfillType::Gyroid => Box::new(Gyroid::new(spacing(1)?)),
+        InfillType::Honeycomb => Box::new(Honeycomb::new(spacing(1)?)),
+    })
+}
+
+/// Generates infill for `region`, the area inside the innermost perimeter of
+/// layer number `layer` at height `z`.
+///
+/// The returned paths are clipped to `region`, and paths whose ends are
+/// close together are joined to avoid unnecessary travel moves.
+pub fn generate_infill(
+    region: &Region,
+    layer: usize,
+    z: f32,
+    config: &SlicerConfig,
+) -> Vec<Polyline> {
+    let Some(pattern) = infill_pattern(
+        config.infill.pattern,
+        config.infill.density,
+        config.extrusion_width,
+    ) else {
+        return Vec::new();
+    };
+    let paths = pattern.generate(region, layer, z);
+    let max_gap = line_spacing(config.infill.density, config.extrusion_width, 1)
+        .unwrap_or(config.extrusion_width)
+        * 2.0;
+    connect_polylines(paths, region, max_gap, config.extrusion_width / 2.0)
+}
+
+/// Greedily chains polylines together, end to end.
+///
+/// Starting from the first path, the path with the endpoint nearest to
+/// either end of the chain is joined on (reversing it if needed) as long as
+/// that endpoint is within `max_gap` and the connecting line stays inside
+/// `region` grown by `tolerance`. Otherwise a new chain is started.
+pub fn connect_polylines(
+    mut paths: Vec<Polyline>,
+    region: &Region,
+    max_gap: f32,
+    tolerance: f32,
+) -> Vec<Polyline> {
+    if paths.len() < 2 {
+        return paths;
+    }
+    let bounds = region.offset(tolerance);
+    let can_link = |from: Vector2<f32>, to: Vector2<f32>| {
+        let link = LineSegment::new(from, to);
+        if link.length() > max_gap {
+            return false;
+        }
+        let clipped: f32 = bounds
+            .clip_segments(&[link])
+            .iter()
+            .map(LineSegment::length)
+            .sum();
+        clipped >= link.length() * 0.999
+    };
+    // Finds the remaining path with an endpoint nearest to `point`. Returns
+    // the index of that path and whether it needs to be reversed so that
+    // its first point is the nearest.
+    let nearest = |paths: &[Polyline], point: Vector2<f32>| {
+        paths
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| {
+                [
+                    (i, false, (p.first().unwrap() - point).magnitude()),
+                    (i, true, (p.last().unwrap() - point).magnitude()),
+                ]
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap()
+    };
+
+    let mut connected = Vec::new();
+    let mut current = paths.remove(0);
+    while !paths.is_empty() {
+        let tail = current.last().unwrap();
+        let head = current.first().unwrap();
+        let after = nearest(&paths, tail);
+        let before = nearest(&paths, head);
+
+        let mut candidates = [(after, true), (before, false)];
+        candidates.sort_by(|a, b| a.0 .2.total_cmp(&b.0 .2));
+        let choice = candidates
+            .into_iter()
+            .find(|&((index, reverse, _), at_tail)| {
+                let path = &paths[index];
+                let end = if reverse { path.last() } else { path.first() };
+                can_link(if at_tail { tail } else { head }, end.unwrap())
+            });
+
+        let Some(((index, reverse, distance), at_tail)) = choice else {
+            connected.push(std::mem::replace(&mut current, paths.remove(0)));
+            continue;
+        };
+        let mut next = paths.remove(index);
+        if reverse {
+            next.reverse();
+        }
+        // Don't repeat the shared point if the paths already touch.
+        let skip = if distance > 0.0 { 0 } else { 1 };
+        if at_tail {
+            current.points_vec().extend(next.points().iter().skip(skip));
+        } else {
+            next.reverse();
+            let mut points = next.points()[..next.points().len() - skip].to_vec();
+            points.extend_from_slice(current.points());
+            current = Polyline::from_points(points);
+        }
+    }
+    connected.push(current);
+    connected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InfillConfig;
+    use crate::contour::ClosedPath;
+    use crate::test_util::square;
+
+    /// Asserts that every point of `paths` lies inside the square returned
+    /// by `square(size)`.
+    pub(super) fn assert_inside_square(paths: &[Polyline], size: f32) {
+        for p in paths.iter().flat_map(|p| p.points()) {
+            assert!((-0.001..=size + 0.001).contains(&p.x), "{:?}", p);
+            assert!((-0.001..=size + 0.001).contains(&p.y), "{:?}", p);
+        }
+    }
+
+    fn config(pattern: InfillType, density: f32) -> SlicerConfig {
+        SlicerConfig {
+            extrusion_width: 0.5,
+            infill: InfillConfig { pattern, density },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spacing_from_density() {
+        use float_eq::assert_float_eq;
+        assert_eq!(line_spacing(0.0, 0.5, 1), None);
+        assert_float_eq!(line_spacing(100.0, 0.5, 1).unwrap(), 0.5, abs <= 0.0001);
+        assert_float_eq!(line_spacing(20.0, 0.5, 1).unwrap(), 2.5, abs <= 0.0001);
+        assert_float_eq!(line_spacing(20.0, 0.5, 2).unwrap(), 5.0, abs <= 0.0001);
+    }
+
+    #[test]
+    fn every_pattern_is_clipped_to_region() {
+        let region = square(10.0);
+        for pattern in [
+            InfillType::Rectilinear,
+            InfillType::Grid,
+            InfillType::Triangles,
+            InfillType::Gyroid,
+            InfillType::Honeycomb,
+        ] {
+            let infill = generate_infill(&region, 3, 0.8, &config(pattern, 30.0));
+            assert!(!infill.is_empty(), "{:?}", pattern);
+            assert_inside_square(&infill, 10.0);
+        }
+    }
+
+    #[test]
+    fn zero_density_is_hollow() {
+        let region = square(10.0);
+        assert!(generate_infill(&region, 0, 0.2, &config(InfillType::Gyroid, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn rectilinear_lines_are_connected() {
+        // In a convex region every scan line can be joined to the next one,
+        // so the whole layer is printed as a single path.
+        let region = square(10.0);
+        let infill = generate_infill(&region, 0, 0.2, &config(InfillType::Rectilinear, 20.0));
+        assert_eq!(infill.len(), 1);
+    }
+
+    #[test]
+    fn connections_do_not_cross_holes() {
+        let mut hole = ClosedPath::new();
+        hole.add_point(3.0, 3.0);
+        hole.add_point(3.0, 7.0);
+        hole.add_point(7.0, 7.0);
+        hole.add_point(7.0, 3.0);
+        let mut outer = ClosedPath::new();
+        outer.add_point(0.0, 0.0);
+        outer.add_point(10.0, 0.0);
+        outer.add_point(10.0, 10.0);
+        outer.add_point(0.0, 10.0);
+        let region = Region::from_paths(&[outer, hole]);
+        let inner = Region::from_paths(&[{
+            let mut p = ClosedPath::new();
+            p.add_point(3.5, 3.5);
+            p.add_point(6.5, 3.5);
+            p.add_point(6.5, 6.5);
+            p.add_point(3.5, 6.5);
+            p
+        }]);
+
+        let infill = generate_infill(&region, 0, 0.2, &config(InfillType::Rectilinear, 20.0));
+
+        for path in infill {
+            let inside: f32 = inner
+                .clip_polylines(&[path])
+                .iter()
+                .map(Polyline::length)
+                .sum();
+            assert_eq!(inside, 0.0);
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt;
+use std::str::FromStr;
+
+use cgmath::InnerSpace;
+use mandoline_mesh::Triangle;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AdaptiveLayerConfig, SlicerConfig};
+
+/// Faces whose normal is closer to vertical than this are treated as flat.
+/// They lie on a single plane, so thin layers wouldn't make them any
+/// smoother.
+const FLAT_NORMAL_Z: f32 = 0.9999;
+
+/// A top layer thinner than this fraction of the layer height is shared with
+/// the layer below, rather than printed on its own.
+const MIN_TOP_LAYER_RATIO: f32 = 0.5;
+
+/// The thinnest step between boundaries, in mm, so that a zero or negative
+/// layer height can't stall slicing.
+const MIN_STEP: f32 = 0.001;
+
+/// Rounds `z` to the nearest micron, so that layers which should line up
+/// with vertices of the mesh aren't thrown off by accumulated rounding.
+fn round_micros(z: f32) -> f32 {
+    (z * 1_000.0).round() / 1_000.0
+}
+
+/// A layer height that varies with Z.
+///
+/// The profile is a list of `(z, height)` control points, sorted by Z. The
+/// layer height between two points is interpolated linearly, and held
+/// constant below the first point and above the last.
+///
+/// Profiles can be written to and read from text with one point per line,
+/// as the Z and the layer height separated by whitespace. Blank lines and
+/// lines starting with `#` are ignored, so profiles can be edited by hand.
+/// In configuration files they're stored as a list of `[z, height]` pairs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct LayerHeightProfile {
+    points: Vec<(f32, f32)>,
+}
+
+/// An error in the text of a `LayerHeightProfile`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseProfileError {
+    /// The line of the error, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseProfileError {}
+
+impl LayerHeightProfile {
+    /// Creates a profile from `(z, height)` control points. Points are
+    /// sorted by Z.
+    pub fn new(mut points: Vec<(f32, f32)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points }
+    }
+
+    /// Creates a profile that reproduces layers with the given
+    /// `boundaries`.
+    ///
+    /// This is used to save the result of adaptive slicing, so that it can be
+    /// tweaked by hand and sliced again.
+    pub fn from_boundaries(boundaries: &[f32]) -> Self {
+        Self::new(
+            boundaries
+                .windows(2)
+                .map(|w| (w[0], round_micros(w[1] - w[0])))
+                .collect(),
+        )
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        self.points.as_slice()
+    }
+
+    /// Returns the layer height at `z`.
+    pub fn height_at(&self, z: f32) -> Option<f32> {
+        let first = self.points.first()?;
+        let last = self.points.last()?;
+        if z <= first.0 {
+            return Some(first.1);
+        }
+        if z >= last.0 {
+            return Some(last.1);
+        }
+        let next = self.points.partition_point(|p| p.0 <= z);
+        let (z0, h0) = self.points[next - 1];
+        let (z1, h1) = self.points[next];
+        let t = (z - z0) / (z1 - z0);
+        Some(h0 + (h1 - h0) * t)
+    }
+}
+
+impl FromStr for LayerHeightProfile {
+    type Err = ParseProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let error = |message: &str| ParseProfileError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(f32::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| error(&e.to_string()))?;
+            let [z, height] = values[..] else {
+                return Err(error("expected a Z and a layer height"));
+            };
+            if height <= 0.0 {
+                return Err(error("layer height must be positive"));
+            }
+            points.push((z, height));
+        }
+        Ok(Self::new(points))
+    }
+}
+
+impl fmt::Display for LayerHeightProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# z layer_height")?;
+        for (z, height) in &self.points {
+            writeln!(f, "{} {}", z, height)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<(f32, f32)>> for LayerHeightProfile {
+    fn from(points: Vec<(f32, f32)>) -> Self {
+        Self::new(points)
+    }
+}
+
+impl From<LayerHeightProfile> for Vec<(f32, f32)> {
+    fn from(profile: LayerHeightProfile) -> Self {
+        profile.points
+    }
+}
+
+/// Returns the range of Z covered by `triangles`.
+fn z_limits(triangles: &[Triangle]) -> Option<(f32, f32)> {
+    triangles
+        .iter()
+        .flat_map(|t| [t.p0.z, t.p1.z, t.p2.z])
+        .fold(None, |limits, z| match limits {
+            None => Some((z, z)),
+            Some((min, max)) => Some((f32::min(min, z), f32::max(max, z))),
+        })
+}
+
+/// Returns layer boundaries starting at 0, spaced by `height(z)`, that
+/// cover the mesh up to `zmax`. If `first_layer_height` is set, the first
+/// layer is that thick instead.
+///
+/// The last layer ends at `zmax`. If that would leave it thinner than
+/// `min_height`, the last two layers are split evenly instead, so neither is
+/// thicker than the layer below the top one was.
+fn boundaries_from(
+    zmax: f32,
+    first_layer_height: Option<f32>,
+    min_height: f32,
+    mut height: impl FnMut(f32) -> f32,
+) -> Vec<f32> {
+    let zmax = round_micros(zmax);
+    let mut boundaries = vec![0.0];
+    let mut z = 0.0;
+    let mut first = first_layer_height;
+    loop {
+        let step = first.take().unwrap_or_else(|| height(z));
+        let next = round_micros(z + step.max(MIN_STEP));
+        if next > zmax - 0.0001 {
+            break;
+        }
+        boundaries.push(next);
+        z = next;
+    }
+    if zmax - z < min_height && boundaries.len() > 2 {
+        boundaries.pop();
+        let below = boundaries[boundaries.len() - 1];
+        boundaries.push(round_micros((below + zmax) / 2.0));
+    }
+    boundaries.push(zmax);
+    boundaries
+}
+
+/// A face of the mesh, reduced to what adaptive slicing needs.
+struct Face {
+    zmin: f32,
+    zmax: f32,
+    /// The layer height that keeps the stair-stepping on this face within
+    /// the configured deviation.
+    height: f32,
+}
+
+/// Picks the height of every layer from the slope of the mesh.
+///
+/// Layers print as stair steps, and on a surface whose normal makes an angle
+/// θ with the Z axis each step sticks out from the true surface by
+/// `height · cos θ`. Each layer is made as thick as it can be while keeping
+/// that deviation within `config.max_deviation` for every face it passes
+/// through, so layers are thinner where the surface is close to horizontal,
+/// like the top of a dome, and thicker on steep walls.
+///
+/// Only the slope of each face is used. The curvature of the surface isn't
+/// taken into account, so a layer can be as thick as its faces' slopes allow
+/// even where the surface bends sharply between them.
+///
+/// Returns the boundaries between layers, as in `layer_boundaries`.
+pub fn adaptive_layer_boundaries(
+    triangles: &[Triangle],
+    first_layer_height: Option<f32>,
+    config: &AdaptiveLayerConfig,
+) -> Vec<f32> {
+    let Some((_, zmax)) = z_limits(triangles) else {
+        return Vec::new();
+    };
+    let (min_height, max_height) = (config.min_height, config.max_height.max(config.min_height));
+    let mut faces = triangles
+        .iter()
+        .filter_map(|t| {
+            let normal = (t.p1 - t.p0).cross(t.p2 - t.p0);
+            let normal_z = (normal.z / normal.magnitude()).abs();
+            if normal_z.is_nan() || normal_z > FLAT_NORMAL_Z {
+                return None;
+            }
+            let height = if normal_z > 0.0 {
+                config.max_deviation / normal_z
+            } else {
+                max_height
+            };
+            Some(Face {
+                zmin: t.p0.z.min(t.p1.z).min(t.p2.z),
+                zmax: t.p0.z.max(t.p1.z).max(t.p2.z),
+                height: height.clamp(min_height, max_height),
+            })
+        })
+        .collect::<Vec<_>>();
+    faces.sort_by(|a, b| a.zmin.total_cmp(&b.zmin));
+
+    // Faces that might overlap the current layer, sorted by their bottom.
+    let mut active: Vec<&Face> = Vec::new();
+    let mut next = 0;
+    boundaries_from(zmax, first_layer_height, min_height, |z| {
+        active.retain(|f| f.zmax > z);
+        while next < faces.len() && faces[next].zmin < z + max_height {
+            active.push(&faces[next]);
+            next += 1;
+        }
+        let mut height = max_height;
+        for face in &active {
+            let start = face.zmin - z;
+            if start >= height {
+                // Faces are in order, so none of the rest reach this layer.
+                break;
+            }
+            if face.height < height {
+                // End the layer where the face starts if that's thinner than
+                // the face needs.
+                height = face.height.max(start).max(min_height);
+            }
+        }
+        height
+    })
+}
+
+/// Picks the layers to slice `triangles` into.
+///
+/// A layer height profile takes priority, then adaptive layer heights.
+/// Otherwise layers are `config.layer_height` thick. The first layer is
+/// `config.first_layer_height` thick, if that's set.
+///
+/// Returns the boundaries between layers: layer `i` runs from
+/// `boundaries[i]` to `boundaries[i + 1]`. The first boundary is the bed, at
+/// 0, and none go past the top of the mesh.
+pub fn layer_boundaries(triangles: &[Triangle], config: &SlicerConfig) -> Vec<f32> {
+    let Some((_, zmax)) = z_limits(triangles) else {
+        return Vec::new();
+    };
+    let first = config.first_layer_height;
+    if let Some(profile) = &config.layer_height_profile {
+        if !profile.points().is_empty() {
+            let min_height = profile.height_at(zmax).unwrap() * MIN_TOP_LAYER_RATIO;
+            return boundaries_from(zmax, first, min_height, |z| profile.height_at(z).unwrap());
+        }
+    }
+    if config.adaptive_layers.enabled {
+        return adaptive_layer_boundaries(triangles, first, &config.adaptive_layers);
+    }
+    let min_height = config.layer_height * MIN_TOP_LAYER_RATIO;
+    boundaries_from(zmax, first, min_height, |_| config.layer_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+    use mandoline_mesh::{DefaultMesh, TriangleMesh, Vector3};
+    use mandoline_test_data::STL_CUBE;
+
+    fn triangles(data: &[u8]) -> Vec<Triangle> {
+        mandoline_stl::parse_stl::<DefaultMesh>(data)
+            .unwrap()
+            .triangles()
+            .collect()
+    }
+
+    /// A cone with its base on the bed, with sides at 45°.
+    fn cone() -> Vec<Triangle> {
+        let apex = Vector3::new(0.0, 0.0, 10.0);
+        let segments = 32;
+        (0..segments)
+            .map(|i| {
+                let point = |i: usize| {
+                    let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                    Vector3::new(10.0 * angle.cos(), 10.0 * angle.sin(), 0.0)
+                };
+                Triangle {
+                    p0: point(i),
+                    p1: point(i + 1),
+                    p2: apex,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn constant_boundaries() {
+        let config = SlicerConfig::default();
+        let boundaries = layer_boundaries(&triangles(STL_CUBE.bytes), &config);
+        assert_eq!(boundaries.len(), 101);
+        assert_float_eq!(boundaries[100], 20.0, abs <= 0.0001);
+
+        let config = SlicerConfig {
+            first_layer_height: Some(0.3),
+            ..Default::default()
+        };
+        let boundaries = layer_boundaries(&triangles(STL_CUBE.bytes), &config);
+        assert_float_eq!(boundaries[1], 0.3, abs <= 0.0001);
+        assert_float_eq!(boundaries[2], 0.5, abs <= 0.0001);
+        // The last layer is thinner, so that it ends at the top of the cube.
+        let last = boundaries.len() - 1;
+        assert_float_eq!(boundaries[last - 1], 19.9, abs <= 0.0001);
+        assert_float_eq!(boundaries[last], 20.0, abs <= 0.0001);
+    }
+
+    #[test]
+    fn thin_top_layer_is_shared() {
+        let config = SlicerConfig {
+            layer_height: 0.3,
+            ..Default::default()
+        };
+        let boundaries = layer_boundaries(&cone(), &config);
+        // 0.1mm would be left over above 9.9mm, so the 0.4mm above 9.6mm is
+        // split into two layers.
+        let last = boundaries.len() - 1;
+        assert_float_eq!(boundaries[last - 2], 9.6, abs <= 0.0001);
+        assert_float_eq!(boundaries[last - 1], 9.8, abs <= 0.0001);
+        assert_float_eq!(boundaries[last], 10.0, abs <= 0.0001);
+        assert!(boundaries
+            .windows(2)
+            .all(|w| w[1] - w[0] <= config.layer_height + 0.0001));
+    }
+
+    #[test]
+    fn zero_layer_height_finishes() {
+        let config = SlicerConfig {
+            layer_height: 0.0,
+            ..Default::default()
+        };
+        let boundaries = layer_boundaries(&cone(), &config);
+        assert_eq!(boundaries.len(), 10_001);
+        assert_float_eq!(boundaries[10_000], 10.0, abs <= 0.0001);
+    }
+
+    #[test]
+    fn adaptive_steep_walls_use_max_height() {
+        let config = AdaptiveLayerConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let boundaries = adaptive_layer_boundaries(&triangles(STL_CUBE.bytes), None, &config);
+        let (last, layers) = boundaries.split_last().unwrap();
+        for w in layers.windows(2) {
+            assert_float_eq!(w[1] - w[0], config.max_height, abs <= 0.001);
+        }
+        assert_float_eq!(*last, 20.0, abs <= 0.0001);
+    }
+
+    #[test]
+    fn adaptive_slopes_are_thinner() {
+        let config = AdaptiveLayerConfig {
+            enabled: true,
+            min_height: 0.05,
+            max_height: 0.3,
+            max_deviation: 0.1,
+        };
+        let boundaries = adaptive_layer_boundaries(&cone(), None, &config);
+        // Sides at 45° leave steps that stick out by h·cos(45°).
+        let expected = 0.1 / std::f32::consts::FRAC_1_SQRT_2;
+        let (last, layers) = boundaries.split_last().unwrap();
+        assert_float_eq!(*last, 10.0, abs <= 0.0001);
+        for w in layers.windows(2) {
+            assert_float_eq!(w[1] - w[0], expected, abs <= 0.002);
+        }
+    }
+
+    #[test]
+    fn profile_round_trip() {
+        let profile: LayerHeightProfile = "# comment\n0 0.1\n\n5.0 0.3\n".parse().unwrap();
+        assert_eq!(profile.points(), &[(0.0, 0.1), (5.0, 0.3)]);
+        assert_float_eq!(profile.height_at(2.5).unwrap(), 0.2, abs <= 0.0001);
+        assert_float_eq!(profile.height_at(8.0).unwrap(), 0.3, abs <= 0.0001);
+        assert_eq!(
+            profile.to_string().parse::<LayerHeightProfile>(),
+            Ok(profile)
+        );
+
+        let error = "0 0.1\n1 x".parse::<LayerHeightProfile>().unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn profile_boundaries() {
+        let config = SlicerConfig {
+            layer_height_profile: Some(LayerHeightProfile::new(vec![
+                (0.0, 0.1),
+                (5.0, 0.1),
+                (5.5, 0.25),
+            ])),
+            ..Default::default()
+        };
+        let boundaries = layer_boundaries(&triangles(STL_CUBE.bytes), &config);
+        assert_float_eq!(boundaries[1], 0.1, abs <= 0.0001);
+        assert_float_eq!(boundaries[50], 5.0, abs <= 0.0001);
+        let last = boundaries.len() - 1;
+        assert_float_eq!(
+            boundaries[last - 1] - boundaries[last - 2],
+            0.25,
+            abs <= 0.0001
+        );
+        assert_float_eq!(boundaries[last], 20.0, abs <= 0.0001);
+
+        // Saving the boundaries as a profile slices the same layers again.
+        let saved = SlicerConfig {
+            layer_height_profile: Some(LayerHeightProfile::from_boundaries(&boundaries)),
+            ..Default::default()
+        };
+        assert_eq!(
+            layer_boundaries(&triangles(STL_CUBE.bytes), &saved),
+            boundaries
+        );
+    }
+}
@@ -1,7 +1,50 @@
+mod adhesion;
+mod arc;
+mod arrange;
+mod combing;
 mod config;
 mod contour;
+mod gcode;
+mod import;
+mod infill;
+mod layer_heights;
+mod ordering;
+mod overhang;
+mod perimeter;
+mod plate;
+mod profile;
+mod progress;
+mod region;
+mod seam;
+mod simplify;
 mod slicer;
+mod support;
+mod surface;
+#[cfg(test)]
+mod test_util;
+mod toolpath;
+mod vase;
 
+pub use adhesion::*;
+pub use arc::*;
+pub use arrange::*;
+pub use combing::*;
 pub use config::*;
 pub use contour::*;
+pub use gcode::*;
+pub use import::*;
+pub use infill::*;
+pub use layer_heights::*;
+pub use ordering::*;
+pub use overhang::*;
+pub use perimeter::*;
+pub use plate::*;
+pub use profile::*;
+pub use progress::*;
+pub use region::*;
+pub use seam::*;
 pub use slicer::*;
+pub use support::*;
+pub use surface::*;
+pub use toolpath::*;
+pub use vase::*;
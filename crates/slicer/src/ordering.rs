@@ -0,0 +1,310 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::toolpath::{ExtrusionPath, ToolpathLayer};
+
+/// Each 2-opt pass tries every run of items and measures the travel through
+/// it, which is cubic in the number of items, so it's only used for
+/// sequences up to this length.
+const MAX_TWO_OPT_ITEMS: usize = 60;
+/// The maximum number of 2-opt passes over a sequence.
+const MAX_TWO_OPT_PASSES: usize = 8;
+
+/// The ends of something that is printed in one go, like a path or an
+/// island.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Endpoints {
+    pub start: Vector2<f32>,
+    pub end: Vector2<f32>,
+    /// True if the item can also be printed from `end` to `start`.
+    pub reversible: bool,
+}
+
+impl Endpoints {
+    fn entry(&self, reversed: bool) -> Vector2<f32> {
+        if reversed {
+            self.end
+        } else {
+            self.start
+        }
+    }
+
+    fn exit(&self, reversed: bool) -> Vector2<f32> {
+        if reversed {
+            self.start
+        } else {
+            self.end
+        }
+    }
+}
+
+/// The travel distance needed to print `order`, starting from `from`.
+fn travel_length(items: &[Endpoints], order: &[(usize, bool)], from: Option<Vector2<f32>>) -> f32 {
+    let mut position = from;
+    let mut length = 0.0;
+    for &(i, reversed) in order {
+        if let Some(p) = position {
+            length += (items[i].entry(reversed) - p).magnitude();
+        }
+        position = Some(items[i].exit(reversed));
+    }
+    length
+}
+
+/// Picks the order to print `items` in, to keep travel moves short.
+///
+/// Starting from `from`, the nearest item is repeatedly chosen next. The
+/// result is then improved with 2-opt: reversing runs of items, and the
+/// direction of each reversible item in the run, wherever that shortens the
+/// total travel.
+///
+/// Returns the index of each item in the order they should be printed, and
+/// whether it should be printed in reverse.
+pub fn order_by_travel(items: &[Endpoints], from: Option<Vector2<f32>>) -> Vec<(usize, bool)> {
+    let mut order = nearest_neighbour(items, from);
+    if order.len() <= MAX_TWO_OPT_ITEMS {
+        two_opt(items, &mut order, from);
+    }
+    order
+}
+
+fn nearest_neighbour(items: &[Endpoints], from: Option<Vector2<f32>>) -> Vec<(usize, bool)> {
+    let mut remaining = (0..items.len()).collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(items.len());
+    let Some(mut position) = from.or_else(|| items.first().map(|i| i.start)) else {
+        return order;
+    };
+    while !remaining.is_empty() {
+        let (slot, reversed, _) = remaining
+            .iter()
+            .enumerate()
+            .flat_map(|(slot, &i)| {
+                let item = &items[i];
+                let forward = (slot, false, (item.start - position).magnitude());
+                let backward = (slot, true, (item.end - position).magnitude());
+                std::iter::once(forward).chain(item.reversible.then_some(backward))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap();
+        let i = remaining.swap_remove(slot);
+        position = items[i].exit(reversed);
+        order.push((i, reversed));
+    }
+    order
+}
+
+fn two_opt(items: &[Endpoints], order: &mut [(usize, bool)], from: Option<Vector2<f32>>) {
+    let n = order.len();
+    // Reverses `order[i..=j]`, flipping reversible items so the run is
+    // printed backwards.
+    let reverse = |order: &mut [(usize, bool)], i: usize, j: usize| {
+        order[i..=j].reverse();
+        for (index, reversed) in &mut order[i..=j] {
+            if items[*index].reversible {
+                *reversed = !*reversed;
+            }
+        }
+    };
+    // The travel into, through and out of `order[i..=j]`.
+    let local_length = |order: &[(usize, bool)], i: usize, j: usize| {
+        let before = if i == 0 {
+            from
+        } else {
+            let (k, r) = order[i - 1];
+            Some(items[k].exit(r))
+        };
+        let end = (j + 2).min(n);
+        travel_length(items, &order[i..end], before)
+    };
+
+    for _ in 0..MAX_TWO_OPT_PASSES {
+        let mut improved = false;
+        for i in 0..n {
+            for j in i + 1..n {
+                let current = local_length(order, i, j);
+                reverse(order, i, j);
+                if local_length(order, i, j) + 1e-4 < current {
+                    improved = true;
+                } else {
+                    reverse(order, i, j);
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+fn path_endpoints(path: &ExtrusionPath) -> Endpoints {
+    Endpoints {
+        start: path.path.first().unwrap(),
+        end: path.path.last().unwrap(),
+        // Closed loops start at their seam, so they are never reversed.
+        reversible: !path.is_closed(),
+    }
+}
+
+/// Orders `paths` to keep travel short, starting from `from`. Consecutive
+/// paths of the same feature are reordered among themselves, but the order
+/// of features is kept.
+fn order_paths(paths: Vec<ExtrusionPath>, from: Option<Vector2<f32>>) -> Vec<ExtrusionPath> {
+    let mut ordered = Vec::with_capacity(paths.len());
+    let mut position = from;
+    let mut paths = paths.into_iter().peekable();
+    while let Some(first) = paths.next() {
+        let mut run = vec![first];
+        while let Some(next) = paths.next_if(|p| p.feature == run[0].feature) {
+            run.push(next);
+        }
+        let endpoints = run.iter().map(path_endpoints).collect::<Vec<_>>();
+        let order = order_by_travel(&endpoints, position);
+        let mut run = run.into_iter().map(Some).collect::<Vec<_>>();
+        for (i, reversed) in order {
+            let mut path = run[i].take().unwrap();
+            if reversed {
+                path.path.reverse();
+            }
+            position = path.path.last();
+            ordered.push(path);
+        }
+    }
+    ordered
+}
+
+/// Orders the paths of `layer` to minimize travel, starting from `from`.
+///
+/// Paths are grouped by the island of the layer they're in, so that each
+/// island is finished before moving on to the next. Islands are then
+/// ordered by their entry and exit points. Within an island, paths keep
+/// their order of features. Returns where the nozzle ends up.
+pub fn order_layer(layer: &mut ToolpathLayer, from: Option<Vector2<f32>>) -> Option<Vector2<f32>> {
+    let paths = std::mem::take(&mut layer.paths)
+        .into_iter()
+        .filter(|p| p.path.points().len() > 1)
+        .collect::<Vec<_>>();
+    let islands = layer.region.islands().collect::<Vec<_>>();
+
+    // Paths that aren't in any island, like supports, form one extra group.
+    let mut groups = vec![Vec::new(); islands.len() + 1];
+    for path in paths {
+        let start = path.path.first().unwrap();
+        let group = islands
+            .iter()
+            .position(|island| island.contains_point(start))
+            .unwrap_or(islands.len());
+        groups[group].push(path);
+    }
+    let groups = groups
+        .into_iter()
+        .filter(|g| !g.is_empty())
+        .collect::<Vec<_>>();
+
+    // Islands are ordered by where their first and last paths are, before
+    // the paths inside them are ordered.
+    let endpoints = groups
+        .iter()
+        .map(|g| Endpoints {
+            start: g[0].path.first().unwrap(),
+            end: g[g.len() - 1].path.last().unwrap(),
+            reversible: false,
+        })
+        .collect::<Vec<_>>();
+    let order = order_by_travel(&endpoints, from);
+
+    let mut groups = groups.into_iter().map(Some).collect::<Vec<_>>();
+    let mut position = from;
+    for (i, _) in order {
+        let group = order_paths(groups[i].take().unwrap(), position);
+        position = group.last().and_then(|p| p.path.last());
+        layer.paths.extend(group);
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contour::Polyline;
+    use crate::test_util::rect;
+    use crate::toolpath::FeatureType;
+
+    fn v(x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x, y)
+    }
+
+    fn segment(x0: f32, y0: f32, x1: f32, y1: f32) -> Endpoints {
+        Endpoints {
+            start: v(x0, y0),
+            end: v(x1, y1),
+            reversible: true,
+        }
+    }
+
+    #[test]
+    fn nearest_neighbour_reverses_lines() {
+        // Zig-zag hatching, with every line pointing the same way.
+        let items = (0..4)
+            .map(|i| segment(0.0, i as f32, 10.0, i as f32))
+            .collect::<Vec<_>>();
+
+        let order = order_by_travel(&items, Some(v(0.0, 0.0)));
+
+        assert_eq!(order, vec![(0, false), (1, true), (2, false), (3, true)]);
+        assert!((travel_length(&items, &order, Some(v(0.0, 0.0))) - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn two_opt_fixes_crossing() {
+        // Points along a line, where nearest neighbour from the middle keeps
+        // doubling back across the start.
+        let items = [1.0, -1.2, 1.5, -2.0, 8.0]
+            .iter()
+            .map(|&x| segment(x, 0.0, x, 0.0))
+            .collect::<Vec<_>>();
+        let from = Some(v(0.0, 0.0));
+        let greedy = nearest_neighbour(&items, from);
+
+        let order = order_by_travel(&items, from);
+
+        assert!(travel_length(&items, &order, from) < travel_length(&items, &greedy, from));
+    }
+
+    fn line(feature: FeatureType, points: &[(f32, f32)]) -> ExtrusionPath {
+        ExtrusionPath::new(
+            feature,
+            Polyline::from_points(points.iter().map(|&(x, y)| v(x, y)).collect()),
+        )
+    }
+
+    #[test]
+    fn islands_are_finished_in_turn() {
+        // Two islands, with their paths interleaved.
+        let mut layer = ToolpathLayer {
+            region: rect(0.0, 0.0, 10.0, 10.0).union(&rect(20.0, 0.0, 30.0, 10.0)),
+            paths: vec![
+                line(FeatureType::Perimeter, &[(21.0, 1.0), (29.0, 1.0)]),
+                line(FeatureType::Perimeter, &[(1.0, 1.0), (9.0, 1.0)]),
+                line(FeatureType::SparseInfill, &[(21.0, 5.0), (29.0, 5.0)]),
+                line(FeatureType::SparseInfill, &[(1.0, 5.0), (9.0, 5.0)]),
+            ],
+            ..Default::default()
+        };
+
+        order_layer(&mut layer, Some(v(0.0, 0.0)));
+
+        let starts = layer
+            .paths
+            .iter()
+            .map(|p| (p.feature, p.path.first().unwrap().x < 10.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                (FeatureType::Perimeter, true),
+                (FeatureType::SparseInfill, true),
+                (FeatureType::Perimeter, false),
+                (FeatureType::SparseInfill, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::f32::consts::PI;
+
+use crate::config::SlicerConfig;
+use crate::infill::hatch;
+use crate::region::{LineSegment, Region};
+
+/// The number of directions tried when choosing a bridge direction.
+const BRIDGE_ANGLE_STEPS: usize = 36;
+
+/// Describes how an unsupported area of a layer is held up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverhangType {
+    /// An area that is only attached to the layer below along one side, and
+    /// needs support material (or a steep enough slope) to print well.
+    Overhang,
+    /// An area that spans a gap between supported parts of the layer below.
+    /// These can be printed in mid-air by running lines straight across the
+    /// gap at `angle` (in radians, counter-clockwise from +X).
+    Bridge { angle: f32 },
+}
+
+/// An area of a layer that is not supported by the layer below.
+#[derive(Clone, Debug)]
+pub struct Overhang {
+    pub kind: OverhangType,
+    pub region: Region,
+}
+
+/// The steepest overhang, in degrees from vertical, that can be printed
+/// without support.
+///
+/// A line is considered supported as long as at least half of its width
+/// rests on the layer below, so each layer may step out by half an extrusion
+/// width.
+pub fn overhang_threshold_angle(config: &SlicerConfig) -> f32 {
+    (config.extrusion_width / 2.0)
+        .atan2(config.layer_height)
+        .to_degrees()
+}
+
+/// Finds the areas of each layer that are not supported by the layer below.
+///
+/// `layers` holds the filled area of every layer, in order from the bed up.
+/// The first layer rests on the bed and never has overhangs.
+pub fn detect_overhangs(layers: &[Region], config: &SlicerConfig) -> Vec<Vec<Overhang>> {
+    let width = config.extrusion_width;
+    let mut overhangs = vec![Vec::new()];
+    for pair in layers.windows(2) {
+        let (below, layer) = (&pair[0], &pair[1]);
+        let unsupported = layer.difference(&below.offset(width / 2.0));
+        let found = unsupported
+            .islands()
+            // Ignore slivers too thin to print.
+            .filter(|island| !island.offset(-width / 4.0).is_empty())
+            .map(|island| {
+                let kind = classify(&island, below, width);
+                Overhang {
+                    kind,
+                    region: island,
+                }
+            })
+            .collect();
+        overhangs.push(found);
+    }
+    overhangs.truncate(layers.len());
+    overhangs
+}
+
+/// Decides whether `area` is a bridge, and if so which direction to print it.
+///
+/// `area` is a bridge if it is anchored to the layer below at two or more
+/// separate places, or if a single anchor surrounds it completely.
+fn classify(area: &Region, below: &Region, width: f32) -> OverhangType {
+    let anchors = area.offset(width).intersection(below);
+    let islands = anchors.islands().collect::<Vec<_>>();
+    let enclosed = islands.len() == 1 && islands[0].paths().len() > 1;
+    if islands.len() < 2 && !enclosed {
+        return OverhangType::Overhang;
+    }
+    OverhangType::Bridge {
+        angle: bridge_angle(area, &anchors, width),
+    }
+}
+
+/// Picks the direction for bridging over `area` that anchors as much of the
+/// bridge as possible.
+///
+/// `anchors` is the part of the layer below that lies within `width` of
+/// `area`. For each candidate direction, lines are drawn across the bridge
+/// and its anchors. Only lines with both ends resting on an anchor hold themselves
+/// up, so the direction where the largest fraction of the gap is covered by
+/// anchored lines wins. Directions within 0.01 of the best fraction tie,
+/// and the one of them with the shortest lines is used.
+pub fn bridge_angle(area: &Region, anchors: &Region, width: f32) -> f32 {
+    let span = area.offset(width);
+    let landing = anchors.offset(width / 10.0);
+    let covered = |lines: &[LineSegment]| -> f32 {
+        area.clip_segments(lines)
+            .iter()
+            .map(LineSegment::length)
+            .sum()
+    };
+    // (angle, fraction anchored, longest line) for each direction.
+    let candidates = (0..BRIDGE_ANGLE_STEPS)
+        .filter_map(|step| {
+            let angle = step as f32 * PI / BRIDGE_ANGLE_STEPS as f32;
+            let lines = hatch(&span, angle, width);
+            let (anchored, loose): (Vec<_>, Vec<_>) = lines
+                .into_iter()
+                .partition(|l| landing.contains_point(l.start) && landing.contains_point(l.end));
+            if anchored.is_empty() {
+                return None;
+            }
+            let anchored_length = covered(&anchored);
+            let total = anchored_length + covered(&loose);
+            let longest = anchored.iter().map(LineSegment::length).fold(0.0, f32::max);
+            Some((angle, anchored_length / total, longest))
+        })
+        .collect::<Vec<_>>();
+    let most = candidates.iter().map(|c| c.1).fold(0.0, f32::max);
+    // Allow a little slack so that directions that are equally good up to
+    // rounding are decided by line length.
+    candidates
+        .iter()
+        .filter(|c| c.1 >= most - 0.01)
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map_or(0.0, |c| c.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rect;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn threshold_angle() {
+        let config = SlicerConfig {
+            layer_height: 0.2,
+            extrusion_width: 0.4,
+            ..Default::default()
+        };
+        assert_float_eq!(overhang_threshold_angle(&config), 45.0, abs <= 0.001);
+    }
+
+    #[test]
+    fn supported_column_has_no_overhangs() {
+        let layers = vec![rect(0.0, 0.0, 10.0, 10.0); 3];
+        let overhangs = detect_overhangs(&layers, &SlicerConfig::default());
+        assert_eq!(overhangs.len(), 3);
+        assert!(overhangs.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn gentle_slope_is_supported() {
+        // Each layer steps out by less than half an extrusion width.
+        let layers = (0..5)
+            .map(|i| rect(0.0, 0.0, 10.0 + i as f32 * 0.1, 10.0))
+            .collect::<Vec<_>>();
+        let overhangs = detect_overhangs(&layers, &SlicerConfig::default());
+        assert!(overhangs.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn ledge_is_overhang() {
+        let layers = vec![rect(0.0, 0.0, 10.0, 10.0), rect(0.0, 0.0, 15.0, 10.0)];
+
+        let overhangs = detect_overhangs(&layers, &SlicerConfig::default());
+
+        assert_eq!(overhangs[1].len(), 1);
+        assert_eq!(overhangs[1][0].kind, OverhangType::Overhang);
+    }
+
+    #[test]
+    fn span_between_pillars_is_bridge() {
+        // Two pillars along X, joined by a deck. The bridge should run along
+        // X, from one pillar to the other.
+        let pillars = rect(0.0, 0.0, 3.0, 5.0).union(&rect(10.0, 0.0, 13.0, 5.0));
+        let layers = vec![pillars, rect(0.0, 0.0, 13.0, 5.0)];
+
+        let overhangs = detect_overhangs(&layers, &SlicerConfig::default());
+
+        assert_eq!(overhangs[1].len(), 1);
+        match overhangs[1][0].kind {
+            OverhangType::Bridge { angle } => assert_float_eq!(angle, 0.0, abs <= 0.01),
+            kind => panic!("expected a bridge, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn lid_over_hole_is_bridge() {
+        // A thin walled tube with a lid. The hole is narrowest along Y, so
+        // that's the best direction to bridge.
+        let tube = rect(0.0, 0.0, 20.0, 8.0).difference(&rect(2.0, 2.0, 18.0, 6.0));
+        let layers = vec![tube, rect(0.0, 0.0, 20.0, 8.0)];
+
+        let overhangs = detect_overhangs(&layers, &SlicerConfig::default());
+
+        assert_eq!(overhangs[1].len(), 1);
+        match overhangs[1][0].kind {
+            OverhangType::Bridge { angle } => assert_float_eq!(angle, PI / 2.0, abs <= 0.01),
+            kind => panic!("expected a bridge, got {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::config::SlicerConfig;
+use crate::region::Region;
+
+/// The perimeter loops generated for a single layer.
+pub struct Perimeters {
+    /// The center lines of each perimeter, outermost first.
+    ///
+    /// Each entry is the region whose outline is the path the nozzle follows
+    /// for that perimeter. Use `Region::paths` to get the individual loops.
+    pub shells: Vec<Region>,
+    /// The area left inside the innermost perimeter, to be filled by infill.
+    pub infill_region: Region,
+}
+
+/// Generates `config.perimeters` loops inside of `region`.
+///
+/// Perimeters are generated by repeatedly insetting the layer outline by one
+/// extrusion width. The first loop is inset by half an extrusion width so
+/// that the outside edge of the extruded line lies on the model surface.
+pub fn generate_perimeters(region: &Region, config: &SlicerConfig) -> Perimeters {
+    let width = config.extrusion_width;
+    let mut shells = Vec::with_capacity(config.perimeters);
+    for i in 0..config.perimeters {
+        let shell = region.offset(-(width / 2.0 + i as f32 * width));
+        if shell.is_empty() {
+            break;
+        }
+        shells.push(shell);
+    }
+    let infill_region = region.offset(-(config.perimeters as f32 * width));
+    Perimeters {
+        shells,
+        infill_region,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::square;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn perimeters_of_square() {
+        let region = square(10.0);
+        let config = SlicerConfig {
+            extrusion_width: 0.5,
+            perimeters: 3,
+            ..Default::default()
+        };
+
+        let perimeters = generate_perimeters(&region, &config);
+
+        assert_eq!(perimeters.shells.len(), 3);
+        assert_float_eq!(perimeters.shells[0].area(), 9.5 * 9.5, abs <= 0.01);
+        assert_float_eq!(perimeters.shells[2].area(), 7.5 * 7.5, abs <= 0.01);
+        assert_float_eq!(perimeters.infill_region.area(), 7.0 * 7.0, abs <= 0.01);
+    }
+}
@@ -0,0 +1,476 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mandoline_mesh::{Triangle, TriangleMesh, Vector3};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::{SlicerConfig, ValidationError};
+use crate::layer_heights::layer_boundaries;
+use crate::profile::{setting_matches, setting_paths, ConfigError};
+use crate::progress::{Cancelled, Progress};
+use crate::slicer::{slice_triangles, SlicedMesh};
+
+/// Settings that apply to the whole plate, and can't be changed for a single
+/// object. Every object is printed in the same layers, with the same nozzle
+/// and in the same G-code program, so these have to match.
+const PLATE_SETTINGS: &[&str] = &[
+    "layer_height",
+    "first_layer_height",
+    "slicing_plane",
+    "adaptive_layers.",
+    "layer_height_profile",
+    "nozzle_diameter",
+    "bed_shape",
+    "extrusion_width",
+    "spiral_vase",
+    "adhesion.",
+    "gcode.",
+    "retraction.",
+    "cooling.",
+    "machine_limits.",
+    "arrange.",
+];
+
+/// Where an object is placed on the plate.
+///
+/// The object is scaled and rotated about the origin of its mesh, then moved
+/// by `offset`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transform {
+    /// How far to move the object in X and Y, in mm.
+    pub offset: (f32, f32),
+    /// The rotation about the Z axis, in degrees counter-clockwise.
+    pub rotation: f32,
+    /// The scale factor, applied to every axis.
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: (0.0, 0.0),
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    /// Moves `point` from the object's mesh onto the plate.
+    pub fn apply(&self, point: Vector3) -> Vector3 {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let p = point * self.scale;
+        Vector3::new(
+            p.x * cos - p.y * sin + self.offset.0,
+            p.x * sin + p.y * cos + self.offset.1,
+            p.z,
+        )
+    }
+
+    /// Moves every triangle of `mesh` onto the plate.
+    pub fn apply_mesh<M: TriangleMesh>(&self, mesh: &M) -> Vec<Triangle> {
+        mesh.triangles()
+            .map(|t| Triangle {
+                p0: self.apply(t.p0),
+                p1: self.apply(t.p1),
+                p2: self.apply(t.p2),
+            })
+            .collect()
+    }
+}
+
+/// Returns the settings for an object on a plate with the settings
+/// `plate`, changed by `overrides`.
+///
+/// `overrides` is laid out like a configuration file. Settings that apply to
+/// the whole plate can't be overridden.
+pub fn object_config(
+    plate: &SlicerConfig,
+    overrides: &Map<String, Value>,
+) -> Result<SlicerConfig, ConfigError> {
+    if let Some(setting) = setting_paths(overrides, "")
+        .into_iter()
+        .find(|s| setting_matches(s, PLATE_SETTINGS))
+    {
+        return Err(ConfigError::PlateSetting(setting));
+    }
+    plate.with_overrides(overrides)
+}
+
+/// A model placed on a plate, along with its own settings.
+pub struct PlateObject<M> {
+    pub mesh: M,
+    pub transform: Transform,
+    /// The settings this object is sliced with, usually from
+    /// `object_config`.
+    pub config: SlicerConfig,
+}
+
+/// An object from a plate, sliced with its own settings.
+pub struct SlicedObject {
+    pub mesh: SlicedMesh,
+    pub config: SlicerConfig,
+}
+
+/// An error slicing a plate.
+#[derive(Debug)]
+pub enum PlateError {
+    /// The objects can't be printed together with the plate's settings.
+    Config(ConfigError),
+    Cancelled(Cancelled),
+}
+
+impl fmt::Display for PlateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlateError::Config(e) => write!(f, "{}", e),
+            PlateError::Cancelled(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PlateError {}
+
+impl From<ConfigError> for PlateError {
+    fn from(e: ConfigError) -> Self {
+        PlateError::Config(e)
+    }
+}
+
+impl From<Cancelled> for PlateError {
+    fn from(e: Cancelled) -> Self {
+        PlateError::Cancelled(e)
+    }
+}
+
+/// Checks that `count` objects can be printed together with the plate's
+/// settings `config`.
+pub fn check_plate(config: &SlicerConfig, count: usize) -> Result<(), ConfigError> {
+    // A vase is a single spiral, so there's no way to move on to another
+    // object part way up.
+    if config.spiral_vase && count > 1 {
+        return Err(ConfigError::Invalid(vec![ValidationError {
+            setting: "spiral_vase".to_string(),
+            message: format!("can only print a single object, not {}", count),
+        }]));
+    }
+    Ok(())
+}
+
+/// Slices every object on a plate into the same stack of layers, so that
+/// they can be printed together.
+///
+/// Layer heights come from `config`, the settings of the whole plate, and
+/// take every object into account. Objects shorter than the tallest have
+/// empty layers at the top.
+///
+/// Returns an error if the objects can't be printed together, as checked by
+/// `check_plate`.
+pub fn slice_plate<M: TriangleMesh>(
+    objects: Vec<PlateObject<M>>,
+    config: &SlicerConfig,
+) -> Result<Vec<SlicedObject>, ConfigError> {
+    check_plate(config, objects.len())?;
+    // With the plate checked and no cancellation token, slicing can't fail.
+    Ok(slice_plate_with_progress(objects, config, Progress::default()).unwrap())
+}
+
+/// Slices a plate like `slice_plate`, reporting progress to `progress` as
+/// each layer of each object is finished.
+///
+/// Returns `Err(PlateError::Cancelled)` if `progress`'s cancellation token
+/// is cancelled before slicing finishes.
+pub fn slice_plate_with_progress<M: TriangleMesh>(
+    objects: Vec<PlateObject<M>>,
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<Vec<SlicedObject>, PlateError> {
+    check_plate(config, objects.len())?;
+    let triangles = objects
+        .iter()
+        .map(|o| o.transform.apply_mesh(&o.mesh))
+        .collect::<Vec<_>>();
+    let boundaries = layer_boundaries(&triangles.concat(), config);
+    let count = objects.len();
+    objects
+        .into_iter()
+        .zip(&triangles)
+        .enumerate()
+        .map(|(index, (object, triangles))| {
+            // Every object goes through the same steps, as they share layers,
+            // so progress is counted over the whole plate.
+            let sink = |stage, done, total| {
+                progress.report(stage, index * total + done, count * total);
+            };
+            let object_progress = Progress {
+                sink: Some(&sink),
+                ..progress
+            };
+            let mesh = slice_triangles(triangles, &boundaries, &object.config, object_progress)?;
+            Ok(SlicedObject {
+                mesh,
+                config: object.config,
+            })
+        })
+        .collect()
+}
+
+/// An object listed in a plate file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlateFileObject {
+    /// The STL file of the object. Relative paths are relative to the plate
+    /// file.
+    pub path: PathBuf,
+    /// Where the object goes. When the plate is arranged automatically,
+    /// only the scale and rotation are kept, and the object may be turned
+    /// further.
+    #[serde(default)]
+    pub transform: Transform,
+    /// How many copies of the object to print, if more than one. Copies can
+    /// only be printed when the plate is arranged automatically.
+    #[serde(default)]
+    pub copies: Option<usize>,
+    /// Settings that differ from the plate's, for `object_config`.
+    #[serde(default)]
+    pub overrides: Map<String, Value>,
+}
+
+/// A TOML or JSON file that lists the objects on a plate.
+///
+/// ```toml
+/// [[objects]]
+/// path = "bracket.stl"
+/// transform = { offset = [40, 60], rotation = 90 }
+/// overrides = { infill = { density = 40 } }
+///
+/// [[objects]]
+/// path = "clip.stl"
+/// copies = 4
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlateFile {
+    pub objects: Vec<PlateFileObject>,
+}
+
+impl PlateFile {
+    /// Reads a plate file from TOML.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let plate: Self = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        plate.checked()
+    }
+
+    /// Reads a plate file from JSON.
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        let plate: Self =
+            serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        plate.checked()
+    }
+
+    fn checked(self) -> Result<Self, ConfigError> {
+        let bad_scale = |scale: f32| scale <= 0.0 || !scale.is_finite();
+        for object in &self.objects {
+            let problem = if bad_scale(object.transform.scale) {
+                "scale must be greater than zero"
+            } else if object.copies == Some(0) {
+                "copies must be at least 1"
+            } else {
+                continue;
+            };
+            return Err(ConfigError::Parse(format!(
+                "{}: {}",
+                object.path.display(),
+                problem
+            )));
+        }
+        Ok(self)
+    }
+
+    /// Reads a plate file, as JSON if it has a `.json` extension or TOML
+    /// otherwise. The paths of its objects are made relative to the current
+    /// directory.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut plate = if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&text)?
+        } else {
+            Self::from_toml(&text)?
+        };
+        if let Some(dir) = path.parent() {
+            for object in &mut plate.objects {
+                object.path = dir.join(&object.path);
+            }
+        }
+        Ok(plate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InfillConfig;
+    use crate::progress::Stage;
+    use float_eq::assert_float_eq;
+    use mandoline_mesh::DefaultMesh;
+    use mandoline_test_data::STL_CUBE;
+    use std::sync::Mutex;
+
+    fn cube() -> DefaultMesh {
+        mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap()
+    }
+
+    #[test]
+    fn transform_rotates_then_moves() {
+        let transform = Transform {
+            offset: (10.0, 20.0),
+            rotation: 90.0,
+            scale: 2.0,
+        };
+
+        let p = transform.apply(Vector3::new(1.0, 0.0, 3.0));
+
+        assert_float_eq!(p.x, 10.0, abs <= 1e-5);
+        assert_float_eq!(p.y, 22.0, abs <= 1e-5);
+        assert_float_eq!(p.z, 6.0, abs <= 1e-5);
+    }
+
+    #[test]
+    fn overrides() {
+        let plate = SlicerConfig::default();
+        let overrides = PlateFile::from_toml(
+            "[[objects]]
+path = 'a.stl'
+overrides = { infill = { density = 40 } }
+
+[[objects]]
+path = 'b.stl'
+overrides = { gcode = { nozzle_temperature = 250 } }
+",
+        )
+        .unwrap()
+        .objects;
+
+        let config = object_config(&plate, &overrides[0].overrides).unwrap();
+        let error = object_config(&plate, &overrides[1].overrides);
+
+        assert_float_eq!(config.infill.density, 40.0, abs <= 1e-6);
+        assert_eq!(config.infill.pattern, plate.infill.pattern);
+        assert!(matches!(
+            error,
+            Err(ConfigError::PlateSetting(s)) if s == "gcode.nozzle_temperature"
+        ));
+    }
+
+    #[test]
+    fn objects_share_layers() {
+        let config = SlicerConfig::default();
+        let small = Transform {
+            offset: (30.0, 0.0),
+            scale: 0.5,
+            ..Default::default()
+        };
+        let dense = SlicerConfig {
+            infill: InfillConfig {
+                density: 80.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let objects = vec![
+            PlateObject {
+                mesh: cube(),
+                transform: Transform::default(),
+                config: SlicerConfig::default(),
+            },
+            PlateObject {
+                mesh: cube(),
+                transform: small,
+                config: dense,
+            },
+        ];
+
+        let sliced = slice_plate(objects, &config).unwrap();
+
+        assert_eq!(sliced.len(), 2);
+        let (big, small) = (&sliced[0].mesh, &sliced[1].mesh);
+        assert_eq!(big.layer_z(), small.layer_z());
+        // The smaller cube is half as tall, so its top layers are empty.
+        let top = big.layers().len() - 1;
+        assert!(small.layers()[top].region().is_empty());
+        assert!(!small.layers()[top / 2 - 1].region().is_empty());
+        let (min, max) = small.layers()[0].region().bounding_box().unwrap();
+        assert_float_eq!(min.x, 30.0, abs <= 0.01);
+        assert_float_eq!(max.x, 40.0, abs <= 0.01);
+        assert_float_eq!(sliced[1].config.infill.density, 80.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn vase_plate_has_one_object() {
+        let config = SlicerConfig {
+            spiral_vase: true,
+            ..Default::default()
+        };
+        let object = |x| PlateObject {
+            mesh: cube(),
+            transform: Transform {
+                offset: (x, 0.0),
+                ..Default::default()
+            },
+            config: SlicerConfig {
+                spiral_vase: true,
+                ..Default::default()
+            },
+        };
+
+        let single = slice_plate(vec![object(0.0)], &config);
+        let double = slice_plate(vec![object(0.0), object(40.0)], &config);
+
+        assert_eq!(single.unwrap().len(), 1);
+        assert!(matches!(
+            double,
+            Err(ConfigError::Invalid(errors)) if errors[0].setting == "spiral_vase"
+        ));
+    }
+
+    #[test]
+    fn progress_counts_every_object() {
+        let config = SlicerConfig::default();
+        let object = |x| PlateObject {
+            mesh: cube(),
+            transform: Transform {
+                offset: (x, 0.0),
+                ..Default::default()
+            },
+            config: SlicerConfig::default(),
+        };
+        let updates = Mutex::new(Vec::new());
+        let sink = |stage, done, total| updates.lock().unwrap().push((stage, done, total));
+        let progress = Progress {
+            sink: Some(&sink),
+            ..Default::default()
+        };
+
+        let sliced =
+            slice_plate_with_progress(vec![object(0.0), object(40.0)], &config, progress).unwrap();
+
+        let layers = sliced[0].mesh.layers().len();
+        let updates = updates.into_inner().unwrap();
+        for (stage, total) in [(Stage::Slicing, 2 * layers), (Stage::Analysis, 6)] {
+            let mut done = updates
+                .iter()
+                .filter(|u| u.0 == stage)
+                .map(|u| {
+                    assert_eq!(u.2, total, "{}", stage);
+                    u.1
+                })
+                .collect::<Vec<_>>();
+            done.sort();
+            assert_eq!(done, (1..=total).collect::<Vec<_>>(), "{}", stage);
+        }
+    }
+}
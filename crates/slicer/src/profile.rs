@@ -0,0 +1,702 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::{SlicerConfig, ValidationError, SCHEMA_VERSION};
+
+/// The keys of a configuration file that describe the file, rather than
+/// changing settings.
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+pub(crate) const NAME_KEY: &str = "name";
+pub(crate) const KIND_KEY: &str = "kind";
+pub(crate) const INHERITS_KEY: &str = "inherits";
+
+/// Settings that describe the printer. A setting ending in `.` covers every
+/// setting in that section.
+const PRINTER_SETTINGS: &[&str] = &[
+    "nozzle_diameter",
+    "bed_shape",
+    "gcode.flavor",
+    "gcode.extrusion_mode",
+    "gcode.travel_acceleration",
+    "gcode.start_gcode",
+    "gcode.end_gcode",
+    "retraction.",
+    "machine_limits.",
+];
+
+/// Settings in the printer's sections that are chosen per print, and so
+/// belong in print profiles.
+const PRINT_SETTINGS: &[&str] = &[
+    "retraction.only_when_crossing_perimeters",
+    "retraction.avoid_crossing_perimeters",
+];
+
+/// Settings that describe the filament.
+const FILAMENT_SETTINGS: &[&str] = &[
+    "gcode.filament_diameter",
+    "gcode.filament_density",
+    "gcode.filament_cost",
+    "gcode.nozzle_temperature",
+    "gcode.bed_temperature",
+    "cooling.",
+];
+
+/// An error loading a configuration file or profile.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    /// The file isn't valid TOML or JSON, or has settings with the wrong
+    /// name or type.
+    Parse(String),
+    /// The file was written for a newer version of the format.
+    UnsupportedSchemaVersion(u64),
+    /// A profile isn't in the library.
+    UnknownProfile(String),
+    /// Two files in a profile directory have profiles with the same name.
+    DuplicateProfile {
+        name: String,
+        paths: [PathBuf; 2],
+    },
+    /// Profiles that inherit from each other in a loop.
+    InheritanceCycle(Vec<String>),
+    /// A profile has a setting that belongs to a different kind of profile,
+    /// or inherits from a different kind of profile.
+    WrongProfileKind {
+        profile: String,
+        setting: String,
+        kind: ProfileKind,
+    },
+    /// The settings were read, but can't be printed.
+    Invalid(Vec<ValidationError>),
+    /// A setting that applies to the whole plate was changed for a single
+    /// object.
+    PlateSetting(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(message) => write!(f, "{}", message),
+            ConfigError::UnsupportedSchemaVersion(version) => write!(
+                f,
+                "schema version {} is not supported, the latest is {}",
+                version, SCHEMA_VERSION
+            ),
+            ConfigError::UnknownProfile(name) => write!(f, "no profile named '{}'", name),
+            ConfigError::DuplicateProfile { name, paths } => write!(
+                f,
+                "profile '{}' is in both {} and {}",
+                name,
+                paths[0].display(),
+                paths[1].display()
+            ),
+            ConfigError::InheritanceCycle(names) => {
+                write!(
+                    f,
+                    "profiles inherit from each other: {}",
+                    names.join(" -> ")
+                )
+            }
+            ConfigError::WrongProfileKind {
+                profile,
+                setting,
+                kind,
+            } => write!(
+                f,
+                "'{}' in profile '{}' belongs in a {} profile",
+                setting, profile, kind
+            ),
+            ConfigError::Invalid(errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "invalid settings: {}", errors.join(", "))
+            }
+            ConfigError::PlateSetting(setting) => write!(
+                f,
+                "'{}' applies to the whole plate and can't be changed for one object",
+                setting
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+/// Parses TOML text into a JSON value, so that both formats can be merged
+/// and read the same way.
+fn parse_toml(text: &str) -> Result<Map<String, Value>, ConfigError> {
+    toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+}
+
+fn parse_json(text: &str) -> Result<Map<String, Value>, ConfigError> {
+    serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+}
+
+/// Parses a file as JSON if it has a `.json` extension, or as TOML
+/// otherwise.
+fn parse_file(path: &Path) -> Result<Map<String, Value>, ConfigError> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|e| e == "json") {
+        parse_json(&text)
+    } else {
+        parse_toml(&text)
+    }
+}
+
+/// Removes the schema version from `settings`, checking that it can be read.
+///
+/// Files without a version are assumed to be the latest.
+fn take_schema_version(settings: &mut Map<String, Value>) -> Result<(), ConfigError> {
+    match settings.remove(SCHEMA_VERSION_KEY) {
+        None => Ok(()),
+        Some(Value::Number(n)) => match n.as_u64() {
+            Some(version) if (1..=SCHEMA_VERSION as u64).contains(&version) => Ok(()),
+            Some(version) => Err(ConfigError::UnsupportedSchemaVersion(version)),
+            None => Err(ConfigError::Parse(format!("invalid schema version {}", n))),
+        },
+        Some(value) => Err(ConfigError::Parse(format!(
+            "invalid schema version {}",
+            value
+        ))),
+    }
+}
+
+fn take_string(
+    settings: &mut Map<String, Value>,
+    key: &str,
+) -> Result<Option<String>, ConfigError> {
+    match settings.remove(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(value) => Err(ConfigError::Parse(format!(
+            "{} must be a string, not {}",
+            key, value
+        ))),
+    }
+}
+
+/// Copies every setting in `overrides` onto `base`, merging sections rather
+/// than replacing them.
+fn merge(base: &mut Map<String, Value>, overrides: &Map<String, Value>) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(Value::Object(base)), Value::Object(overrides)) => merge(base, overrides),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Returns true if the setting at `path` is one of `patterns`. Patterns
+/// ending in a dot match every setting in that section.
+pub(crate) fn setting_matches(path: &str, patterns: &[&str]) -> bool {
+    patterns
+        .iter()
+        .any(|p| path == *p || (p.ends_with('.') && path.starts_with(p)))
+}
+
+/// Returns the path of every setting in `settings`, with sections joined by
+/// dots.
+pub(crate) fn setting_paths(settings: &Map<String, Value>, prefix: &str) -> Vec<String> {
+    settings
+        .iter()
+        .flat_map(|(key, value)| {
+            let path = format!("{}{}", prefix, key);
+            match value {
+                Value::Object(section) => setting_paths(section, &format!("{}.", path)),
+                _ => vec![path],
+            }
+        })
+        .collect()
+}
+
+/// Tidies settings before they're written out.
+///
+/// Numbers that were written from `f32` settings are replaced with the
+/// shortest decimal that reads back as the same `f32`, so that `0.2` isn't
+/// written as `0.20000000298023224`. Unset optional settings are removed,
+/// since TOML has no null and a missing setting reads back as unset anyway.
+fn tidy(value: &mut Value) {
+    match value {
+        Value::Number(n) if n.is_f64() => {
+            let x = n.as_f64().unwrap() as f32;
+            if let Some(rounded) = x
+                .to_string()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+            {
+                *n = rounded;
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(tidy),
+        Value::Object(values) => {
+            values.retain(|_, value| !value.is_null());
+            values.values_mut().for_each(tidy);
+        }
+        _ => {}
+    }
+}
+
+impl SlicerConfig {
+    /// Reads a configuration from settings, filling in any that are missing
+    /// with their defaults.
+    pub(crate) fn from_settings(mut settings: Map<String, Value>) -> Result<Self, ConfigError> {
+        take_schema_version(&mut settings)?;
+        let config: SlicerConfig = serde_json::from_value(Value::Object(settings))
+            .map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    /// Reads and validates a configuration from TOML.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_toml(text)?)
+    }
+
+    /// Reads and validates a configuration from JSON.
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_json(text)?)
+    }
+
+    /// Reads and validates a configuration from a TOML or JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_file(path.as_ref())?)
+    }
+
+    /// Returns a copy of these settings with `overrides` applied on top.
+    /// `overrides` is laid out like a configuration file, but only needs the
+    /// settings that change.
+    pub fn with_overrides(&self, overrides: &Map<String, Value>) -> Result<Self, ConfigError> {
+        let mut settings = self.to_settings();
+        merge(&mut settings, overrides);
+        Self::from_settings(settings)
+    }
+
+    /// Returns every setting, with the schema version.
+    fn to_settings(&self) -> Map<String, Value> {
+        let mut settings = Map::new();
+        settings.insert(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION.into());
+        let mut value = serde_json::to_value(self).unwrap();
+        tidy(&mut value);
+        if let Value::Object(config) = value {
+            settings.extend(config);
+        }
+        settings
+    }
+
+    /// Writes every setting as TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.to_settings()).unwrap()
+    }
+
+    /// Writes every setting as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_settings()).unwrap()
+    }
+}
+
+/// What a profile describes.
+///
+/// Profiles of each kind only contain their own settings, so a printer,
+/// filament and print profile can be mixed and matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileKind {
+    /// The printer's nozzle, firmware and retraction.
+    Printer,
+    /// The filament's diameter and temperatures.
+    Filament,
+    /// Everything else: layers, perimeters, infill, support and speeds.
+    Print,
+}
+
+impl ProfileKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProfileKind::Printer => "printer",
+            ProfileKind::Filament => "filament",
+            ProfileKind::Print => "print",
+        }
+    }
+
+    /// Returns the kind of profile that `setting` belongs in.
+    pub fn of_setting(setting: &str) -> ProfileKind {
+        if setting_matches(setting, PRINT_SETTINGS) {
+            ProfileKind::Print
+        } else if setting_matches(setting, PRINTER_SETTINGS) {
+            ProfileKind::Printer
+        } else if setting_matches(setting, FILAMENT_SETTINGS) {
+            ProfileKind::Filament
+        } else {
+            ProfileKind::Print
+        }
+    }
+}
+
+impl fmt::Display for ProfileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A named set of settings, which may inherit from another profile of the
+/// same kind.
+///
+/// Profiles are written like a configuration file, with a `name`, a `kind`
+/// and an optional `inherits` alongside the settings:
+///
+/// ```toml
+/// schema_version = 1
+/// name = "fine"
+/// kind = "print"
+/// inherits = "standard"
+///
+/// layer_height = 0.1
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub kind: ProfileKind,
+    /// The name of the profile this one is based on.
+    pub inherits: Option<String>,
+    settings: Map<String, Value>,
+}
+
+impl Profile {
+    pub(crate) fn from_settings(mut settings: Map<String, Value>) -> Result<Self, ConfigError> {
+        take_schema_version(&mut settings)?;
+        let name = take_string(&mut settings, NAME_KEY)?
+            .ok_or_else(|| ConfigError::Parse("profile has no name".to_string()))?;
+        let kind = match settings.remove(KIND_KEY) {
+            Some(kind) => serde_json::from_value(kind)
+                .map_err(|e| ConfigError::Parse(format!("profile '{}': {}", name, e)))?,
+            None => {
+                return Err(ConfigError::Parse(format!(
+                    "profile '{}' has no kind",
+                    name
+                )))
+            }
+        };
+        let inherits = take_string(&mut settings, INHERITS_KEY)?;
+        for setting in setting_paths(&settings, "") {
+            if ProfileKind::of_setting(&setting) != kind {
+                return Err(ConfigError::WrongProfileKind {
+                    profile: name,
+                    kind: ProfileKind::of_setting(&setting),
+                    setting,
+                });
+            }
+        }
+        Ok(Self {
+            name,
+            kind,
+            inherits,
+            settings,
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_toml(text)?)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_json(text)?)
+    }
+
+    /// Reads a profile from a TOML or JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_settings(parse_file(path.as_ref())?)
+    }
+
+    /// Writes the profile as TOML.
+    pub fn to_toml(&self) -> String {
+        let mut settings = Map::new();
+        settings.insert(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION.into());
+        settings.insert(NAME_KEY.to_string(), self.name.clone().into());
+        settings.insert(KIND_KEY.to_string(), self.kind.name().into());
+        if let Some(inherits) = &self.inherits {
+            settings.insert(INHERITS_KEY.to_string(), inherits.clone().into());
+        }
+        settings.extend(self.settings.clone());
+        toml::to_string(&settings).unwrap()
+    }
+}
+
+/// A collection of profiles that can inherit from each other.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileLibrary {
+    profiles: HashMap<String, Profile>,
+}
+
+impl ProfileLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.toml` and `.json` file in `dir` as a profile. Two files
+    /// with profiles of the same name are an error.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|p| p.extension().is_some_and(|e| e == "toml" || e == "json"));
+        paths.sort();
+        let mut library = Self::new();
+        let mut files = HashMap::new();
+        for path in paths {
+            let profile = Profile::from_file(&path)?;
+            if let Some(first) = files.insert(profile.name.clone(), path.clone()) {
+                return Err(ConfigError::DuplicateProfile {
+                    name: profile.name,
+                    paths: [first, path],
+                });
+            }
+            library.add(profile);
+        }
+        Ok(library)
+    }
+
+    /// Adds a profile, replacing any with the same name.
+    pub fn add(&mut self, profile: Profile) {
+        self.profiles.insert(profile.name.clone(), profile);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Returns the settings of a profile, merged on top of the settings of
+    /// the profiles it inherits from.
+    fn resolve(&self, name: &str) -> Result<(ProfileKind, Map<String, Value>), ConfigError> {
+        let mut chain = Vec::new();
+        let mut seen = HashSet::new();
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if !seen.insert(name) {
+                let mut names = chain
+                    .iter()
+                    .map(|p: &&Profile| p.name.clone())
+                    .collect::<Vec<_>>();
+                names.push(name.to_string());
+                return Err(ConfigError::InheritanceCycle(names));
+            }
+            let profile = self
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))?;
+            if let Some(child) = chain.last() {
+                if profile.kind != child.kind {
+                    return Err(ConfigError::WrongProfileKind {
+                        profile: child.name.clone(),
+                        setting: INHERITS_KEY.to_string(),
+                        kind: profile.kind,
+                    });
+                }
+            }
+            chain.push(profile);
+            next = profile.inherits.as_deref();
+        }
+        let mut settings = Map::new();
+        for profile in chain.iter().rev() {
+            merge(&mut settings, &profile.settings);
+        }
+        Ok((chain[0].kind, settings))
+    }
+
+    /// Builds a configuration from the named profiles.
+    ///
+    /// Printer settings are applied first, then filament and then print
+    /// settings, whatever order the names are given in. Profiles of the same
+    /// kind are applied in order. Settings that no profile sets keep their
+    /// defaults.
+    pub fn config(&self, names: &[&str]) -> Result<SlicerConfig, ConfigError> {
+        let mut profiles = names
+            .iter()
+            .map(|name| self.resolve(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        profiles.sort_by_key(|(kind, _)| *kind);
+        let mut settings = Map::new();
+        for (_, profile) in &profiles {
+            merge(&mut settings, profile);
+        }
+        SlicerConfig::from_settings(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InfillType;
+    use crate::gcode::GcodeFlavor;
+    use float_eq::assert_float_eq;
+
+    fn library(profiles: &[&str]) -> ProfileLibrary {
+        let mut library = ProfileLibrary::new();
+        for text in profiles {
+            library.add(Profile::from_toml(text).unwrap());
+        }
+        library
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = SlicerConfig {
+            layer_height: 0.15,
+            first_layer_height: Some(0.3),
+            infill: crate::config::InfillConfig {
+                pattern: InfillType::Gyroid,
+                density: 35.0,
+            },
+            ..Default::default()
+        };
+
+        let toml = config.to_toml();
+        assert!(toml.starts_with("schema_version = 1\n"), "{}", toml);
+        assert!(toml.contains("layer_height = 0.15\n"), "{}", toml);
+        for read in [
+            SlicerConfig::from_toml(&toml).unwrap(),
+            SlicerConfig::from_json(&config.to_json()).unwrap(),
+        ] {
+            assert_float_eq!(read.layer_height, 0.15, abs <= 1e-6);
+            assert_eq!(read.first_layer_height, Some(0.3));
+            assert_eq!(read.infill.pattern, InfillType::Gyroid);
+            assert_eq!(read.to_toml(), toml);
+        }
+    }
+
+    #[test]
+    fn missing_settings_are_defaults() {
+        let config =
+            SlicerConfig::from_toml("layer_height = 0.1\n[gcode]\nflavor = \"klipper\"\n").unwrap();
+
+        assert_float_eq!(config.layer_height, 0.1, abs <= 1e-6);
+        assert_eq!(config.gcode.flavor, GcodeFlavor::Klipper);
+        assert_eq!(config.perimeters, 2);
+        assert_float_eq!(config.gcode.nozzle_temperature, 210.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(
+            SlicerConfig::from_toml("layer_heigth = 0.1"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            SlicerConfig::from_json("{\"schema_version\": 99}"),
+            Err(ConfigError::UnsupportedSchemaVersion(99))
+        ));
+        match SlicerConfig::from_toml("nozzle_diameter = 0.4\nlayer_height = 0.35") {
+            Err(ConfigError::Invalid(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].setting, "layer_height");
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match SlicerConfig::from_toml("[gcode.feedrates]\ntravel = 0") {
+            Err(ConfigError::Invalid(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].setting, "gcode.feedrates.travel");
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match SlicerConfig::from_toml("[support]\noverhang_angle = 90") {
+            Err(ConfigError::Invalid(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].setting, "support.overhang_angle");
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn profiles_inherit_and_layer() {
+        let library = library(&[
+            "name = \"base\"\nkind = \"printer\"\nnozzle_diameter = 0.6\n[retraction]\nlength = 2.0\nspeed = 40.0\n",
+            "name = \"direct\"\nkind = \"printer\"\ninherits = \"base\"\n[retraction]\nlength = 0.5\n",
+            "name = \"petg\"\nkind = \"filament\"\n[gcode]\nnozzle_temperature = 240.0\n",
+            "name = \"draft\"\nkind = \"print\"\nlayer_height = 0.4\n",
+        ]);
+
+        let config = library.config(&["draft", "petg", "direct"]).unwrap();
+
+        assert_float_eq!(config.nozzle_diameter, 0.6, abs <= 1e-6);
+        assert_float_eq!(config.retraction.length, 0.5, abs <= 1e-6);
+        assert_float_eq!(config.retraction.speed, 40.0, abs <= 1e-6);
+        assert_float_eq!(config.gcode.nozzle_temperature, 240.0, abs <= 1e-6);
+        assert_float_eq!(config.layer_height, 0.4, abs <= 1e-6);
+        // 0.4mm layers are too thick for the default 0.4mm nozzle.
+        assert!(matches!(
+            library.config(&["draft"]),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn profile_errors() {
+        assert!(matches!(
+            Profile::from_toml("name = \"pla\"\nkind = \"filament\"\nlayer_height = 0.1\n"),
+            Err(ConfigError::WrongProfileKind {
+                kind: ProfileKind::Print,
+                ..
+            })
+        ));
+        let library = library(&[
+            "name = \"a\"\nkind = \"print\"\ninherits = \"b\"\n",
+            "name = \"b\"\nkind = \"print\"\ninherits = \"a\"\n",
+            "name = \"c\"\nkind = \"filament\"\ninherits = \"a\"\n",
+        ]);
+        match library.config(&["a"]) {
+            Err(ConfigError::InheritanceCycle(names)) => assert_eq!(names, ["a", "b", "a"]),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        assert!(matches!(
+            library.config(&["c"]),
+            Err(ConfigError::WrongProfileKind { .. })
+        ));
+        assert!(matches!(
+            library.config(&["d"]),
+            Err(ConfigError::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn settings_chosen_per_print() {
+        for setting in [
+            "retraction.avoid_crossing_perimeters",
+            "retraction.only_when_crossing_perimeters",
+            "gcode.print_acceleration",
+        ] {
+            assert_eq!(ProfileKind::of_setting(setting), ProfileKind::Print);
+        }
+        assert_eq!(
+            ProfileKind::of_setting("retraction.length"),
+            ProfileKind::Printer
+        );
+    }
+
+    #[test]
+    fn duplicate_profile_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = "name = \"pla\"\nkind = \"filament\"\n";
+        fs::write(dir.path().join("a.toml"), profile).unwrap();
+        fs::write(dir.path().join("b.toml"), profile).unwrap();
+
+        let error = ProfileLibrary::from_dir(dir.path()).unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("'pla'"), "{}", message);
+        assert!(message.contains("a.toml") && message.contains("b.toml"));
+    }
+}
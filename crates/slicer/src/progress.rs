@@ -0,0 +1,191 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A stage of the slicing pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Cutting the mesh into layers.
+    Slicing,
+    /// Finding the surfaces, overhangs and support of each layer.
+    Analysis,
+    /// Generating the extrusions for each layer.
+    Toolpaths,
+    /// Writing G-code for each layer.
+    Gcode,
+}
+
+impl Stage {
+    /// A human readable name for the stage.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Slicing => "slicing",
+            Stage::Analysis => "analysis",
+            Stage::Toolpaths => "toolpaths",
+            Stage::Gcode => "gcode",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Receives progress updates from the slicing pipeline.
+///
+/// Updates may come from several threads at once, and aren't necessarily
+/// in order when they do.
+pub trait ProgressSink: Sync {
+    /// Called when `done` out of `total` steps of `stage` are finished.
+    fn report(&self, stage: Stage, done: usize, total: usize);
+}
+
+impl<F: Fn(Stage, usize, usize) + Sync> ProgressSink for F {
+    fn report(&self, stage: Stage, done: usize, total: usize) {
+        self(stage, done, total)
+    }
+}
+
+/// The error returned when a job is stopped by its `CancellationToken`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("slicing was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A flag used to stop a running job from another thread.
+///
+/// Clones share the same flag, so one can be handed to the job and another
+/// kept to cancel it. Jobs check the flag between layers, so they stop
+/// shortly after being cancelled rather than immediately.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks any job using this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// How a job reports its progress, and how it can be cancelled.
+///
+/// The default reports nothing and can't be cancelled.
+#[derive(Clone, Copy, Default)]
+pub struct Progress<'a> {
+    pub sink: Option<&'a dyn ProgressSink>,
+    pub cancellation: Option<&'a CancellationToken>,
+}
+
+impl Progress<'_> {
+    /// Passes an update on to the sink, if there is one.
+    pub fn report(&self, stage: Stage, done: usize, total: usize) {
+        if let Some(sink) = self.sink {
+            sink.report(stage, done, total);
+        }
+    }
+
+    /// Returns `Err(Cancelled)` if the job has been cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        match self.cancellation {
+            Some(token) if token.is_cancelled() => Err(Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::config::SlicerConfig;
+    use crate::gcode::generate_gcode_with_progress;
+    use crate::slicer::slice_mesh_with_progress;
+    use crate::toolpath::generate_toolpaths_with_progress;
+    use mandoline_mesh::DefaultMesh;
+    use mandoline_test_data::STL_CUBE;
+
+    fn cube() -> DefaultMesh {
+        mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap()
+    }
+
+    #[test]
+    fn token_is_shared_by_clones() {
+        let token = CancellationToken::new();
+        let progress = Progress {
+            cancellation: Some(&token),
+            ..Default::default()
+        };
+        assert_eq!(progress.check(), Ok(()));
+        token.clone().cancel();
+        assert_eq!(progress.check(), Err(Cancelled));
+    }
+
+    #[test]
+    fn reports_every_stage() {
+        let config = SlicerConfig::default();
+        let updates = Mutex::new(Vec::new());
+        let sink = |stage, done, total| updates.lock().unwrap().push((stage, done, total));
+        let progress = Progress {
+            sink: Some(&sink),
+            ..Default::default()
+        };
+
+        let sliced = slice_mesh_with_progress(cube(), &config, progress).unwrap();
+        let toolpaths = generate_toolpaths_with_progress(&sliced, &config, progress).unwrap();
+        generate_gcode_with_progress(&toolpaths, &config, progress).unwrap();
+
+        let updates = updates.into_inner().unwrap();
+        for stage in [
+            Stage::Slicing,
+            Stage::Analysis,
+            Stage::Toolpaths,
+            Stage::Gcode,
+        ] {
+            let mut done = updates
+                .iter()
+                .filter(|u| u.0 == stage)
+                .map(|u| (u.1, u.2))
+                .collect::<Vec<_>>();
+            done.sort();
+            let total = done[0].1;
+            assert!(total > 0, "{}", stage);
+            assert_eq!(done.last(), Some(&(total, total)), "{}", stage);
+        }
+    }
+
+    #[test]
+    fn cancelled_slice_stops() {
+        let config = SlicerConfig::default();
+        let token = CancellationToken::new();
+        // Cancel as soon as the first layer is sliced.
+        let sink = |_, _, _| token.cancel();
+        let progress = Progress {
+            sink: Some(&sink),
+            cancellation: Some(&token),
+        };
+
+        assert_eq!(
+            slice_mesh_with_progress(cube(), &config, progress).err(),
+            Some(Cancelled)
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use cgmath::Vector2;
+use geo::algorithm::buffer::{BufferStyle, LineJoin};
+use geo::bool_ops::FillRule;
+use geo::orient::{Direction, Orient};
+use geo::{
+    Area, BooleanOps, BoundingRect, Buffer, ConvexHull, Coord, Intersects, LineString, MapCoords,
+    MultiLineString, MultiPoint, MultiPolygon, Polygon,
+};
+
+use crate::contour::{ClosedPath, Contour, Polyline};
+
+/// Joins sharper than this angle (in radians) are beveled instead of mitered
+/// when offsetting a region.
+const MITER_LIMIT: f32 = 0.5;
+
+/// A line segment in the XY plane of a layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineSegment {
+    pub start: Vector2<f32>,
+    pub end: Vector2<f32>,
+}
+
+impl LineSegment {
+    pub fn new(start: Vector2<f32>, end: Vector2<f32>) -> Self {
+        Self { start, end }
+    }
+
+    pub fn length(&self) -> f32 {
+        let d = self.end - self.start;
+        (d.x * d.x + d.y * d.y).sqrt()
+    }
+}
+
+/// An area of a layer, made up of zero or more islands that may contain holes.
+///
+/// A `Contour` only describes the outlines produced by slicing. A `Region`
+/// is the filled area bounded by those outlines and supports the boolean
+/// and offset operations needed to turn outlines into toolpaths.
+#[derive(Clone, Debug)]
+pub struct Region {
+    polygons: MultiPolygon<f32>,
+}
+
+fn to_line_string(path: &ClosedPath) -> LineString<f32> {
+    let mut coords: Vec<(f32, f32)> = path.points().iter().map(|p| (p.x, p.y)).collect();
+    if let Some(&first) = coords.first() {
+        coords.push(first);
+    }
+    LineString::from(coords)
+}
+
+fn to_closed_path(ring: &LineString<f32>) -> ClosedPath {
+    let mut path = ClosedPath::new();
+    // geo repeats the first coordinate at the end of a closed ring, but a
+    // `ClosedPath` stores it only once.
+    let coords = &ring.0;
+    let len = if coords.len() > 1 && coords.first() == coords.last() {
+        coords.len() - 1
+    } else {
+        coords.len()
+    };
+    for c in &coords[..len] {
+        path.add_point(c.x, c.y);
+    }
+    path
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self {
+            polygons: MultiPolygon(Vec::new()),
+        }
+    }
+
+    /// Builds the filled area bounded by a set of closed paths.
+    ///
+    /// Paths are combined with the even-odd rule, so a path nested inside
+    /// another becomes a hole, and a path inside that hole becomes a new
+    /// island. This does not depend on the winding of the input paths.
+    pub fn from_paths(paths: &[ClosedPath]) -> Self {
+        let rings = paths
+            .iter()
+            .filter(|p| p.points().len() >= 3)
+            .map(|p| Polygon::new(to_line_string(p), vec![]))
+            .collect::<Vec<_>>();
+        let subject = MultiPolygon(rings);
+        Self {
+            polygons: subject.union_with_fill_rule(&MultiPolygon(vec![]), FillRule::EvenOdd),
+        }
+    }
+
+    /// Builds a polygon approximating a circle with `segments` sides.
+    pub fn circle(center: Vector2<f32>, radius: f32, segments: usize) -> Self {
+        let mut path = ClosedPath::new();
+        for i in 0..segments {
+            let angle = i as f32 * std::f32::consts::TAU / segments as f32;
+            path.add_point(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            );
+        }
+        Self::from_paths(&[path])
+    }
+
+    /// Returns the smallest convex region that contains all of `points`.
+    pub fn convex_hull_of_points(points: &[Vector2<f32>]) -> Self {
+        if points.len() < 3 {
+            return Self::new();
+        }
+        let points = MultiPoint::from(points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>());
+        Self {
+            polygons: MultiPolygon(vec![points.convex_hull()]),
+        }
+    }
+
+    /// Merges many regions into one.
+    ///
+    /// This is equivalent to folding `union` over all of `regions`, but does
+    /// the work in a single pass.
+    pub fn union_all<'a>(regions: impl IntoIterator<Item = &'a Region>) -> Self {
+        let polygons = regions
+            .into_iter()
+            .flat_map(|r| r.polygons.0.iter())
+            .map(|p| p.orient(Direction::Default))
+            .collect::<Vec<_>>();
+        Self {
+            polygons: MultiPolygon(polygons)
+                .union_with_fill_rule(&MultiPolygon(vec![]), FillRule::NonZero),
+        }
+    }
+
+    pub fn from_contour(contour: &Contour) -> Self {
+        Self::from_paths(contour.paths())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polygons.0.is_empty()
+    }
+
+    /// The total filled area of this region.
+    pub fn area(&self) -> f32 {
+        self.polygons.unsigned_area()
+    }
+
+    /// Returns the (min, max) corners of the axis-aligned bounding box of
+    /// this region, or `None` if the region is empty.
+    pub fn bounding_box(&self) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        self.polygons.bounding_rect().map(|r| {
+            (
+                Vector2::new(r.min().x, r.min().y),
+                Vector2::new(r.max().x, r.max().y),
+            )
+        })
+    }
+
+    /// Returns true if `point` is inside this region or on its boundary.
+    pub fn contains_point(&self, point: Vector2<f32>) -> bool {
+        self.polygons.intersects(&Coord {
+            x: point.x,
+            y: point.y,
+        })
+    }
+
+    /// Grows (positive `distance`) or shrinks (negative `distance`) this
+    /// region by moving every edge along its normal.
+    pub fn offset(&self, distance: f32) -> Region {
+        if self.is_empty() || distance == 0.0 {
+            return self.clone();
+        }
+        let style = BufferStyle::new(distance).line_join(LineJoin::Miter(MITER_LIMIT));
+        Self {
+            polygons: self.polygons.buffer_with_style(style),
+        }
+    }
+
+    pub fn union(&self, other: &Region) -> Region {
+        Self {
+            polygons: self.polygons.union(&other.polygons),
+        }
+    }
+
+    pub fn intersection(&self, other: &Region) -> Region {
+        Self {
+            polygons: self.polygons.intersection(&other.polygons),
+        }
+    }
+
+    pub fn difference(&self, other: &Region) -> Region {
+        Self {
+            polygons: self.polygons.difference(&other.polygons),
+        }
+    }
+
+    /// Returns the smallest convex region that contains all of this region.
+    pub fn convex_hull(&self) -> Region {
+        if self.is_empty() {
+            return Region::new();
+        }
+        Region {
+            polygons: MultiPolygon(vec![self.polygons.convex_hull()]),
+        }
+    }
+
+    /// Moves every point of this region with `f`, which must keep the
+    /// region the right way up, like a rotation or a translation.
+    pub fn map_points(&self, f: impl Fn(Vector2<f32>) -> Vector2<f32>) -> Region {
+        Region {
+            polygons: self.polygons.map_coords(|c| {
+                let p = f(Vector2::new(c.x, c.y));
+                Coord { x: p.x, y: p.y }
+            }),
+        }
+    }
+
+    /// Returns this region with all of its holes filled in.
+    pub fn without_holes(&self) -> Region {
+        let polygons = self
+            .polygons
+            .0
+            .iter()
+            .map(|p| Polygon::new(p.exterior().clone(), vec![]))
+            .collect::<Vec<_>>();
+        Region {
+            polygons: MultiPolygon(polygons)
+                .union_with_fill_rule(&MultiPolygon(vec![]), FillRule::NonZero),
+        }
+    }
+
+    /// Splits this region into its separate islands.
+    pub fn islands(&self) -> impl Iterator<Item = Region> + '_ {
+        self.polygons.0.iter().map(|p| Region {
+            polygons: MultiPolygon(vec![p.clone()]),
+        })
+    }
+
+    /// Returns the outlines of this region.
+    ///
+    /// For each island the outer boundary is emitted first, in
+    /// counter-clockwise order, followed by its holes in clockwise order.
+    pub fn paths(&self) -> Vec<ClosedPath> {
+        let mut paths = Vec::new();
+        for polygon in &self.polygons.0 {
+            let polygon = polygon.orient(Direction::Default);
+            paths.push(to_closed_path(polygon.exterior()));
+            paths.extend(polygon.interiors().iter().map(to_closed_path));
+        }
+        paths
+    }
+
+    /// Clips a set of polylines to this region.
+    ///
+    /// A polyline that leaves and re-enters the region is split into several
+    /// polylines, one for each portion inside the region.
+    pub fn clip_polylines(&self, polylines: &[Polyline]) -> Vec<Polyline> {
+        if self.is_empty() || polylines.is_empty() {
+            return Vec::new();
+        }
+        let lines = MultiLineString(
+            polylines
+                .iter()
+                .map(|p| {
+                    LineString::from(p.points().iter().map(|v| (v.x, v.y)).collect::<Vec<_>>())
+                })
+                .collect(),
+        );
+        self.polygons
+            .clip(&lines, false)
+            .0
+            .into_iter()
+            .map(|ls| Polyline::from_points(ls.0.iter().map(|c| Vector2::new(c.x, c.y)).collect()))
+            .filter(|p| p.points().len() >= 2 && p.length() > 0.0)
+            .collect()
+    }
+
+    /// Clips a set of line segments to this region, returning only the
+    /// portions of each segment that lie inside.
+    pub fn clip_segments(&self, segments: &[LineSegment]) -> Vec<LineSegment> {
+        if self.is_empty() || segments.is_empty() {
+            return Vec::new();
+        }
+        let lines = MultiLineString(
+            segments
+                .iter()
+                .map(|s| LineString::from(vec![(s.start.x, s.start.y), (s.end.x, s.end.y)]))
+                .collect(),
+        );
+        self.polygons
+            .clip(&lines, false)
+            .0
+            .iter()
+            .flat_map(|ls| {
+                ls.lines().map(|l| {
+                    LineSegment::new(
+                        Vector2::new(l.start.x, l.start.y),
+                        Vector2::new(l.end.x, l.end.y),
+                    )
+                })
+            })
+            .filter(|s| s.length() > 0.0)
+            .collect()
+    }
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rect_path;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn nested_paths_use_even_odd() {
+        let region = Region::from_paths(&[
+            rect_path(0.0, 0.0, 10.0, 10.0),
+            rect_path(2.0, 2.0, 8.0, 8.0),
+            rect_path(4.0, 4.0, 6.0, 6.0),
+        ]);
+        assert_eq!(region.islands().count(), 2);
+        assert_float_eq!(region.area(), 100.0 - 36.0 + 4.0, abs <= 0.001);
+    }
+
+    #[test]
+    fn offset_square() {
+        let region = Region::from_paths(&[rect_path(0.0, 0.0, 10.0, 10.0)]);
+        assert_float_eq!(region.offset(-1.0).area(), 64.0, abs <= 0.001);
+        assert_float_eq!(region.offset(1.0).area(), 144.0, abs <= 0.01);
+        assert!(region.offset(-5.5).is_empty());
+    }
+
+    #[test]
+    fn union_all_overlapping() {
+        let regions = [
+            Region::from_paths(&[rect_path(0.0, 0.0, 2.0, 2.0)]),
+            Region::from_paths(&[rect_path(1.0, 0.0, 3.0, 2.0)]),
+            Region::from_paths(&[rect_path(5.0, 5.0, 6.0, 6.0)]),
+        ];
+        let union = Region::union_all(&regions);
+        assert_eq!(union.islands().count(), 2);
+        assert_float_eq!(union.area(), 7.0, abs <= 0.001);
+    }
+
+    #[test]
+    fn clip_segment_through_hole() {
+        let region = Region::from_paths(&[
+            rect_path(0.0, 0.0, 10.0, 10.0),
+            rect_path(4.0, 4.0, 6.0, 6.0),
+        ]);
+        let clipped = region.clip_segments(&[LineSegment::new(
+            Vector2::new(-1.0, 5.0),
+            Vector2::new(11.0, 5.0),
+        )]);
+        assert_eq!(clipped.len(), 2);
+        let total: f32 = clipped.iter().map(LineSegment::length).sum();
+        assert_float_eq!(total, 8.0, abs <= 0.001);
+    }
+}
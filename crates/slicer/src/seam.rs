@@ -0,0 +1,178 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::SeamPosition;
+use crate::contour::ClosedPath;
+
+/// Corners that turn by less than this, in radians, are treated as straight
+/// when looking for a corner to hide the seam in.
+const MIN_CORNER_ANGLE: f32 = 0.1;
+
+/// Returns the signed angle the path turns through at each of its points, in
+/// radians.
+///
+/// Positive angles turn left. Since outlines are counter-clockwise and holes
+/// are clockwise, the model is always on the left of the path, so negative
+/// angles are concave corners of the model.
+pub(crate) fn turn_angles(points: &[Vector2<f32>]) -> Vec<f32> {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let incoming = points[i] - points[(i + n - 1) % n];
+            let outgoing = points[(i + 1) % n] - points[i];
+            let cross = incoming.x * outgoing.y - incoming.y * outgoing.x;
+            cross.atan2(incoming.dot(outgoing))
+        })
+        .collect()
+}
+
+/// Picks the point to hide a seam in.
+///
+/// The sharpest concave corner is preferred, since the seam is tucked away
+/// in it. Otherwise the sharpest convex corner is used, where the seam at
+/// least lines up with an edge of the model. `preferred` breaks ties.
+fn hidden_seam(points: &[Vector2<f32>], preferred: impl Fn(usize) -> f32) -> usize {
+    let angles = turn_angles(points);
+    let score = |i: usize| {
+        let angle = angles[i];
+        if angle < -MIN_CORNER_ANGLE {
+            // Concave corners always beat convex ones.
+            10.0 - angle
+        } else if angle > MIN_CORNER_ANGLE {
+            angle
+        } else {
+            0.0
+        }
+    };
+    (0..points.len())
+        .max_by(|&a, &b| {
+            score(a)
+                .total_cmp(&score(b))
+                .then(preferred(a).total_cmp(&preferred(b)))
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the point furthest towards the back of the bed (+Y), preferring
+/// the leftmost on a tie.
+fn rear_seam(points: &[Vector2<f32>]) -> usize {
+    (0..points.len())
+        .max_by(|&a, &b| {
+            points[a]
+                .y
+                .total_cmp(&points[b].y)
+                .then(points[b].x.total_cmp(&points[a].x))
+        })
+        .unwrap_or(0)
+}
+
+/// A hash of `seed`, used to pick random seams that are the same every time
+/// a model is sliced.
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Chooses where on a perimeter loop to start printing it.
+///
+/// `previous` holds the seams of the layer below, used to line up seams
+/// when `position` is `SeamPosition::Aligned`. `seed` picks the seam for
+/// `SeamPosition::Random`, and should differ for every loop.
+///
+/// Returns the index into `path`'s points of the seam.
+pub fn choose_seam(
+    path: &ClosedPath,
+    position: SeamPosition,
+    previous: &[Vector2<f32>],
+    seed: u64,
+) -> usize {
+    let points = path.points();
+    if points.is_empty() {
+        return 0;
+    }
+    let distance_to_previous = |i: usize| {
+        previous
+            .iter()
+            .map(|p| (points[i] - p).magnitude())
+            .fold(f32::MAX, f32::min)
+    };
+    match position {
+        SeamPosition::Aligned if !previous.is_empty() => (0..points.len())
+            .min_by(|&a, &b| distance_to_previous(a).total_cmp(&distance_to_previous(b)))
+            .unwrap(),
+        // With nothing to line up with, start the seam in a hidden corner so
+        // the layers above follow it there.
+        SeamPosition::Aligned | SeamPosition::Hidden => hidden_seam(points, |i| points[i].y),
+        SeamPosition::Rear => rear_seam(points),
+        SeamPosition::Random => (splitmix64(seed) % points.len() as u64) as usize,
+    }
+}
+
+/// Rotates `path` so that it starts at its seam.
+pub fn place_seam(
+    path: &mut ClosedPath,
+    position: SeamPosition,
+    previous: &[Vector2<f32>],
+    seed: u64,
+) {
+    let seam = choose_seam(path, position, previous, seed);
+    path.points_vec().rotate_left(seam);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An L shape, with its inside corner at (2, 2).
+    fn l_shape() -> ClosedPath {
+        let mut path = ClosedPath::new();
+        for (x, y) in [
+            (10.0, 0.0),
+            (10.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ] {
+            path.add_point(x, y);
+        }
+        path
+    }
+
+    #[test]
+    fn hidden_seam_in_concave_corner() {
+        let mut path = l_shape();
+        place_seam(&mut path, SeamPosition::Hidden, &[], 0);
+        assert_eq!(path.points()[0], Vector2::new(2.0, 2.0));
+        // The loop is rotated, not changed.
+        assert_eq!(path.points().len(), 6);
+        assert_eq!(path.points()[1], Vector2::new(2.0, 10.0));
+    }
+
+    #[test]
+    fn rear_seam_at_back_left() {
+        let mut path = l_shape();
+        place_seam(&mut path, SeamPosition::Rear, &[], 0);
+        assert_eq!(path.points()[0], Vector2::new(0.0, 10.0));
+    }
+
+    #[test]
+    fn aligned_seam_follows_previous() {
+        let path = l_shape();
+        let previous = [Vector2::new(9.5, 2.5)];
+        let seam = choose_seam(&path, SeamPosition::Aligned, &previous, 0);
+        assert_eq!(path.points()[seam], Vector2::new(10.0, 2.0));
+    }
+
+    #[test]
+    fn random_seam_varies() {
+        let path = l_shape();
+        let seams = (0..20)
+            .map(|seed| choose_seam(&path, SeamPosition::Random, &[], seed))
+            .collect::<Vec<_>>();
+        assert!(seams.iter().any(|&s| s != seams[0]));
+        // The same seed always gives the same seam.
+        assert_eq!(seams[3], choose_seam(&path, SeamPosition::Random, &[], 3));
+    }
+}
@@ -0,0 +1,428 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::ResolutionConfig;
+use crate::contour::ClosedPath;
+
+/// Returns the distance from `p` to the segment from `a` to `b`.
+fn distance_to_segment(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    if length2 == 0.0 {
+        return (p - a).magnitude();
+    }
+    let t = ((p - a).dot(ab) / length2).clamp(0.0, 1.0);
+    (p - (a + ab * t)).magnitude()
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Returns twice the signed area of a closed path, positive if it's
+/// counter-clockwise.
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum()
+}
+
+/// Returns true if the segments `a0`-`a1` and `b0`-`b1` cross or touch.
+fn segments_intersect(
+    a0: Vector2<f32>,
+    a1: Vector2<f32>,
+    b0: Vector2<f32>,
+    b1: Vector2<f32>,
+) -> bool {
+    let d1 = cross(a1 - a0, b0 - a0);
+    let d2 = cross(a1 - a0, b1 - a0);
+    let d3 = cross(b1 - b0, a0 - b0);
+    let d4 = cross(b1 - b0, a1 - b0);
+    if d1 == 0.0 && d2 == 0.0 {
+        // Collinear segments only intersect if they overlap.
+        let axis = a1 - a0;
+        let (s0, s1) = (0.0, axis.magnitude2());
+        let (t0, t1) = ((b0 - a0).dot(axis), (b1 - a0).dot(axis));
+        return t0.min(t1) <= s1 && t0.max(t1) >= s0;
+    }
+    d1 * d2 <= 0.0 && d3 * d4 <= 0.0
+}
+
+/// A closed path being simplified.
+///
+/// Points are removed by clearing their `kept` flag rather than being
+/// deleted, so that they can be put back if removing them makes the path
+/// cross itself.
+struct Simplification<'a> {
+    points: &'a [Vector2<f32>],
+    kept: Vec<bool>,
+    /// The points left after merging short segments, which Douglas-Peucker
+    /// picks from.
+    candidates: Vec<bool>,
+}
+
+impl Simplification<'_> {
+    /// Returns the indices of the points still in the path.
+    fn kept_indices(&self) -> Vec<usize> {
+        (0..self.points.len()).filter(|&i| self.kept[i]).collect()
+    }
+
+    /// Returns the indices of the points between `a` and `b`, wrapping
+    /// around the end of the path.
+    fn between(&self, a: usize, b: usize) -> impl Iterator<Item = usize> {
+        let n = self.points.len();
+        let count = (b + n - a - 1) % n;
+        (1..=count).map(move |k| (a + k) % n)
+    }
+
+    /// Returns the point between `a` and `b` that is farthest from the
+    /// segment joining them, with its distance. With `candidates_only`, only
+    /// points that survived merging are considered.
+    fn farthest(&self, a: usize, b: usize, candidates_only: bool) -> Option<(usize, f32)> {
+        let (pa, pb) = (self.points[a], self.points[b]);
+        self.between(a, b)
+            .filter(|&i| !candidates_only || self.candidates[i])
+            .map(|i| (i, distance_to_segment(self.points[i], pa, pb)))
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+    }
+
+    /// Keeps the points needed for the path between `a` and `b` to stay
+    /// within `tolerance` of the original.
+    fn douglas_peucker(&mut self, a: usize, b: usize, tolerance: f32) {
+        let mut stack = vec![(a, b)];
+        while let Some((a, b)) = stack.pop() {
+            if let Some((i, distance)) = self.farthest(a, b, true) {
+                if distance > tolerance {
+                    self.kept[i] = true;
+                    stack.push((a, i));
+                    stack.push((i, b));
+                }
+            }
+        }
+    }
+
+    /// Returns how far the kept point at `indices[k]` is from the line
+    /// joining its kept neighbours.
+    fn corner_size(&self, indices: &[usize], k: usize) -> f32 {
+        let n = indices.len();
+        let prev = self.points[indices[(k + n - 1) % n]];
+        let next = self.points[indices[(k + 1) % n]];
+        distance_to_segment(self.points[indices[k]], prev, next)
+    }
+
+    /// Removes one end of every segment shorter than `min_length`,
+    /// choosing whichever end makes the smaller corner.
+    fn merge_short_segments(&mut self, min_length: f32) {
+        let mut indices = self.kept_indices();
+        let mut k = 0;
+        while k < indices.len() && indices.len() > 3 {
+            let n = indices.len();
+            let (a, b) = (indices[k], indices[(k + 1) % n]);
+            if (self.points[b] - self.points[a]).magnitude() >= min_length {
+                k += 1;
+                continue;
+            }
+            let remove = if self.corner_size(&indices, k) < self.corner_size(&indices, (k + 1) % n)
+            {
+                k
+            } else {
+                (k + 1) % n
+            };
+            self.kept[indices[remove]] = false;
+            indices.remove(remove);
+            // The segment ending at the removed point may now be short, so
+            // look at it again.
+            k = k.saturating_sub(1);
+        }
+    }
+
+    /// Puts back the point that the segment starting at kept point `a` is
+    /// farthest from. Returns false if the segment has no removed points.
+    fn restore(&mut self, a: usize, candidates_only: bool) -> bool {
+        let b = (1..self.points.len())
+            .map(|k| (a + k) % self.points.len())
+            .find(|&i| self.kept[i])
+            .unwrap_or(a);
+        match self.farthest(a, b, candidates_only) {
+            Some((i, _)) => {
+                self.kept[i] = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A segment of a path being simplified.
+struct Segment {
+    path: usize,
+    /// The indices of the segment's kept points in its path.
+    start: usize,
+    end: usize,
+    p0: Vector2<f32>,
+    p1: Vector2<f32>,
+}
+
+/// Returns the paths and starting points of every segment that replaces
+/// removed points and crosses another segment.
+fn find_crossings(paths: &[Simplification]) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    for (path, simplification) in paths.iter().enumerate() {
+        let indices = simplification.kept_indices();
+        for (k, &start) in indices.iter().enumerate() {
+            let end = indices[(k + 1) % indices.len()];
+            segments.push(Segment {
+                path,
+                start,
+                end,
+                p0: simplification.points[start],
+                p1: simplification.points[end],
+            });
+        }
+    }
+    let is_shortcut = |s: &Segment| paths[s.path].between(s.start, s.end).next().is_some();
+
+    // Sweep across the segments from left to right, only comparing the ones
+    // that overlap in X.
+    segments.sort_by(|a, b| a.p0.x.min(a.p1.x).total_cmp(&b.p0.x.min(b.p1.x)));
+    let mut crossings = Vec::new();
+    let mut active: Vec<&Segment> = Vec::new();
+    for segment in &segments {
+        let min_x = segment.p0.x.min(segment.p1.x);
+        active.retain(|s| s.p0.x.max(s.p1.x) >= min_x);
+        for other in &active {
+            let adjacent = segment.path == other.path
+                && (segment.start == other.end || segment.end == other.start);
+            if adjacent || !segments_intersect(segment.p0, segment.p1, other.p0, other.p1) {
+                continue;
+            }
+            for s in [segment, *other] {
+                if is_shortcut(s) {
+                    crossings.push((s.path, s.start));
+                }
+            }
+        }
+        active.push(segment);
+    }
+    crossings
+}
+
+/// Simplifies closed paths, removing points that add less detail than
+/// `resolution` asks for.
+///
+/// Segments that are too short are merged into their neighbours, and then
+/// the paths are simplified with the Douglas-Peucker algorithm. Removing
+/// points can make a path cross itself or one of the other paths, so points
+/// are put back until none of the simplified paths cross.
+pub(crate) fn simplify_paths(
+    paths: &[ClosedPath],
+    resolution: &ResolutionConfig,
+) -> Vec<ClosedPath> {
+    let mut simplifications = paths
+        .iter()
+        .map(|path| {
+            let points = path.points();
+            let mut simplification = Simplification {
+                points,
+                kept: vec![true; points.len()],
+                candidates: Vec::new(),
+            };
+            if points.len() > 3 && resolution.min_segment_length > 0.0 {
+                simplification.merge_short_segments(resolution.min_segment_length);
+            }
+            let candidates = simplification.kept_indices();
+            simplification.candidates = simplification.kept.clone();
+            if candidates.len() > 3 && resolution.max_deviation > 0.0 {
+                // Split the path at the point farthest from its start, and
+                // simplify each half.
+                let start = candidates[0];
+                let far = candidates
+                    .iter()
+                    .copied()
+                    .max_by(|&i, &j| {
+                        let d = |k: usize| (points[k] - points[start]).magnitude2();
+                        d(i).total_cmp(&d(j))
+                    })
+                    .unwrap();
+                simplification.kept = vec![false; points.len()];
+                simplification.kept[start] = true;
+                simplification.kept[far] = true;
+                simplification.douglas_peucker(start, far, resolution.max_deviation);
+                simplification.douglas_peucker(far, start, resolution.max_deviation);
+                while simplification.kept_indices().len() < 3 {
+                    simplification.restore(start, true);
+                    simplification.restore(far, true);
+                }
+            }
+            simplification
+        })
+        .collect::<Vec<_>>();
+
+    loop {
+        let crossings = find_crossings(&simplifications);
+        if crossings.is_empty() {
+            break;
+        }
+        let mut restored = false;
+        for (path, start) in crossings {
+            if simplifications[path].kept[start] {
+                restored |= simplifications[path].restore(start, false);
+            }
+        }
+        if !restored {
+            break;
+        }
+    }
+
+    simplifications
+        .iter()
+        .map(|s| {
+            let points = s
+                .kept_indices()
+                .iter()
+                .map(|&i| s.points[i])
+                .collect::<Vec<_>>();
+            // A path that has collapsed or turned inside out can't be
+            // simplified, so keep it as it was.
+            let (before, after) = (signed_area(s.points), signed_area(&points));
+            if after == 0.0 || before.signum() != after.signum() {
+                ClosedPath::from_points(s.points.to_vec())
+            } else {
+                ClosedPath::from_points(points)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn circle(radius: f32, segments: usize) -> ClosedPath {
+        let mut path = ClosedPath::new();
+        for i in 0..segments {
+            let angle = 2.0 * PI * i as f32 / segments as f32;
+            path.add_point(radius * angle.cos(), radius * angle.sin());
+        }
+        path
+    }
+
+    fn resolution(max_deviation: f32, min_segment_length: f32) -> ResolutionConfig {
+        ResolutionConfig {
+            max_deviation,
+            min_segment_length,
+            ..Default::default()
+        }
+    }
+
+    fn assert_simple(paths: &[ClosedPath]) {
+        let segments = paths
+            .iter()
+            .enumerate()
+            .flat_map(|(p, path)| {
+                let n = path.points().len();
+                (0..n).map(move |i| (p, i, path.points()[i], path.points()[(i + 1) % n], n))
+            })
+            .collect::<Vec<_>>();
+        for (i, a) in segments.iter().enumerate() {
+            for b in &segments[i + 1..] {
+                let adjacent = a.0 == b.0 && ((a.1 + 1) % a.4 == b.1 || (b.1 + 1) % b.4 == a.1);
+                if !adjacent {
+                    assert!(
+                        !segments_intersect(a.2, a.3, b.2, b.3),
+                        "{:?} crosses {:?}",
+                        (a.2, a.3),
+                        (b.2, b.3)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_within_deviation() {
+        let path = circle(10.0, 1000);
+
+        let simplified = simplify_paths(&[path], &resolution(0.05, 0.0));
+
+        let points = simplified[0].points();
+        assert!(points.len() < 100, "{}", points.len());
+        // Every point of the circle stays close to the simplified path.
+        for p in circle(10.0, 1000).points() {
+            let distance = (0..points.len())
+                .map(|i| distance_to_segment(*p, points[i], points[(i + 1) % points.len()]))
+                .fold(f32::MAX, f32::min);
+            assert!(distance <= 0.05 + 1e-4, "{}", distance);
+        }
+    }
+
+    #[test]
+    fn merges_short_segments() {
+        // A square with a tiny notch in one side.
+        let square = || {
+            let mut path = ClosedPath::new();
+            for (x, y) in [
+                (0.0, 0.0),
+                (5.0, 0.0),
+                (5.0, 0.02),
+                (5.02, 0.02),
+                (5.02, 0.0),
+                (10.0, 0.0),
+                (10.0, 10.0),
+                (0.0, 10.0),
+            ] {
+                path.add_point(x, y);
+            }
+            path
+        };
+
+        let kept = simplify_paths(&[square()], &resolution(0.01, 0.0));
+        let merged = simplify_paths(&[square()], &resolution(0.01, 0.1));
+
+        assert_eq!(kept[0].points().len(), 8);
+        assert!(merged[0].points().len() < 6);
+        for (p0, p1) in merged[0].segments() {
+            let length = ((p1.0 - p0.0).powi(2) + (p1.1 - p0.1).powi(2)).sqrt();
+            assert!(length >= 0.1, "{:?}", (p0, p1));
+        }
+    }
+
+    #[test]
+    fn never_crosses() {
+        // A square with a small bump in its bottom edge, and a hole that
+        // reaches down into the bump. Flattening the bump would cut through
+        // the hole.
+        let mut outer = ClosedPath::new();
+        for (x, y) in [
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (5.0, -0.4),
+            (6.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+        ] {
+            outer.add_point(x, y);
+        }
+        let mut hole = ClosedPath::new();
+        for (x, y) in [(4.8, 2.0), (5.2, 2.0), (5.0, -0.2)] {
+            hole.add_point(x, y);
+        }
+
+        let simplified = simplify_paths(&[outer, hole], &resolution(0.5, 0.0));
+
+        assert_simple(&simplified);
+        assert!(simplified[0].points().contains(&Vector2::new(5.0, -0.4)));
+        assert!(simplified[0].points().len() < 7);
+    }
+
+    #[test]
+    fn keeps_tiny_paths() {
+        let path = circle(0.01, 12);
+
+        let simplified = simplify_paths(&[path], &resolution(0.1, 0.1));
+
+        assert!(simplified[0].points().len() >= 3);
+        assert!(signed_area(simplified[0].points()) > 0.0);
+    }
+}
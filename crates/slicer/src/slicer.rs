@@ -1,11 +1,20 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use cgmath::{InnerSpace, Vector2};
 use mandoline_mesh::{Triangle, TriangleMesh, Vector3};
 use ordered_float::OrderedFloat;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 use crate::config::*;
 use crate::contour::*;
+use crate::layer_heights::layer_boundaries;
+use crate::overhang::*;
+use crate::progress::{Cancelled, Progress, Stage};
+use crate::region::Region;
+use crate::support::*;
+use crate::surface::*;
 
 pub type OrderedVec2 = Vector2<OrderedFloat<f32>>;
 pub type SegmentMap = HashMap<OrderedVec2, OrderedVec2>;
@@ -25,8 +34,88 @@ impl Truncate for f32 {
     }
 }
 
+/// A single layer of a sliced mesh.
+pub struct SlicedLayer {
+    /// The height of the top of this layer, where the nozzle prints it.
+    z: f32,
+    /// The thickness of this layer.
+    height: f32,
+    /// The height the mesh was cut at to find this layer's contour.
+    slice_z: f32,
+    contour: Contour,
+    region: Region,
+    surfaces: Vec<Surface>,
+    overhangs: Vec<Overhang>,
+    support: SupportLayer,
+}
+
+impl SlicedLayer {
+    pub fn new(contour: Contour) -> Self {
+        let region = Region::from_contour(&contour);
+        Self {
+            z: 0.0,
+            height: 0.0,
+            slice_z: 0.0,
+            contour,
+            region,
+            surfaces: Vec::new(),
+            overhangs: Vec::new(),
+            support: SupportLayer::default(),
+        }
+    }
+
+    /// The height of the top of this layer above the bed, in mm. This is
+    /// where the nozzle sits while printing it.
+    pub fn z(&self) -> f32 {
+        self.z
+    }
+
+    /// The thickness of this layer, in mm.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// The height the mesh was cut at to find this layer's contour, in mm.
+    pub fn slice_z(&self) -> f32 {
+        self.slice_z
+    }
+
+    /// The outlines produced by slicing the mesh at this layer.
+    pub fn contour(&self) -> &Contour {
+        &self.contour
+    }
+
+    /// The area enclosed by this layer's contour.
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// This layer's area, divided up by how it should be filled.
+    pub fn surfaces(&self) -> &[Surface] {
+        self.surfaces.as_slice()
+    }
+
+    /// The areas of this layer that are not supported by the layer below.
+    pub fn overhangs(&self) -> &[Overhang] {
+        self.overhangs.as_slice()
+    }
+
+    /// The support material printed on this layer.
+    pub fn support(&self) -> &SupportLayer {
+        &self.support
+    }
+
+    /// The area of this layer covered by surfaces of type `kind`.
+    pub fn surface_region(&self, kind: SurfaceType) -> Region {
+        self.surfaces
+            .iter()
+            .filter(|s| s.kind == kind)
+            .fold(Region::new(), |acc, s| acc.union(&s.region))
+    }
+}
+
 pub struct SlicedMesh {
-    contours: Vec<Contour>,
+    layers: Vec<SlicedLayer>,
     limits_x: (f32, f32),
     limits_y: (f32, f32),
 }
@@ -34,14 +123,20 @@ pub struct SlicedMesh {
 impl SlicedMesh {
     pub fn new() -> Self {
         Self {
-            contours: Vec::new(),
+            layers: Vec::new(),
             limits_x: (0., 0.),
             limits_y: (0., 0.),
         }
     }
 
-    pub fn contours(&self) -> &[Contour] {
-        self.contours.as_slice()
+    pub fn layers(&self) -> &[SlicedLayer] {
+        self.layers.as_slice()
+    }
+
+    /// The height of the top of each layer, in mm. Layers are not
+    /// necessarily evenly spaced.
+    pub fn layer_z(&self) -> Vec<f32> {
+        self.layers.iter().map(SlicedLayer::z).collect()
     }
 
     pub fn limits_x(&self) -> (f32, f32) {
@@ -64,6 +159,11 @@ fn is_on_plane(p: &Vector3, z: f32) -> bool {
 }
 
 fn intersect(p0: &Vector3, p1: &Vector3, z: f32) -> Option<Vector3> {
+    // Neighbouring triangles share edges but list them in opposite directions.
+    // Always work from the lower point so both triangles find exactly the same
+    // intersection, and their segments join up.
+    let (p0, p1) = if p1.z < p0.z { (p1, p0) } else { (p0, p1) };
+
     // Return none if no intersection.
     let d0 = p0.z - z;
     let d1 = p1.z - z;
@@ -106,189 +206,308 @@ fn compute_min_max(t: &Triangle) -> (f32, f32) {
     (zmin, zmax)
 }
 
-// Computes the layer numbers that the triangle instersects.
-//
-// The returned range is the set of cutting planes (defined by multiples of
-// layer height) that will intersect the range zmin-zmax. Returns None if
-// this triangle does not intersect any cutting planes.
-fn compute_constant_layer_range(
-    zmin: f32,
-    zmax: f32,
+/// Fills in the surfaces, overhangs and support material for each layer,
+/// based on the layers around it.
+pub(crate) fn analyze_layers(
+    layers: &mut [SlicedLayer],
     config: &SlicerConfig,
-) -> Option<std::ops::RangeInclusive<usize>> {
-    let min_layer = (zmin / config.layer_height).ceil() as usize;
-    let max_layer = (zmax / config.layer_height).floor() as usize;
-    if max_layer > min_layer {
-        Some(min_layer..=max_layer)
-    } else {
-        None
+    progress: Progress,
+) -> Result<(), Cancelled> {
+    const STEPS: usize = 3;
+    let regions = layers.iter().map(|l| l.region.clone()).collect::<Vec<_>>();
+    let surfaces = classify_surfaces(&regions, config);
+    progress.report(Stage::Analysis, 1, STEPS);
+    progress.check()?;
+    let overhangs = detect_overhangs(&regions, config);
+    for ((layer, surfaces), overhangs) in layers.iter_mut().zip(surfaces).zip(overhangs) {
+        layer.surfaces = surfaces;
+        layer.overhangs = overhangs;
+    }
+    progress.report(Stage::Analysis, 2, STEPS);
+    progress.check()?;
+    let supports = generate_supports(layers, config);
+    for (layer, support) in layers.iter_mut().zip(supports) {
+        layer.support = support;
     }
+    progress.report(Stage::Analysis, 3, STEPS);
+    Ok(())
 }
 
-/// Given a triangle mesh, we slice it into a series of contour layers using
-/// the parameters in `SlicerConfig`.
-pub fn slice_mesh<M: TriangleMesh>(m: M, config: &SlicerConfig) -> SlicedMesh {
-    // The vector has an entry for each slice, in-order.
-    //
-    // Each layer is a hash-map that the start of a line segment to the
-    // end of that same line segment.
-    //
-    // This is used to piece the geometry back together at the end.
+/// Adds the line segment from `first` to `second`, where a cutting plane
+/// crosses triangle `t`, to `segments`.
+fn add_segment(segments: &mut SegmentMap, t: &Triangle, first: &Vector3, second: &Vector3) {
+    // Direction: We have a triangle with vertices in ccw order, and 2 points
+    // where the slicing plane cuts the trigangle. We need to determine if the
+    // produced vector is first->second or second->first.
     //
-    // TODO: If triangle mesh knows it's min/max z we can pre-allocate
-    // the entire vec here.
-    // TODO: HashMap here is not great since we may have rouding errors.
-    // We do some course (to nearest um) rouding to mitigate this.
-    let mut slices: Vec<HashMap<OrderedVec2, OrderedVec2>> = Vec::new();
+    // One way to do this is to combine the plane normal with the triangle
+    // normal with a cross product to the the direction vector.
+    const Z_NORMAL: Vector3 = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+    // u,v are two edge vectors of the triangle. Take their cross product to
+    // find the outward normal vector for this triangle.
+    let u = t.p1 - t.p0;
+    let v = t.p2 - t.p0;
+    let triangle_normal = u.cross(v).normalize();
+
+    // The direction of the generate line segment is represented by the cross
+    // product of the slicing plane normal and the triangle normal.
+    let direction = Z_NORMAL.cross(triangle_normal).normalize();
+
+    // Generate the line segment that is in the same direction we expect.
+    let forward = first - second;
+    let (first, second) = if forward.dot(direction) > 0.0 {
+        (first, second)
+    } else {
+        (second, first)
+    };
 
-    let mut add_slice = |layer, t: Triangle, first: &Vector3, second: &Vector3| {
-        if slices.len() <= layer {
-            slices.resize_with(layer + 1, HashMap::new);
-        }
+    // Floats are not hash nor eq, so we use the ordered-float crate. This is relying
+    // on numeric representations to be identical which is a bit dicey.
+    segments.insert(
+        Vector2 {
+            x: first.x.truncate_micros().into(),
+            y: first.y.truncate_micros().into(),
+            // z: implicit based on the layer.
+        },
+        Vector2 {
+            x: second.x.truncate_micros().into(),
+            y: second.y.truncate_micros().into(),
+            // z: implicit based on the layer.
+        },
+    );
+}
 
-        // Direction: We have a triangle with vertices in ccw order, and 2 points
-        // where the slicing plane cuts the trigangle. We need to determine if the
-        // produced vector is first->second or second->first.
+/// Adds the line segment where the plane at `cutting_plane` crosses `t` to
+/// `segments`, if there is one.
+fn slice_triangle(segments: &mut SegmentMap, t: &Triangle, cutting_plane: f32) {
+    let (zmin, zmax) = compute_min_max(t);
+    let a_planar = is_on_plane(&t.p0, cutting_plane);
+    let b_planar = is_on_plane(&t.p1, cutting_plane);
+    let c_planar = is_on_plane(&t.p2, cutting_plane);
+
+    match (a_planar, b_planar, c_planar) {
+        // All points lie on the cutting plane. This means the entire triangle
+        // is on the cutting plane. We don't generate line segments for this case
+        // but instead will generate these line segments from adjacent geometry.
+        (true, true, true) => (),
+
+        // If a single point lies on the cutting plane, we also ignore the point.
         //
-        // One way to do this is to combine the plane normal with the triangle
-        // normal with a cross product to the the direction vector.
-        const Z_NORMAL: Vector3 = Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: 1.0,
-        };
-        // u,v are two edge vectors of the triangle. Take their cross product to
-        // find the outward normal vector for this triangle.
-        let u = t.p1 - t.p0;
-        let v = t.p2 - t.p0;
-        let triangle_normal = u.cross(v).normalize();
-
-        // The direction of the generate line segment is represented by the cross
-        // product of the slicing plane normal and the triangle normal.
-        let direction = Z_NORMAL.cross(triangle_normal).normalize();
-
-        // Generate the line segment that is in the same direction we expect.
-        let forward = first - second;
-        let (first, second) = if forward.dot(direction) > 0.0 {
-            (first, second)
-        } else {
-            (second, first)
-        };
-
-        // Floats are not hash nor eq, so we use the ordered-float crate. This is relying
-        // on numeric representations to be identical which is a bit dicey.
-        slices[layer].insert(
-            Vector2 {
-                x: first.x.truncate_micros().into(),
-                y: first.y.truncate_micros().into(),
-                // z: implicit based on `layer`.
-            },
-            Vector2 {
-                x: second.x.truncate_micros().into(),
-                y: second.y.truncate_micros().into(),
-                // z: implicit based on `layer`.
-            },
-        );
-    };
-
-    // For each triangle, compute the slices that intersects this triangle
-    // and where.
-    for t in m.triangles() {
-        let (zmin, zmax) = compute_min_max(&t);
-        let Some(layer_range) = compute_constant_layer_range(zmin, zmax, config) else {
-            continue;
-        };
-        for layer in layer_range {
-            let cutting_plane = layer as f32 * config.layer_height;
-
-            let a_planar = is_on_plane(&t.p0, cutting_plane);
-            let b_planar = is_on_plane(&t.p1, cutting_plane);
-            let c_planar = is_on_plane(&t.p2, cutting_plane);
-
-            match (a_planar, b_planar, c_planar) {
-                // All points lie on the cutting plane. This means the entire triangle
-                // is on the cutting plane. We don't generate line segments for this case
-                // but instead will generate these line segments from adjacent geometry.
-                (true, true, true) => (),
-
-                // If a single point lies on the cutting plane, we also ignore the point.
-                //
-                // Note we do need to handle the case where the cutting plane intersects a
-                // line and a vertex. We know that does not happen if the vertex lies at zmin
-                // or zmax.
-                (true, false, false) if float_eq(t.p0.z, zmin) || float_eq(t.p0.z, zmax) => (),
-                (false, true, false) if float_eq(t.p1.z, zmin) || float_eq(t.p1.z, zmax) => (),
-                (false, false, true) if float_eq(t.p2.z, zmin) || float_eq(t.p2.z, zmax) => (),
-
-                // If two points lie on the cutting plane, then one triangle edge
-                // represents a line segment to be contributed to the slice.
-                (true, true, false) => add_slice(layer, t, &t.p0, &t.p1),
-                (false, true, true) => add_slice(layer, t, &t.p1, &t.p2),
-                (true, false, true) => add_slice(layer, t, &t.p2, &t.p0),
-
-                // We need to calculate the intersection between the cutting plane and
-                // at least one edge. The second intersection will either be another
-                // triangle edge, or a triangle vertex.
-                _ => {
-                    // Compute intersection points.
-                    //
-                    // We have 3 points that define a triangle, and a cutting plane that is
-                    // defined by the normal vector that lies along +z and the distance of
-                    // the cutting plane from the origin in the variable `cutting_plane`.
-                    //
-                    // If we label the points of the triangle as a, b, c such that these
-                    // points occur in a counter-clockwise when looking at the front of the
-                    // triangle, we next compute if any of the 3 line segments ab, bc, ca
-                    // intersect with the cutting plane. Here `None` means no intersection,
-                    // otherwise the coordinate of the intersection point is provided.
-                    let ab = intersect(&t.p0, &t.p1, cutting_plane);
-                    let bc = intersect(&t.p1, &t.p2, cutting_plane);
-                    let ca = intersect(&t.p2, &t.p0, cutting_plane);
-
-                    // Compute the total number of intersection points.
-                    let mut count = 0;
-                    for intersection in &[ab, bc, ca] {
-                        if intersection.is_some() {
-                            count += 1;
-                        }
-                    }
-                    // TODO: We need to handle the case of a line-vertex intersection. This
-                    // doesn't occur in the first cube model I'm using.
-                    if count != 2 {
-                        continue;
-                    }
-                    assert_eq!(count, 2);
-                    let (first, second) = if let Some(ab) = ab {
-                        (ab, if let Some(bc) = bc { bc } else { ca.unwrap() })
-                    } else {
-                        (bc.unwrap(), ca.unwrap())
-                    };
-
-                    // Generate the line segment that is in the same direction we expect.
-                    add_slice(layer, t, &first, &second);
-                }
+        // Note we do need to handle the case where the cutting plane intersects a
+        // line and a vertex. We know that does not happen if the vertex lies at zmin
+        // or zmax.
+        (true, false, false) if float_eq(t.p0.z, zmin) || float_eq(t.p0.z, zmax) => (),
+        (false, true, false) if float_eq(t.p1.z, zmin) || float_eq(t.p1.z, zmax) => (),
+        (false, false, true) if float_eq(t.p2.z, zmin) || float_eq(t.p2.z, zmax) => (),
+
+        // Otherwise the cutting plane runs from that vertex to the opposite edge.
+        (true, false, false) => {
+            if let Some(p) = intersect(&t.p1, &t.p2, cutting_plane) {
+                add_segment(segments, t, &t.p0, &p);
             }
         }
-    }
-    slices.into_iter().fold(SlicedMesh::new(), |mut a, x| {
-        let c = Contour::from_segment_map(x);
-        let xlim = c.limits_x();
-        let ylim = c.limits_y();
-        if xlim.0 < a.limits_x.0 {
-            a.limits_x.0 = xlim.0;
-        }
-        if xlim.1 > a.limits_x.1 {
-            a.limits_x.1 = xlim.1;
+        (false, true, false) => {
+            if let Some(p) = intersect(&t.p2, &t.p0, cutting_plane) {
+                add_segment(segments, t, &t.p1, &p);
+            }
         }
-        if ylim.0 < a.limits_y.0 {
-            a.limits_y.0 = ylim.0;
+        (false, false, true) => {
+            if let Some(p) = intersect(&t.p0, &t.p1, cutting_plane) {
+                add_segment(segments, t, &t.p2, &p);
+            }
         }
-        if ylim.1 > a.limits_y.1 {
-            a.limits_y.1 = ylim.1;
+
+        // If two points lie on the cutting plane, then one triangle edge
+        // represents a line segment to be contributed to the slice.
+        (true, true, false) => add_segment(segments, t, &t.p0, &t.p1),
+        (false, true, true) => add_segment(segments, t, &t.p1, &t.p2),
+        (true, false, true) => add_segment(segments, t, &t.p2, &t.p0),
+
+        // We need to calculate the intersection between the cutting plane and
+        // at least one edge. The second intersection will either be another
+        // triangle edge, or a triangle vertex.
+        _ => {
+            // Compute intersection points.
+            //
+            // We have 3 points that define a triangle, and a cutting plane that is
+            // defined by the normal vector that lies along +z and the distance of
+            // the cutting plane from the origin in the variable `cutting_plane`.
+            //
+            // If we label the points of the triangle as a, b, c such that these
+            // points occur in a counter-clockwise when looking at the front of the
+            // triangle, we next compute if any of the 3 line segments ab, bc, ca
+            // intersect with the cutting plane. Here `None` means no intersection,
+            // otherwise the coordinate of the intersection point is provided.
+            let ab = intersect(&t.p0, &t.p1, cutting_plane);
+            let bc = intersect(&t.p1, &t.p2, cutting_plane);
+            let ca = intersect(&t.p2, &t.p0, cutting_plane);
+
+            // Compute the total number of intersection points.
+            let mut count = 0;
+            for intersection in &[ab, bc, ca] {
+                if intersection.is_some() {
+                    count += 1;
+                }
+            }
+            // Line-vertex intersections are handled above, so anything else is a
+            // degenerate triangle.
+            if count != 2 {
+                return;
+            }
+            assert_eq!(count, 2);
+            let (first, second) = if let Some(ab) = ab {
+                (ab, if let Some(bc) = bc { bc } else { ca.unwrap() })
+            } else {
+                (bc.unwrap(), ca.unwrap())
+            };
+
+            // Generate the line segment that is in the same direction we expect.
+            add_segment(segments, t, &first, &second);
         }
-        a.contours.push(c);
-        a
-    })
+    }
+}
+
+/// Finds the triangles that cross each of the cutting planes in `planes`,
+/// which must be in ascending order.
+///
+/// Triangles are sorted by their lowest point and the planes swept upwards,
+/// keeping a set of the triangles that reach the current plane. Each
+/// triangle is only visited for the planes it spans, rather than every
+/// triangle being checked against every plane.
+fn sweep_triangles(triangles: &[Triangle], planes: &[f32]) -> Vec<Vec<usize>> {
+    let mut order = triangles
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let (zmin, zmax) = compute_min_max(t);
+            (zmin, zmax, i)
+        })
+        .collect::<Vec<_>>();
+    order.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut active = Vec::new();
+    let mut next = 0;
+    planes
+        .iter()
+        .map(|&plane| {
+            while next < order.len() && order[next].0 <= plane + 0.0001 {
+                active.push(order[next]);
+                next += 1;
+            }
+            active.retain(|&(_, zmax, _)| zmax >= plane - 0.0001);
+            active.iter().map(|&(_, _, i)| i).collect()
+        })
+        .collect()
+}
+
+/// Slices the triangles in `crossing` with the plane at `cutting_plane`,
+/// and joins the segments into a contour, simplified to `resolution`.
+fn slice_layer(
+    triangles: &[Triangle],
+    crossing: &[usize],
+    cutting_plane: f32,
+    resolution: &ResolutionConfig,
+) -> Contour {
+    // A hash-map from the start of each line segment to the end of that same
+    // line segment. This is used to piece the geometry back together.
+    //
+    // TODO: HashMap here is not great since we may have rouding errors.
+    // We do some course (to nearest um) rouding to mitigate this.
+    let mut segments = SegmentMap::new();
+    for &i in crossing {
+        slice_triangle(&mut segments, &triangles[i], cutting_plane);
+    }
+    Contour::from_segment_map(segments, resolution.collinear_tolerance).simplify(resolution)
+}
+
+/// Given a triangle mesh, we slice it into a series of contour layers using
+/// the parameters in `SlicerConfig`.
+///
+/// With the `parallel` feature, layers are sliced and their contours
+/// assembled on multiple threads.
+pub fn slice_mesh<M: TriangleMesh>(m: M, config: &SlicerConfig) -> SlicedMesh {
+    // Without a cancellation token, slicing can't fail.
+    slice_mesh_with_progress(m, config, Progress::default()).unwrap()
+}
+
+/// Slices a mesh like `slice_mesh`, reporting progress to `progress` as each
+/// layer is finished.
+///
+/// Returns `Err(Cancelled)` if `progress`'s cancellation token is cancelled
+/// before slicing finishes.
+pub fn slice_mesh_with_progress<M: TriangleMesh>(
+    m: M,
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<SlicedMesh, Cancelled> {
+    let triangles = m.triangles().collect::<Vec<_>>();
+    let boundaries = layer_boundaries(&triangles, config);
+    slice_triangles(&triangles, &boundaries, config, progress)
+}
+
+/// Slices `triangles` into a layer between each pair of `boundaries`, then
+/// finds the surfaces, overhangs and support of every layer.
+pub(crate) fn slice_triangles(
+    triangles: &[Triangle],
+    boundaries: &[f32],
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<SlicedMesh, Cancelled> {
+    let planes = boundaries
+        .windows(2)
+        .map(|w| match config.slicing_plane {
+            SlicingPlane::Middle => (w[0] + w[1]) / 2.0,
+            SlicingPlane::Top => w[1],
+        })
+        .collect::<Vec<_>>();
+    let crossing = sweep_triangles(triangles, &planes);
+
+    let done = AtomicUsize::new(0);
+    let layers = 0..planes.len();
+    #[cfg(feature = "parallel")]
+    let layers = layers.into_par_iter();
+    let contours = layers
+        .map(|i| {
+            progress.check()?;
+            let contour = slice_layer(triangles, &crossing[i], planes[i], &config.resolution);
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            progress.report(Stage::Slicing, done, planes.len());
+            Ok(contour)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut sliced = contours
+        .into_iter()
+        .enumerate()
+        .fold(SlicedMesh::new(), |mut a, (i, c)| {
+            let xlim = c.limits_x();
+            let ylim = c.limits_y();
+            if xlim.0 < a.limits_x.0 {
+                a.limits_x.0 = xlim.0;
+            }
+            if xlim.1 > a.limits_x.1 {
+                a.limits_x.1 = xlim.1;
+            }
+            if ylim.0 < a.limits_y.0 {
+                a.limits_y.0 = ylim.0;
+            }
+            if ylim.1 > a.limits_y.1 {
+                a.limits_y.1 = ylim.1;
+            }
+            let mut layer = SlicedLayer::new(c);
+            layer.z = boundaries[i + 1];
+            layer.height = boundaries[i + 1] - boundaries[i];
+            layer.slice_z = planes[i];
+            a.layers.push(layer);
+            a
+        });
+
+    analyze_layers(&mut sliced.layers, config, progress)?;
+    Ok(sliced)
 }
 
 #[cfg(test)]
@@ -388,15 +607,101 @@ mod tests {
 
     #[test]
     fn slice_simple_cube() {
-        let config = SlicerConfig { layer_height: 0.2 };
+        let config = SlicerConfig {
+            layer_height: 0.2,
+            ..Default::default()
+        };
         let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
         slice_mesh(mesh, &config);
     }
 
+    #[test]
+    fn slice_cube_surfaces() {
+        let config = SlicerConfig {
+            layer_height: 0.2,
+            ..Default::default()
+        };
+        let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let sliced = slice_mesh(mesh, &config);
+
+        let layers = sliced.layers();
+        let last = layers.len() - 1;
+        for layer in &layers[..config.bottom_layers] {
+            assert_eq!(layer.surfaces()[0].kind, SurfaceType::Bottom);
+        }
+        assert_eq!(layers[last / 2].surfaces()[0].kind, SurfaceType::Sparse);
+        for layer in &layers[layers.len() - config.top_layers..] {
+            assert_eq!(layer.surfaces()[0].kind, SurfaceType::Top);
+        }
+    }
+
     #[test]
     fn slice_calibration_cube() {
-        let config = SlicerConfig { layer_height: 0.2 };
+        let config = SlicerConfig {
+            layer_height: 0.2,
+            ..Default::default()
+        };
         let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CALIBRATION_CUBE.bytes).unwrap();
         slice_mesh(mesh, &config);
     }
+
+    #[test]
+    fn slice_cube_adaptive() {
+        let config = SlicerConfig {
+            adaptive_layers: AdaptiveLayerConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let sliced = slice_mesh(mesh, &config);
+
+        // The cube only has vertical walls, so every layer is as thick as
+        // allowed, apart from the top one which ends at the top of the cube.
+        assert_eq!(sliced.layers().len(), 67);
+        assert_float_eq!(sliced.layers()[10].height(), 0.3, abs <= 0.0001);
+        assert_float_eq!(sliced.layer_z()[66], 20.0, abs <= 0.0001);
+        assert_float_eq!(sliced.layers()[66].height(), 0.2, abs <= 0.0001);
+    }
+
+    #[test]
+    fn slice_cube_layer_z() {
+        let mesh = || mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let sliced = slice_mesh(mesh(), &SlicerConfig::default());
+
+        // A 20mm cube has 100 layers, with no empty layer on the bed.
+        let layers = sliced.layers();
+        assert_eq!(layers.len(), 100);
+        assert_float_eq!(layers[0].z(), 0.2, abs <= 0.0001);
+        assert_float_eq!(layers[0].height(), 0.2, abs <= 0.0001);
+        assert_float_eq!(layers[0].slice_z(), 0.1, abs <= 0.0001);
+        assert_float_eq!(layers[99].z(), 20.0, abs <= 0.0001);
+        assert!(layers.iter().all(|l| l.region().area() > 399.0));
+
+        let config = SlicerConfig {
+            first_layer_height: Some(0.3),
+            slicing_plane: SlicingPlane::Top,
+            ..Default::default()
+        };
+        let sliced = slice_mesh(mesh(), &config);
+        let layers = sliced.layers();
+        assert_float_eq!(layers[0].height(), 0.3, abs <= 0.0001);
+        assert_float_eq!(layers[0].slice_z(), 0.3, abs <= 0.0001);
+        assert_float_eq!(layers[1].z(), 0.5, abs <= 0.0001);
+        assert_float_eq!(layers[1].height(), 0.2, abs <= 0.0001);
+    }
+
+    #[test]
+    fn sweep_finds_crossing_triangles() {
+        let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let triangles = mesh.triangles().collect::<Vec<_>>();
+
+        let crossing = sweep_triangles(&triangles, &[0.1, 19.9, 25.0]);
+
+        // Only the 8 triangles of the cube's sides cross the planes inside it.
+        assert_eq!(crossing[0].len(), 8);
+        assert_eq!(crossing[1].len(), 8);
+        assert!(crossing[2].is_empty());
+    }
 }
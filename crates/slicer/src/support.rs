@@ -0,0 +1,451 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::config::{SlicerConfig, SupportStyle};
+use crate::infill::line_spacing;
+use crate::overhang::OverhangType;
+use crate::region::Region;
+use crate::slicer::SlicedLayer;
+
+/// The number of sides used to approximate the cross section of a branch.
+const BRANCH_SEGMENTS: usize = 16;
+/// How much a branch's radius grows for every mm it descends.
+const BRANCH_GROWTH: f32 = 0.05;
+/// Branches never grow past this radius, in mm.
+const MAX_BRANCH_RADIUS: f32 = 5.0;
+/// The number of directions tried when pushing a branch away from the model.
+const AVOIDANCE_DIRECTIONS: usize = 16;
+
+/// The support material for a single layer.
+#[derive(Clone, Debug, Default)]
+pub struct SupportLayer {
+    /// The body of the support, printed at the support density.
+    pub base: Region,
+    /// The dense layers directly under the model that it rests on.
+    pub interface: Region,
+}
+
+impl SupportLayer {
+    pub fn is_empty(&self) -> bool {
+        self.base.is_empty() && self.interface.is_empty()
+    }
+}
+
+/// Finds the area of each layer that needs support.
+///
+/// Areas that step out from the layer below further than the support
+/// overhang angle allows need support, except for the bridges found while
+/// analysing the layers, which can be printed in mid-air.
+fn supported_areas(layers: &[SlicedLayer], config: &SlicerConfig) -> Vec<Region> {
+    let width = config.extrusion_width;
+    let step = config.layer_height * config.support.overhang_angle.to_radians().tan();
+    let mut areas = vec![Region::new()];
+    for pair in layers.windows(2) {
+        let (below, layer) = (&pair[0], &pair[1]);
+        let bridges = Region::union_all(
+            layer
+                .overhangs()
+                .iter()
+                .filter(|o| matches!(o.kind, OverhangType::Bridge { .. }))
+                .map(|o| &o.region),
+        );
+        let area = layer
+            .region()
+            .difference(&below.region().offset(step))
+            .difference(&bridges);
+        // Drop slivers too thin to be worth supporting.
+        let area = Region::union_all(
+            area.islands()
+                .filter(|island| !island.offset(-width / 2.0).is_empty())
+                .collect::<Vec<_>>()
+                .iter(),
+        );
+        areas.push(area);
+    }
+    areas.truncate(layers.len());
+    areas
+}
+
+/// A single branch of a tree support, at one layer.
+#[derive(Clone, Copy, Debug)]
+struct Branch {
+    position: Vector2<f32>,
+    radius: f32,
+}
+
+/// Grows tree branches from the bottom of each contact area down to the bed
+/// or the model, returning the area covered by branches on every layer.
+///
+/// `contacts[i]` is the area that branches starting on layer `i` need to
+/// hold up, and `obstacles[i]` is the area branches must keep out of.
+fn grow_trees(
+    contacts: &[Region],
+    obstacles: &[Region],
+    models: &[Region],
+    config: &SlicerConfig,
+) -> Vec<Region> {
+    let width = config.extrusion_width;
+    let tip_radius = width;
+    let max_move = config.layer_height * config.support.overhang_angle.to_radians().tan();
+    let tip_spacing = line_spacing(config.support.density, width, 1)
+        .unwrap_or(f32::MAX)
+        .max(2.0 * tip_radius);
+
+    let mut branches: Vec<Branch> = Vec::new();
+    let mut trees = vec![Region::new(); contacts.len()];
+    for i in (0..contacts.len()).rev() {
+        // Start new branches on a grid over each new contact area, with at
+        // least one branch per island.
+        for island in contacts[i].islands() {
+            let tips = grid_points(&island, tip_spacing);
+            let tips = if tips.is_empty() {
+                island.paths()[0].points()[..1].to_vec()
+            } else {
+                tips
+            };
+            branches.extend(tips.into_iter().map(|position| Branch {
+                position,
+                radius: tip_radius,
+            }));
+        }
+
+        // Draw branches towards their nearest neighbour, merging them once
+        // they meet.
+        let mut moved: Vec<Branch> = Vec::with_capacity(branches.len());
+        let mut merged = vec![false; branches.len()];
+        for a in 0..branches.len() {
+            if merged[a] {
+                continue;
+            }
+            let branch = branches[a];
+            let nearest = (0..branches.len())
+                .filter(|&b| b != a && !merged[b])
+                .map(|b| (b, (branches[b].position - branch.position).magnitude()))
+                .min_by(|x, y| x.1.total_cmp(&y.1));
+            match nearest {
+                Some((b, distance)) if distance <= max_move => {
+                    merged[b] = true;
+                    let other = branches[b];
+                    moved.push(Branch {
+                        position: (branch.position + other.position) / 2.0,
+                        radius: (branch.radius.powi(2) + other.radius.powi(2))
+                            .sqrt()
+                            .min(MAX_BRANCH_RADIUS),
+                    });
+                }
+                Some((b, distance)) if distance <= 4.0 * MAX_BRANCH_RADIUS => {
+                    let direction = (branches[b].position - branch.position) / distance;
+                    moved.push(Branch {
+                        position: branch.position + direction * max_move,
+                        ..branch
+                    });
+                }
+                _ => moved.push(branch),
+            }
+        }
+
+        // Keep branches away from the model. Branches that end up over the
+        // model rest on it and stop. Branches that can't get clear carry on
+        // straight down, trimmed to keep out of the model, until they reach
+        // it or the bed.
+        branches = moved
+            .into_iter()
+            .filter_map(|mut branch| {
+                branch.radius =
+                    (branch.radius + config.layer_height * BRANCH_GROWTH).min(MAX_BRANCH_RADIUS);
+                if models[i].contains_point(branch.position) {
+                    return None;
+                }
+                Some(avoid(branch, &obstacles[i], max_move))
+            })
+            .collect();
+
+        let circles = branches
+            .iter()
+            .map(|b| Region::circle(b.position, b.radius, BRANCH_SEGMENTS))
+            .collect::<Vec<_>>();
+        trees[i] = Region::union_all(&circles).difference(&obstacles[i]);
+    }
+    trees
+}
+
+/// Moves `branch` out of `obstacle` if it overlaps it. The branch is left
+/// where it is if it can't be moved clear within `max_move`.
+fn avoid(branch: Branch, obstacle: &Region, max_move: f32) -> Branch {
+    let clear = |position: Vector2<f32>| {
+        Region::circle(position, branch.radius, BRANCH_SEGMENTS)
+            .intersection(obstacle)
+            .is_empty()
+    };
+    if clear(branch.position) {
+        return branch;
+    }
+    (0..AVOIDANCE_DIRECTIONS)
+        .map(|d| {
+            let angle = d as f32 * std::f32::consts::TAU / AVOIDANCE_DIRECTIONS as f32;
+            branch.position + Vector2::new(angle.cos(), angle.sin()) * max_move
+        })
+        .find(|&p| clear(p))
+        .map_or(branch, |position| Branch { position, ..branch })
+}
+
+/// Returns the points of a grid with the given spacing that fall inside
+/// `region`.
+fn grid_points(region: &Region, spacing: f32) -> Vec<Vector2<f32>> {
+    let Some((min, max)) = region.bounding_box() else {
+        return Vec::new();
+    };
+    let mut points = Vec::new();
+    let mut y = (min.y / spacing).ceil() * spacing;
+    while y <= max.y {
+        let mut x = (min.x / spacing).ceil() * spacing;
+        while x <= max.x {
+            let p = Vector2::new(x, y);
+            if region.contains_point(p) {
+                points.push(p);
+            }
+            x += spacing;
+        }
+        y += spacing;
+    }
+    points
+}
+
+/// Generates support material for every layer of a sliced model.
+///
+/// Support is placed under areas that overhang by more than
+/// `config.support.overhang_angle`, leaving `z_gap_layers` empty layers
+/// between the support and the model above it, and `xy_gap` of clearance
+/// around the model on every layer. The top `interface_layers` of each
+/// support are returned separately, so they can be printed densely.
+pub fn generate_supports(layers: &[SlicedLayer], config: &SlicerConfig) -> Vec<SupportLayer> {
+    let support = &config.support;
+    let mut supports = vec![SupportLayer::default(); layers.len()];
+    if !support.enabled || layers.is_empty() {
+        return supports;
+    }
+    let needed = supported_areas(layers, config);
+    let models = layers
+        .iter()
+        .map(|l| l.region().clone())
+        .collect::<Vec<_>>();
+    let obstacles = models
+        .iter()
+        .map(|m| m.offset(support.xy_gap))
+        .collect::<Vec<_>>();
+
+    // The area that needs support `offset` layers below the top of the
+    // support, ie: the area of layer `i + 1 + z_gap_layers + offset`.
+    let needed_at = |i: usize, offset: usize| {
+        needed
+            .get(i + 1 + support.z_gap_layers + offset)
+            .cloned()
+            .unwrap_or_default()
+    };
+    // The area of layer `i` that is within `interface_layers` of the model
+    // above it.
+    let roof = |i: usize| {
+        Region::union_all(
+            (0..support.interface_layers)
+                .map(|k| needed_at(i, k))
+                .collect::<Vec<_>>()
+                .iter(),
+        )
+    };
+
+    match support.style {
+        SupportStyle::Grid => {
+            // Project each overhang straight down until it reaches the bed
+            // or the model.
+            let mut column = Region::new();
+            for i in (0..layers.len()).rev() {
+                column = column.union(&needed_at(i, 0)).difference(&obstacles[i]);
+                let interface = column.intersection(&roof(i));
+                supports[i] = SupportLayer {
+                    base: column.difference(&interface),
+                    interface,
+                };
+            }
+        }
+        SupportStyle::Tree => {
+            // Trees start under the interface layers, so a solid roof still
+            // spans the whole overhang.
+            let contacts = (0..layers.len())
+                .map(|i| needed_at(i, support.interface_layers))
+                .collect::<Vec<_>>();
+            let trees = grow_trees(&contacts, &obstacles, &models, config);
+            for (i, base) in trees.into_iter().enumerate() {
+                let interface = roof(i).difference(&obstacles[i]);
+                supports[i] = SupportLayer {
+                    base: base.difference(&interface),
+                    interface,
+                };
+            }
+        }
+    }
+    supports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SupportConfig;
+    use crate::contour::{ClosedPath, Contour};
+    use crate::progress::Progress;
+    use crate::slicer::analyze_layers;
+    use crate::test_util::rect_path;
+    use float_eq::assert_float_eq;
+
+    fn layer(paths: Vec<ClosedPath>) -> SlicedLayer {
+        let mut contour = Contour::new();
+        for p in paths {
+            contour.add_path(p);
+        }
+        SlicedLayer::new(contour)
+    }
+
+    /// A 'T' shape: a 4mm wide post that is 10 layers tall, with a 20mm wide
+    /// slab on top of it for another 3 layers. The overhangs of each layer
+    /// have been found, but there is no support yet.
+    fn t_shape() -> Vec<SlicedLayer> {
+        let mut layers = (0..10)
+            .map(|_| layer(vec![rect_path(8.0, 8.0, 12.0, 12.0)]))
+            .collect::<Vec<_>>();
+        layers.extend((0..3).map(|_| layer(vec![rect_path(0.0, 8.0, 20.0, 12.0)])));
+        analyze_layers(&mut layers, &SlicerConfig::default(), Progress::default()).unwrap();
+        layers
+    }
+
+    fn config(style: SupportStyle) -> SlicerConfig {
+        SlicerConfig {
+            support: SupportConfig {
+                enabled: true,
+                style,
+                z_gap_layers: 1,
+                interface_layers: 2,
+                xy_gap: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let supports = generate_supports(&t_shape(), &SlicerConfig::default());
+        assert!(supports.iter().all(SupportLayer::is_empty));
+    }
+
+    #[test]
+    fn grid_support_under_slab() {
+        let layers = t_shape();
+
+        let supports = generate_supports(&layers, &config(SupportStyle::Grid));
+
+        // The slab starts on layer 10, so with a 1 layer gap the support
+        // ends on layer 8, and the 2 layers below it are interface.
+        assert!(supports[9].is_empty());
+        for support in &supports[7..=8] {
+            assert!(!support.interface.is_empty());
+            assert!(support.base.is_empty());
+        }
+        for support in &supports[0..7] {
+            assert!(support.interface.is_empty());
+            assert!(!support.base.is_empty());
+            // Both sides of the post are supported.
+            assert_eq!(support.base.islands().count(), 2);
+        }
+        // Support keeps clear of the post.
+        for (support, layer) in supports.iter().zip(&layers) {
+            let clearance = layer.region().offset(0.49);
+            assert!(support.base.intersection(&clearance).area() < 0.001);
+            assert!(support.interface.intersection(&clearance).area() < 0.001);
+        }
+    }
+
+    #[test]
+    fn tree_support_reaches_bed() {
+        let layers = t_shape();
+
+        let supports = generate_supports(&layers, &config(SupportStyle::Tree));
+
+        assert!(!supports[8].interface.is_empty());
+        assert!(!supports[0].base.is_empty());
+        // Trees are made of thin branches, so they use less material than
+        // solid columns.
+        let grid = generate_supports(&layers, &config(SupportStyle::Grid));
+        assert!(supports[0].base.area() < grid[0].base.area());
+        for (support, layer) in supports.iter().zip(&layers) {
+            let clearance = layer.region().offset(0.49);
+            assert!(support.base.intersection(&clearance).area() < 0.001);
+        }
+    }
+
+    #[test]
+    fn branches_merge() {
+        let contacts = vec![
+            Region::new(),
+            Region::new(),
+            Region::new(),
+            Region::new(),
+            Region::from_paths(&[rect_path(0.0, 0.0, 0.2, 0.2), rect_path(0.6, 0.0, 0.8, 0.2)]),
+        ];
+        let empty = vec![Region::new(); contacts.len()];
+
+        let trees = grow_trees(&contacts, &empty, &empty, &config(SupportStyle::Tree));
+
+        assert_eq!(trees[0].islands().count(), 1);
+    }
+
+    #[test]
+    fn trapped_branch_reaches_bed() {
+        // A tube around a single contact point, too narrow for the branch
+        // and too wide to get out of.
+        let config = config(SupportStyle::Tree);
+        let center = Vector2::new(0.0, 0.0);
+        let tube = Region::circle(center, 10.0, 32).difference(&Region::circle(
+            center,
+            0.75 * config.extrusion_width,
+            32,
+        ));
+        let mut contacts = vec![Region::new(); 5];
+        contacts[4] = Region::circle(center, 0.1, 8);
+        let tubes = vec![tube; 5];
+
+        let trees = grow_trees(&contacts, &tubes, &tubes, &config);
+
+        for tree in &trees {
+            assert!(!tree.is_empty());
+            assert!(tree.intersection(&tubes[0]).area() < 0.001);
+        }
+    }
+
+    #[test]
+    fn overhang_angle_is_used() {
+        // A slope that steps out 1mm on every 0.2mm layer.
+        let mut layers = (0..10)
+            .map(|i| layer(vec![rect_path(0.0, 0.0, 10.0 + i as f32, 10.0)]))
+            .collect::<Vec<_>>();
+        analyze_layers(&mut layers, &SlicerConfig::default(), Progress::default()).unwrap();
+        let with_angle = |angle| SlicerConfig {
+            support: SupportConfig {
+                overhang_angle: angle,
+                ..config(SupportStyle::Grid).support
+            },
+            ..Default::default()
+        };
+
+        let under_45 = supported_areas(&layers, &with_angle(45.0));
+        let under_10 = supported_areas(&layers, &with_angle(10.0));
+
+        // Only the part past the angle from the layer below is supported,
+        // even where that's more than the overhang that was detected.
+        for (a, b) in under_45[1..].iter().zip(&under_10[1..]) {
+            assert_float_eq!(a.area(), 8.0, abs <= 0.01);
+            assert_float_eq!(
+                b.area(),
+                10.0 * (1.0 - 0.2 * 10f32.to_radians().tan()),
+                abs <= 0.01
+            );
+        }
+    }
+}
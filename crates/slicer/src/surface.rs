@@ -0,0 +1,174 @@
+use crate::config::SlicerConfig;
+use crate::region::Region;
+
+/// How an area of a layer should be filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceType {
+    /// Solid area that is exposed to air within `top_layers` layers above.
+    Top,
+    /// Solid area that is exposed to air within `bottom_layers` layers
+    /// below, including the layers resting on the bed.
+    Bottom,
+    /// Interior area that is covered above and below, and can use sparse
+    /// infill.
+    Sparse,
+}
+
+impl SurfaceType {
+    /// Returns true if this surface needs 100% infill.
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, SurfaceType::Sparse)
+    }
+}
+
+/// An area of a layer with a single fill type.
+#[derive(Clone, Debug)]
+pub struct Surface {
+    pub kind: SurfaceType,
+    pub region: Region,
+}
+
+/// Returns the area that is present in every layer of `layers`.
+///
+/// Layers outside of the model are treated as empty, so if `layers` runs off
+/// either end of the model the result is empty.
+fn covered<'a>(mut layers: impl Iterator<Item = Option<&'a Region>>) -> Region {
+    let Some(Some(first)) = layers.next() else {
+        return Region::new();
+    };
+    let mut covered = first.clone();
+    for layer in layers {
+        match layer {
+            Some(layer) => covered = covered.intersection(layer),
+            None => return Region::new(),
+        }
+        if covered.is_empty() {
+            break;
+        }
+    }
+    covered
+}
+
+/// Divides each layer into top, bottom and sparse surfaces.
+///
+/// `layers` holds the filled area of every layer, in order from the bed up.
+/// A part of a layer that is not also present in every one of the
+/// `config.bottom_layers` layers below it is bottom surface, and likewise for
+/// the `config.top_layers` layers above it and top surface. Area that is
+/// both is treated as bottom surface.
+pub fn classify_surfaces(layers: &[Region], config: &SlicerConfig) -> Vec<Vec<Surface>> {
+    let layer_at = |i: isize| {
+        if i >= 0 && (i as usize) < layers.len() {
+            Some(&layers[i as usize])
+        } else {
+            None
+        }
+    };
+    layers
+        .iter()
+        .enumerate()
+        .map(|(i, region)| {
+            if region.is_empty() {
+                return Vec::new();
+            }
+            let i = i as isize;
+            let below = (1..=config.bottom_layers as isize).map(|k| layer_at(i - k));
+            let above = (1..=config.top_layers as isize).map(|k| layer_at(i + k));
+
+            let bottom = if config.bottom_layers == 0 {
+                Region::new()
+            } else {
+                region.difference(&covered(below))
+            };
+            let top = if config.top_layers == 0 {
+                Region::new()
+            } else {
+                region.difference(&covered(above)).difference(&bottom)
+            };
+            let sparse = region.difference(&bottom).difference(&top);
+
+            [
+                (SurfaceType::Bottom, bottom),
+                (SurfaceType::Top, top),
+                (SurfaceType::Sparse, sparse),
+            ]
+            .into_iter()
+            .filter(|(_, region)| !region.is_empty())
+            .map(|(kind, region)| Surface { kind, region })
+            .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rect;
+    use float_eq::assert_float_eq;
+
+    fn area_of(surfaces: &[Surface], kind: SurfaceType) -> f32 {
+        surfaces
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.region.area())
+            .sum()
+    }
+
+    fn config(top_layers: usize, bottom_layers: usize) -> SlicerConfig {
+        SlicerConfig {
+            top_layers,
+            bottom_layers,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn column_has_solid_ends() {
+        let layers = vec![rect(0.0, 0.0, 10.0, 10.0); 10];
+
+        let surfaces = classify_surfaces(&layers, &config(3, 2));
+
+        for (i, layer) in surfaces.iter().enumerate() {
+            let expected = match i {
+                0 | 1 => SurfaceType::Bottom,
+                7..=9 => SurfaceType::Top,
+                _ => SurfaceType::Sparse,
+            };
+            assert_eq!(layer.len(), 1, "layer {}", i);
+            assert_eq!(layer[0].kind, expected, "layer {}", i);
+            assert_float_eq!(layer[0].region.area(), 100.0, abs <= 0.01);
+        }
+    }
+
+    #[test]
+    fn ledge_is_top_surface() {
+        // A 10x10 base, with a 5x10 tower on top of it. The half of the base
+        // that isn't covered by the tower needs to be solid below the top.
+        let mut layers = vec![rect(0.0, 0.0, 10.0, 10.0); 5];
+        layers.extend(vec![rect(0.0, 0.0, 5.0, 10.0); 5]);
+
+        let surfaces = classify_surfaces(&layers, &config(2, 1));
+
+        // Layers 3 and 4 are within 2 layers of the ledge.
+        for layer in &surfaces[3..5] {
+            assert_float_eq!(area_of(layer, SurfaceType::Top), 50.0, abs <= 0.01);
+            assert_float_eq!(area_of(layer, SurfaceType::Sparse), 50.0, abs <= 0.01);
+        }
+        assert_float_eq!(area_of(&surfaces[2], SurfaceType::Top), 0.0, abs <= 0.01);
+        assert_float_eq!(
+            area_of(&surfaces[2], SurfaceType::Sparse),
+            100.0,
+            abs <= 0.01
+        );
+    }
+
+    #[test]
+    fn thin_layers_prefer_bottom() {
+        let layers = vec![rect(0.0, 0.0, 10.0, 10.0); 2];
+        let surfaces = classify_surfaces(&layers, &config(3, 3));
+        for layer in surfaces {
+            assert_eq!(area_of(&layer, SurfaceType::Top), 0.0);
+            assert_float_eq!(area_of(&layer, SurfaceType::Bottom), 100.0, abs <= 0.01);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Shapes shared by the unit tests.
+
+use crate::contour::ClosedPath;
+use crate::region::Region;
+
+/// Returns the axis-aligned rectangle from `(x0, y0)` to `(x1, y1)`,
+/// wound counter-clockwise.
+pub(crate) fn rect_path(x0: f32, y0: f32, x1: f32, y1: f32) -> ClosedPath {
+    let mut path = ClosedPath::new();
+    path.add_point(x0, y0);
+    path.add_point(x1, y0);
+    path.add_point(x1, y1);
+    path.add_point(x0, y1);
+    path
+}
+
+/// Returns the region covered by `rect_path(x0, y0, x1, y1)`.
+pub(crate) fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Region {
+    Region::from_paths(&[rect_path(x0, y0, x1, y1)])
+}
+
+/// Returns the `size` by `size` square with a corner at the origin.
+pub(crate) fn square(size: f32) -> Region {
+    rect(0.0, 0.0, size, size)
+}
+
+/// Returns the closed path through `points`.
+pub(crate) fn polygon(points: &[(f32, f32)]) -> ClosedPath {
+    let mut path = ClosedPath::new();
+    for &(x, y) in points {
+        path.add_point(x, y);
+    }
+    path
+}
@@ -0,0 +1,723 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use cgmath::Vector2;
+
+use crate::adhesion::{generate_brim, generate_raft, generate_skirt};
+use crate::config::{InfillType, SlicerConfig};
+use crate::contour::Polyline;
+use crate::infill::{connect_polylines, generate_infill, hatch, infill_pattern, line_spacing};
+use crate::ordering::order_layer;
+use crate::overhang::OverhangType;
+use crate::perimeter::generate_perimeters;
+use crate::plate::{check_plate, PlateError, SlicedObject};
+use crate::profile::ConfigError;
+use crate::progress::{Cancelled, Progress, Stage};
+use crate::region::Region;
+use crate::seam::place_seam;
+use crate::slicer::{SlicedLayer, SlicedMesh};
+use crate::surface::SurfaceType;
+use crate::vase::{spiralize, vase_layer};
+
+/// What part of the print an extrusion belongs to.
+///
+/// Features are printed with different settings, eg: speeds, and are kept
+/// separate so that later stages can tell them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeatureType {
+    /// The outermost perimeter, which forms the visible surface of the model.
+    ExternalPerimeter,
+    /// Perimeters inside of the external perimeter.
+    Perimeter,
+    /// 100% infill under top surfaces and over bottom surfaces.
+    SolidInfill,
+    /// Infill for the interior of the model.
+    SparseInfill,
+    /// Lines printed in mid-air across a gap.
+    Bridge,
+    /// The body of the support material.
+    Support,
+    /// The dense top layers of the support material, that the model rests on.
+    SupportInterface,
+    /// Loops around the first layer that prime the nozzle.
+    Skirt,
+    /// Loops attached to the outside of the first layer that help it stick to
+    /// the bed.
+    Brim,
+    /// The widely spaced bottom layers of a raft.
+    RaftBase,
+    /// The solid top layers of a raft, that the model rests on.
+    RaftInterface,
+}
+
+impl FeatureType {
+    /// The name of the feature, as written in `;TYPE:` comments.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeatureType::ExternalPerimeter => "ExternalPerimeter",
+            FeatureType::Perimeter => "Perimeter",
+            FeatureType::SolidInfill => "SolidInfill",
+            FeatureType::SparseInfill => "SparseInfill",
+            FeatureType::Bridge => "Bridge",
+            FeatureType::Support => "Support",
+            FeatureType::SupportInterface => "SupportInterface",
+            FeatureType::Skirt => "Skirt",
+            FeatureType::Brim => "Brim",
+            FeatureType::RaftBase => "RaftBase",
+            FeatureType::RaftInterface => "RaftInterface",
+        }
+    }
+}
+
+impl fmt::Display for FeatureType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single continuous extrusion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtrusionPath {
+    pub feature: FeatureType,
+    /// The points the nozzle passes through. Closed loops end on the point
+    /// they started from.
+    pub path: Polyline,
+    /// The height of the nozzle at each point of `path`, for paths that climb
+    /// as they're printed, like the spiral of vase mode. `None` if the whole
+    /// path is printed at the height of its layer.
+    pub z: Option<Vec<f32>>,
+    /// True if the path runs over an overhang, and needs extra cooling.
+    pub overhang: bool,
+}
+
+impl ExtrusionPath {
+    pub fn new(feature: FeatureType, path: Polyline) -> Self {
+        Self {
+            feature,
+            path,
+            z: None,
+            overhang: false,
+        }
+    }
+
+    /// Returns true if this path ends where it started.
+    pub fn is_closed(&self) -> bool {
+        self.path.points().len() > 2 && self.path.first() == self.path.last()
+    }
+}
+
+/// All of the extrusions for one layer of the print.
+#[derive(Clone, Debug, Default)]
+pub struct ToolpathLayer {
+    pub index: usize,
+    /// The height of the nozzle while printing this layer, in mm.
+    pub z: f32,
+    /// The thickness of this layer, in mm.
+    pub height: f32,
+    /// The area of the model on this layer. Travels that stay inside it
+    /// don't need to retract.
+    pub region: Region,
+    pub paths: Vec<ExtrusionPath>,
+}
+
+impl ToolpathLayer {
+    /// Returns the paths in this layer of type `feature`.
+    pub fn paths_of(&self, feature: FeatureType) -> impl Iterator<Item = &ExtrusionPath> + '_ {
+        self.paths.iter().filter(move |p| p.feature == feature)
+    }
+}
+
+/// Generates lines that fill `region` at a single angle.
+pub(crate) fn fill_lines(region: &Region, angle: f32, spacing: f32, width: f32) -> Vec<Polyline> {
+    let lines = hatch(region, angle, spacing)
+        .into_iter()
+        .map(|s| Polyline::from_points(vec![s.start, s.end]))
+        .collect();
+    connect_polylines(lines, region, spacing * 2.0, width / 2.0)
+}
+
+/// Generates the extrusions for a single sliced layer.
+///
+/// `seams` holds the seams of the perimeters on the layer below, and is
+/// replaced with the seams of this layer.
+fn layer_toolpaths(
+    index: usize,
+    layer: &SlicedLayer,
+    config: &SlicerConfig,
+    seams: &mut Vec<Vector2<f32>>,
+) -> ToolpathLayer {
+    let width = config.extrusion_width;
+    let z = layer.z();
+    let mut paths = Vec::new();
+    let mut add = |feature, polylines: Vec<Polyline>| {
+        paths.extend(
+            polylines
+                .into_iter()
+                .filter(|p| p.points().len() > 1)
+                .map(|p| ExtrusionPath::new(feature, p)),
+        );
+    };
+
+    let perimeters = generate_perimeters(layer.region(), config);
+    let previous_seams = std::mem::take(seams);
+    for (i, shell) in perimeters.shells.iter().enumerate() {
+        let feature = if i == 0 {
+            FeatureType::ExternalPerimeter
+        } else {
+            FeatureType::Perimeter
+        };
+        let loops = shell
+            .paths()
+            .into_iter()
+            .enumerate()
+            .map(|(j, mut path)| {
+                let seed = ((index as u64) << 32) ^ ((i as u64) << 16) ^ j as u64;
+                place_seam(&mut path, config.seam_position, &previous_seams, seed);
+                let mut points = path.points().to_vec();
+                seams.push(points[0]);
+                points.push(points[0]);
+                Polyline::from_points(points)
+            })
+            .collect();
+        add(feature, loops);
+    }
+
+    let infill_region = &perimeters.infill_region;
+    let mut bridged = Region::new();
+    for overhang in layer.overhangs() {
+        if let OverhangType::Bridge { angle } = overhang.kind {
+            let area = overhang.region.intersection(infill_region);
+            add(FeatureType::Bridge, fill_lines(&area, angle, width, width));
+            bridged = bridged.union(&area);
+        }
+    }
+
+    let solid = layer
+        .surface_region(SurfaceType::Top)
+        .union(&layer.surface_region(SurfaceType::Bottom))
+        .intersection(infill_region)
+        .difference(&bridged);
+    if let Some(pattern) = infill_pattern(InfillType::Rectilinear, 100.0, width) {
+        let lines = pattern.generate(&solid, index, z);
+        add(
+            FeatureType::SolidInfill,
+            connect_polylines(lines, &solid, width * 2.0, width / 2.0),
+        );
+    }
+
+    let sparse = layer
+        .surface_region(SurfaceType::Sparse)
+        .intersection(infill_region)
+        .difference(&bridged);
+    add(
+        FeatureType::SparseInfill,
+        generate_infill(&sparse, index, z, config),
+    );
+
+    let support = layer.support();
+    if let Some(spacing) = line_spacing(config.support.density, width, 1) {
+        add(
+            FeatureType::Support,
+            fill_lines(&support.base, 0.0, spacing, width),
+        );
+    }
+    // Cross the interface lines over each other on alternate layers, so they
+    // make a mesh for the model to rest on.
+    let interface_angle = if index.is_multiple_of(2) {
+        0.0
+    } else {
+        PI / 2.0
+    };
+    add(
+        FeatureType::SupportInterface,
+        fill_lines(&support.interface, interface_angle, width, width),
+    );
+
+    // Perimeters that run over an overhang are cooled as hard as bridges.
+    let overhangs = Region::union_all(
+        layer
+            .overhangs()
+            .iter()
+            .filter(|o| o.kind == OverhangType::Overhang)
+            .map(|o| &o.region),
+    );
+    if !overhangs.is_empty() {
+        for path in &mut paths {
+            if matches!(
+                path.feature,
+                FeatureType::ExternalPerimeter | FeatureType::Perimeter
+            ) {
+                path.overhang = !overhangs
+                    .clip_polylines(std::slice::from_ref(&path.path))
+                    .is_empty();
+            }
+        }
+    }
+
+    ToolpathLayer {
+        index,
+        z,
+        height: layer.height(),
+        region: layer.region().clone(),
+        paths,
+    }
+}
+
+/// Generates the extrusions for every layer of a sliced mesh.
+///
+/// Each layer is made up of its perimeters, solid and sparse infill,
+/// bridges and support material. Paths are ordered to keep travel moves
+/// short, finishing each island before moving on to the next.
+///
+/// If a raft is enabled its layers are printed first, and the model is
+/// lifted to sit on top of it. The skirt, and the brim if there's no raft,
+/// are printed at the start of the first layer.
+///
+/// In vase mode, the layers above the first `bottom_layers` are printed as
+/// one continuous spiral around the outside of the model.
+pub fn generate_toolpaths(mesh: &SlicedMesh, config: &SlicerConfig) -> Vec<ToolpathLayer> {
+    // Without a cancellation token, this can't fail.
+    generate_toolpaths_with_progress(mesh, config, Progress::default()).unwrap()
+}
+
+/// Generates toolpaths like `generate_toolpaths`, reporting progress to
+/// `progress` as each layer is finished.
+///
+/// Returns `Err(Cancelled)` if `progress`'s cancellation token is cancelled
+/// before all of the layers are generated.
+pub fn generate_toolpaths_with_progress(
+    mesh: &SlicedMesh,
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<Vec<ToolpathLayer>, Cancelled> {
+    objects_toolpaths(&[(mesh, config)], config, progress)
+}
+
+/// Generates the extrusions for every object on a plate, like
+/// `generate_toolpaths`.
+///
+/// Each object's paths are generated with its own settings, then combined
+/// into one layer for each height. Within a layer, each object is finished
+/// before moving on to the next, in the order they're listed. Adhesion and
+/// rafts use `config`, the settings of the whole plate.
+///
+/// Returns an error if the objects can't be printed together, as checked by
+/// `check_plate`.
+pub fn generate_plate_toolpaths(
+    objects: &[SlicedObject],
+    config: &SlicerConfig,
+) -> Result<Vec<ToolpathLayer>, ConfigError> {
+    check_plate(config, objects.len())?;
+    // With the plate checked and no cancellation token, this can't fail.
+    Ok(generate_plate_toolpaths_with_progress(objects, config, Progress::default()).unwrap())
+}
+
+/// Generates toolpaths like `generate_plate_toolpaths`, reporting progress
+/// to `progress` as each layer of each object is finished.
+///
+/// Returns `Err(PlateError::Cancelled)` if `progress`'s cancellation token
+/// is cancelled before all of the layers are generated.
+pub fn generate_plate_toolpaths_with_progress(
+    objects: &[SlicedObject],
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<Vec<ToolpathLayer>, PlateError> {
+    check_plate(config, objects.len())?;
+    let objects = objects
+        .iter()
+        .map(|o| (&o.mesh, &o.config))
+        .collect::<Vec<_>>();
+    Ok(objects_toolpaths(&objects, config, progress)?)
+}
+
+/// Generates the toolpaths for sliced meshes that share layers, each with its
+/// own settings.
+///
+/// In vase mode there's only one object, and the spiral starts above that
+/// object's bottom layers.
+fn objects_toolpaths(
+    objects: &[(&SlicedMesh, &SlicerConfig)],
+    config: &SlicerConfig,
+    progress: Progress,
+) -> Result<Vec<ToolpathLayer>, Cancelled> {
+    let total = objects.iter().map(|(mesh, _)| mesh.layers().len()).sum();
+    let mut done = 0;
+    // The spiral needs at least one normal layer to start from.
+    let spiral_start = |object_config: &SlicerConfig| object_config.bottom_layers.max(1);
+    let mut object_layers = Vec::with_capacity(objects.len());
+    for &(mesh, object_config) in objects {
+        let mut seams = Vec::new();
+        let mut layers = Vec::with_capacity(mesh.layers().len());
+        for (index, layer) in mesh.layers().iter().enumerate() {
+            progress.check()?;
+            if config.spiral_vase && index >= spiral_start(object_config) {
+                layers.push(vase_layer(index, layer, object_config.extrusion_width));
+            } else {
+                layers.push(layer_toolpaths(index, layer, object_config, &mut seams));
+            }
+            done += 1;
+            progress.report(Stage::Toolpaths, done, total);
+        }
+        object_layers.push(layers.into_iter());
+    }
+
+    // Everything printed on the bed by the first layer of every object.
+    let first_layer = objects
+        .iter()
+        .filter_map(|(mesh, _)| mesh.layers().first())
+        .map(|layer| {
+            let support = layer.support();
+            Region::union_all(&[
+                layer.region().clone(),
+                support.base.clone(),
+                support.interface.clone(),
+            ])
+        })
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or_default();
+    let mut raft = generate_raft(&first_layer, config);
+    let brim = if raft.is_empty() {
+        generate_brim(&first_layer, config)
+    } else {
+        Vec::new()
+    };
+    let raft_height = raft.iter().map(|l| l.height).sum::<f32>();
+    let raft_layers = raft.len();
+    let bed_layer = raft.first().map_or(first_layer, |l| l.region.clone());
+
+    // Each layer starts from where the layer below finished.
+    let mut position = None;
+    for layer in &mut raft {
+        position = order_layer(layer, position).or(position);
+    }
+    let mut layers = raft;
+    let layer_count = objects.iter().map(|(mesh, _)| mesh.layers().len()).max();
+    for _ in 0..layer_count.unwrap_or(0) {
+        let mut combined: Option<ToolpathLayer> = None;
+        for object in &mut object_layers {
+            let Some(mut layer) = object.next() else {
+                continue;
+            };
+            layer.index += raft_layers;
+            layer.z += raft_height;
+            // Ordering each object on its own keeps its paths together.
+            position = order_layer(&mut layer, position).or(position);
+            match &mut combined {
+                Some(combined) => {
+                    combined.region = combined.region.union(&layer.region);
+                    combined.paths.append(&mut layer.paths);
+                }
+                None => combined = Some(layer),
+            }
+        }
+        layers.extend(combined);
+    }
+
+    if let (true, Some((_, object_config))) = (config.spiral_vase, objects.first()) {
+        spiralize(&mut layers, raft_layers + spiral_start(object_config));
+    }
+
+    if let Some(layer) = layers.first_mut() {
+        // Keep the skirt clear of the brim as well as the model.
+        let printed = if brim.is_empty() {
+            bed_layer
+        } else {
+            bed_layer.offset(config.adhesion.brim_width)
+        };
+        let adhesion = generate_skirt(&printed, config)
+            .into_iter()
+            .rev()
+            .map(|p| ExtrusionPath::new(FeatureType::Skirt, p))
+            .chain(
+                brim.into_iter()
+                    .map(|p| ExtrusionPath::new(FeatureType::Brim, p)),
+            );
+        layer.paths.splice(0..0, adhesion);
+    }
+    Ok(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AdhesionConfig, InfillConfig, SupportConfig, SupportStyle};
+    use crate::contour::Contour;
+    use crate::plate::{slice_plate, PlateObject, Transform};
+    use crate::slicer::{analyze_layers, slice_mesh};
+    use crate::test_util::rect_path;
+    use float_eq::assert_float_eq;
+    use mandoline_mesh::DefaultMesh;
+    use mandoline_test_data::STL_CUBE;
+
+    fn slice_cube(config: &SlicerConfig) -> Vec<ToolpathLayer> {
+        let mesh = mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let sliced = slice_mesh(mesh, config);
+        generate_toolpaths(&sliced, config)
+    }
+
+    #[test]
+    fn cube_features() {
+        let config = SlicerConfig::default();
+
+        let layers = slice_cube(&config);
+
+        let middle = &layers[layers.len() / 2];
+        assert_eq!(middle.paths_of(FeatureType::ExternalPerimeter).count(), 1);
+        assert_eq!(middle.paths_of(FeatureType::Perimeter).count(), 1);
+        assert!(middle
+            .paths_of(FeatureType::ExternalPerimeter)
+            .all(ExtrusionPath::is_closed));
+        assert!(middle.paths_of(FeatureType::SparseInfill).count() > 0);
+        assert_eq!(middle.paths_of(FeatureType::SolidInfill).count(), 0);
+
+        let top = &layers[layers.len() - 2];
+        assert!(top.paths_of(FeatureType::SolidInfill).count() > 0);
+        assert_eq!(top.paths_of(FeatureType::SparseInfill).count(), 0);
+
+        // A cube sits flat on the bed and doesn't need support.
+        assert!(layers
+            .iter()
+            .all(|l| l.paths_of(FeatureType::Support).count() == 0));
+    }
+
+    #[test]
+    fn seams_line_up() {
+        let config = SlicerConfig::default();
+
+        let layers = slice_cube(&config);
+
+        let seams = layers
+            .iter()
+            .filter_map(|l| l.paths_of(FeatureType::ExternalPerimeter).next())
+            .map(|p| p.path.first().unwrap())
+            .collect::<Vec<_>>();
+        assert!(seams.len() > 10);
+        assert!(seams.iter().all(|&s| s == seams[1]), "{:?}", seams);
+    }
+
+    #[test]
+    fn support_is_separate_feature() {
+        // A 10x10 square floating one layer above the bed. The empty first
+        // layer is filled with support interface, and nothing else.
+        let square = || {
+            let mut contour = Contour::new();
+            contour.add_path(rect_path(0.0, 0.0, 10.0, 10.0));
+            SlicedLayer::new(contour)
+        };
+        let mut layers = vec![SlicedLayer::new(Contour::new())];
+        layers.extend((0..5).map(|_| square()));
+        let config = SlicerConfig {
+            support: SupportConfig {
+                enabled: true,
+                style: SupportStyle::Grid,
+                z_gap_layers: 0,
+                interface_layers: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        analyze_layers(&mut layers, &config, Progress::default()).unwrap();
+
+        let toolpaths = layer_toolpaths(0, &layers[0], &config, &mut Vec::new());
+
+        assert!(toolpaths.paths_of(FeatureType::SupportInterface).count() > 0);
+        assert!(toolpaths
+            .paths
+            .iter()
+            .all(|p| p.feature == FeatureType::SupportInterface));
+        let above = layer_toolpaths(1, &layers[1], &config, &mut Vec::new());
+        assert!(above.paths_of(FeatureType::SupportInterface).count() == 0);
+        assert!(above.paths_of(FeatureType::ExternalPerimeter).count() > 0);
+    }
+
+    #[test]
+    fn overhang_perimeters_are_marked() {
+        // A 10x10 square, with a 20x10 rectangle sticking out from it above.
+        let rectangle = |width: f32| {
+            let mut contour = Contour::new();
+            contour.add_path(rect_path(0.0, 0.0, width, 10.0));
+            SlicedLayer::new(contour)
+        };
+        let mut layers = vec![rectangle(10.0), rectangle(10.0), rectangle(20.0)];
+        let config = SlicerConfig::default();
+        analyze_layers(&mut layers, &config, Progress::default()).unwrap();
+
+        let below = layer_toolpaths(1, &layers[1], &config, &mut Vec::new());
+        let above = layer_toolpaths(2, &layers[2], &config, &mut Vec::new());
+
+        assert!(below.paths.iter().all(|p| !p.overhang));
+        let external = above
+            .paths_of(FeatureType::ExternalPerimeter)
+            .next()
+            .unwrap();
+        assert!(external.overhang);
+        assert!(above.paths.iter().filter(|p| p.overhang).all(|p| matches!(
+            p.feature,
+            FeatureType::ExternalPerimeter | FeatureType::Perimeter
+        )));
+    }
+
+    #[test]
+    fn plate_objects_printed_in_turn() {
+        let config = SlicerConfig::default();
+        let cube = || mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let object = |x: f32, density: f32| PlateObject {
+            mesh: cube(),
+            transform: Transform {
+                offset: (x, 0.0),
+                ..Default::default()
+            },
+            config: SlicerConfig {
+                infill: InfillConfig {
+                    density,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        };
+        let sliced = slice_plate(vec![object(0.0, 20.0), object(40.0, 60.0)], &config).unwrap();
+
+        let layers = generate_plate_toolpaths(&sliced, &config).unwrap();
+
+        assert_eq!(layers.len(), sliced[0].mesh.layers().len());
+        let infill_length = |layer: &ToolpathLayer, right: bool| {
+            layer
+                .paths_of(FeatureType::SparseInfill)
+                .filter(|p| (p.path.first().unwrap().x > 30.0) == right)
+                .map(|p| p.path.length())
+                .sum::<f32>()
+        };
+        for layer in &layers[1..] {
+            // All of the first cube is printed before the second.
+            let sides = layer
+                .paths
+                .iter()
+                .map(|p| p.path.first().unwrap().x > 30.0)
+                .collect::<Vec<_>>();
+            assert!(sides.windows(2).all(|w| w[0] <= w[1]), "{:?}", sides);
+            assert!(sides.contains(&true));
+        }
+        let middle = &layers[layers.len() / 2];
+        assert!(infill_length(middle, true) > infill_length(middle, false) * 2.0);
+    }
+
+    #[test]
+    fn adhesion_on_first_layer() {
+        let config = SlicerConfig {
+            adhesion: AdhesionConfig {
+                skirt_loops: 2,
+                brim_width: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let layers = slice_cube(&config);
+
+        let first = &layers[0];
+        assert_eq!(first.paths_of(FeatureType::Skirt).count(), 2);
+        assert!(first.paths_of(FeatureType::Brim).count() > 0);
+        // The skirt and brim are printed before the model.
+        assert_eq!(first.paths[0].feature, FeatureType::Skirt);
+        assert!(layers[1..].iter().all(|l| l
+            .paths
+            .iter()
+            .all(|p| p.feature != FeatureType::Skirt && p.feature != FeatureType::Brim)));
+    }
+
+    #[test]
+    fn raft_lifts_model() {
+        let plain = slice_cube(&SlicerConfig::default());
+        let config = SlicerConfig {
+            adhesion: AdhesionConfig {
+                raft_layers: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let layers = slice_cube(&config);
+
+        assert_eq!(layers.len(), plain.len() + 3);
+        assert!(layers[0].paths_of(FeatureType::RaftBase).count() > 0);
+        assert!(layers[2].paths_of(FeatureType::RaftInterface).count() > 0);
+        // No brim on a raft.
+        assert!(layers
+            .iter()
+            .all(|l| l.paths_of(FeatureType::Brim).count() == 0));
+        for (i, layer) in layers.iter().enumerate() {
+            assert_eq!(layer.index, i);
+        }
+        assert_float_eq!(layers[3].z, plain[0].z + 0.6, abs <= 0.0001);
+        assert!(layers[3]
+            .paths_of(FeatureType::ExternalPerimeter)
+            .next()
+            .is_some());
+    }
+
+    #[test]
+    fn vase_mode_spirals() {
+        let config = SlicerConfig {
+            spiral_vase: true,
+            ..Default::default()
+        };
+
+        let layers = slice_cube(&config);
+
+        // The bottom layers are printed as normal.
+        for layer in &layers[..config.bottom_layers] {
+            assert!(layer.paths_of(FeatureType::SolidInfill).count() > 0);
+            assert!(layer.paths.iter().all(|p| p.z.is_none()));
+        }
+        // Above them, each layer is a single climbing loop, starting where
+        // the one below ended.
+        let spiral = &layers[config.bottom_layers..];
+        for (below, layer) in layers[config.bottom_layers - 1..]
+            .iter()
+            .zip(spiral)
+            .skip(1)
+        {
+            assert_eq!(layer.paths.len(), 1);
+            let path = &layer.paths[0];
+            assert_eq!(path.feature, FeatureType::ExternalPerimeter);
+            assert_eq!(path.path.first(), below.paths[0].path.last());
+            let z = path.z.as_ref().unwrap();
+            assert_float_eq!(z[0], below.z, abs <= 1e-5);
+            assert_float_eq!(z[z.len() - 1], layer.z, abs <= 1e-5);
+        }
+    }
+
+    #[test]
+    fn vase_plate_uses_object_settings() {
+        let config = SlicerConfig {
+            spiral_vase: true,
+            ..Default::default()
+        };
+        let cube = || mandoline_stl::parse_stl::<DefaultMesh>(STL_CUBE.bytes).unwrap();
+        let object = |x: f32| PlateObject {
+            mesh: cube(),
+            transform: Transform {
+                offset: (x, 0.0),
+                ..Default::default()
+            },
+            config: SlicerConfig {
+                spiral_vase: true,
+                bottom_layers: config.bottom_layers + 2,
+                ..Default::default()
+            },
+        };
+        let single = slice_plate(vec![object(0.0)], &config).unwrap();
+        let double =
+            slice_plate(vec![object(0.0), object(40.0)], &SlicerConfig::default()).unwrap();
+
+        let layers = generate_plate_toolpaths(&single, &config).unwrap();
+
+        // The spiral starts above the object's own bottom layers.
+        let bottom_layers = single[0].config.bottom_layers;
+        assert!(layers[..bottom_layers]
+            .iter()
+            .all(|l| l.paths.iter().all(|p| p.z.is_none())));
+        assert!(layers[bottom_layers + 1].paths[0].z.is_some());
+        assert!(generate_plate_toolpaths(&double, &config).is_err());
+    }
+}
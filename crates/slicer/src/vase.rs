@@ -0,0 +1,283 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::contour::Polyline;
+use crate::region::Region;
+use crate::slicer::SlicedLayer;
+use crate::toolpath::{ExtrusionPath, FeatureType, ToolpathLayer};
+
+/// The spiral is split into moves no longer than this, in mm, so that
+/// blending between the outlines of consecutive layers follows the shape of
+/// both.
+const SPIRAL_SEGMENT_LENGTH: f32 = 1.0;
+
+/// The outline that the spiral follows on a layer with the area `region`.
+///
+/// This is the centre line of the external perimeter around the largest
+/// island, ignoring any holes, as a closed loop that ends on the point it
+/// started from.
+pub fn vase_outline(region: &Region, width: f32) -> Option<Polyline> {
+    let largest = |region: &Region| {
+        region
+            .islands()
+            .max_by(|a, b| a.area().total_cmp(&b.area()))
+    };
+    let island = largest(region)?.without_holes();
+    let outline = largest(&island.offset(-width / 2.0))?;
+    let path = outline.paths().into_iter().next()?;
+    let mut points = path.points().to_vec();
+    if points.len() < 3 {
+        return None;
+    }
+    points.push(points[0]);
+    Some(Polyline::from_points(points))
+}
+
+/// Generates a layer of a vase, with nothing but its outline as the
+/// external perimeter. `spiralize` later turns the outlines into a spiral.
+pub(crate) fn vase_layer(index: usize, layer: &SlicedLayer, width: f32) -> ToolpathLayer {
+    let paths = vase_outline(layer.region(), width)
+        .map(|outline| ExtrusionPath::new(FeatureType::ExternalPerimeter, outline))
+        .into_iter()
+        .collect();
+    ToolpathLayer {
+        index,
+        z: layer.z(),
+        height: layer.height(),
+        region: layer.region().clone(),
+        paths,
+    }
+}
+
+/// Returns the point on the closed loop `points` nearest to `target`, and the
+/// index of the segment it's on.
+fn nearest_point(points: &[Vector2<f32>], target: Vector2<f32>) -> (usize, Vector2<f32>) {
+    let mut best = (0, points[0]);
+    let mut best_distance = f32::INFINITY;
+    for (i, w) in points.windows(2).enumerate() {
+        let d = w[1] - w[0];
+        let t = if d.magnitude2() > 0.0 {
+            ((target - w[0]).dot(d) / d.magnitude2()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let p = w[0] + d * t;
+        let distance = (p - target).magnitude2();
+        if distance < best_distance {
+            best_distance = distance;
+            best = (i, p);
+        }
+    }
+    best
+}
+
+/// Rotates the closed loop `points` to start and end at the point nearest to
+/// `from`, splitting segments so no move is longer than
+/// `SPIRAL_SEGMENT_LENGTH`.
+fn start_loop_near(points: &[Vector2<f32>], from: Vector2<f32>) -> Vec<Vector2<f32>> {
+    let (segment, start) = nearest_point(points, from);
+    let n = points.len() - 1;
+    let corners = (1..=n).map(|i| points[(segment + i) % n]);
+    let mut result = vec![start];
+    let mut previous = start;
+    for point in corners.chain(std::iter::once(start)) {
+        let length = (point - previous).magnitude();
+        let steps = (length / SPIRAL_SEGMENT_LENGTH).ceil().max(1.0) as usize;
+        for step in 1..=steps {
+            result.push(previous + (point - previous) * (step as f32 / steps as f32));
+        }
+        previous = point;
+    }
+    result.dedup();
+    result
+}
+
+/// The distance along `points` from its start to each of its points.
+fn distances(points: &[Vector2<f32>]) -> Vec<f32> {
+    let mut total = 0.0;
+    std::iter::once(0.0)
+        .chain(points.windows(2).map(|w| {
+            total += (w[1] - w[0]).magnitude();
+            total
+        }))
+        .collect()
+}
+
+/// Returns the point `fraction` of the way along `points`, where `lengths`
+/// are the distances of each point from the start. `cursor` is the segment
+/// the last point was found on, since fractions only ever increase.
+fn point_along(
+    points: &[Vector2<f32>],
+    lengths: &[f32],
+    fraction: f32,
+    cursor: &mut usize,
+) -> Vector2<f32> {
+    let distance = fraction * lengths[lengths.len() - 1];
+    while *cursor + 2 < points.len() && lengths[*cursor + 1] < distance {
+        *cursor += 1;
+    }
+    let (a, b) = (points[*cursor], points[*cursor + 1]);
+    let span = lengths[*cursor + 1] - lengths[*cursor];
+    if span <= 0.0 {
+        return b;
+    }
+    let t = ((distance - lengths[*cursor]) / span).clamp(0.0, 1.0);
+    a + (b - a) * t
+}
+
+/// Turns the outlines of `layers[first..]`, from `vase_layer`, into a single
+/// continuous spiral.
+///
+/// Each loop starts where the one below finished and climbs steadily from
+/// the height of the layer below to its own height. Along the way, its
+/// points move gradually from the outline of the layer below to its own
+/// outline, so that where the outline changes between layers there's no
+/// step, and so no seam.
+pub fn spiralize(layers: &mut [ToolpathLayer], first: usize) {
+    let Some(below) = first.checked_sub(1).and_then(|i| layers.get(i)) else {
+        return;
+    };
+    let mut z = below.z;
+    let mut position = below.paths.last().and_then(|p| p.path.last());
+    let mut previous: Option<(Vec<Vector2<f32>>, Vec<f32>)> = None;
+    for layer in &mut layers[first..] {
+        let Some(outline) = layer
+            .paths
+            .iter()
+            .find(|p| p.feature == FeatureType::ExternalPerimeter)
+        else {
+            // The spiral can't carry on across an empty layer.
+            previous = None;
+            continue;
+        };
+        let outline_points = outline.path.points();
+        let from = position.unwrap_or(outline_points[0]);
+        let points = start_loop_near(outline_points, from);
+        let lengths = distances(&points);
+        let total = lengths[lengths.len() - 1];
+
+        let mut cursor = 0;
+        let (path, heights): (Vec<_>, Vec<_>) = points
+            .iter()
+            .zip(&lengths)
+            .map(|(&point, &length)| {
+                let fraction = if total > 0.0 { length / total } else { 1.0 };
+                let point = match &previous {
+                    Some((below, below_lengths)) => {
+                        let start = point_along(below, below_lengths, fraction, &mut cursor);
+                        start + (point - start) * fraction
+                    }
+                    None => point,
+                };
+                (point, z + (layer.z - z) * fraction)
+            })
+            .unzip();
+
+        position = path.last().copied();
+        z = layer.z;
+        layer.paths = vec![ExtrusionPath {
+            feature: FeatureType::ExternalPerimeter,
+            path: Polyline::from_points(path),
+            z: Some(heights),
+            overhang: false,
+        }];
+        previous = Some((points, lengths));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    fn v(x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x, y)
+    }
+
+    fn square_layer(index: usize, size: f32) -> ToolpathLayer {
+        let points = vec![
+            v(0.0, 0.0),
+            v(size, 0.0),
+            v(size, size),
+            v(0.0, size),
+            v(0.0, 0.0),
+        ];
+        ToolpathLayer {
+            index,
+            z: (index + 1) as f32 * 0.2,
+            height: 0.2,
+            region: Region::new(),
+            paths: vec![ExtrusionPath::new(
+                FeatureType::ExternalPerimeter,
+                Polyline::from_points(points),
+            )],
+        }
+    }
+
+    #[test]
+    fn outline_skips_holes() {
+        let outer = Region::circle(v(0.0, 0.0), 10.0, 64);
+        let ring = outer.difference(&Region::circle(v(0.0, 0.0), 5.0, 64));
+
+        let outline = vase_outline(&ring, 0.4).unwrap();
+
+        assert_eq!(outline.first(), outline.last());
+        assert!(outline
+            .points()
+            .iter()
+            .all(|p| p.magnitude() > 9.0 && p.magnitude() < 10.0));
+    }
+
+    #[test]
+    fn spiral_climbs_continuously() {
+        let mut layers = (0..4).map(|i| square_layer(i, 10.0)).collect::<Vec<_>>();
+
+        spiralize(&mut layers, 1);
+
+        let mut end = layers[0].paths[0].path.last().unwrap();
+        let mut z = layers[0].z;
+        for layer in &layers[1..] {
+            assert_eq!(layer.paths.len(), 1);
+            let path = &layer.paths[0];
+            let heights = path.z.as_ref().unwrap();
+            assert_eq!(heights.len(), path.path.points().len());
+            // Each loop picks up exactly where the last one stopped.
+            assert_eq!(path.path.first().unwrap(), end);
+            assert_float_eq!(heights[0], z, abs <= 1e-5);
+            assert_float_eq!(heights[heights.len() - 1], layer.z, abs <= 1e-5);
+            assert!(heights.windows(2).all(|w| w[1] > w[0]));
+            end = path.path.last().unwrap();
+            z = layer.z;
+        }
+    }
+
+    #[test]
+    fn spiral_blends_between_outlines() {
+        // A vase that gets wider by 1mm on every side each layer.
+        let mut layers = (0..3)
+            .map(|i| {
+                let mut layer = square_layer(i, 10.0 + 2.0 * i as f32);
+                let offset = v(i as f32, i as f32);
+                for p in layer.paths[0].path.points_vec() {
+                    *p -= offset;
+                }
+                layer
+            })
+            .collect::<Vec<_>>();
+
+        spiralize(&mut layers, 1);
+
+        // Halfway around the top loop, the nozzle is halfway between the
+        // outlines of the top two layers.
+        let path = &layers[2].paths[0].path;
+        let points = path.points();
+        let lengths = distances(points);
+        let total = lengths[lengths.len() - 1];
+        let middle = lengths.iter().position(|&l| l >= total / 2.0).unwrap();
+        let p = points[middle];
+        let inset = p.x.min(p.y).min(10.0 - p.x).min(10.0 - p.y);
+        assert_float_eq!(inset, -1.5, abs <= 0.1);
+        // The loop finishes on the outline of its own layer.
+        let end = path.last().unwrap();
+        assert!(end.x.min(end.y) <= -2.0 + 1e-4 || end.x.max(end.y) >= 12.0 - 1e-4);
+    }
+}
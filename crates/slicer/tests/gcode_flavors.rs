@@ -0,0 +1,114 @@
+//! Compares the G-code generated for each firmware flavor against known good
+//! output in `tests/golden`.
+//!
+//! To update the golden files after an intentional change to the output, run
+//! the tests with `UPDATE_GOLDEN=1` set and review the diff.
+
+use std::path::PathBuf;
+
+use cgmath::Vector2;
+use mandoline::{
+    generate_gcode, ClosedPath, ExtrusionPath, FeatureType, GcodeConfig, GcodeFlavor, Polyline,
+    Region, RetractionConfig, SlicerConfig, ToolpathLayer,
+};
+
+fn path(feature: FeatureType, points: &[(f32, f32)]) -> ExtrusionPath {
+    ExtrusionPath::new(
+        feature,
+        Polyline::from_points(points.iter().map(|&(x, y)| Vector2::new(x, y)).collect()),
+    )
+}
+
+fn square(x0: f32, y0: f32, size: f32) -> ClosedPath {
+    let mut path = ClosedPath::new();
+    path.add_point(x0, y0);
+    path.add_point(x0 + size, y0);
+    path.add_point(x0 + size, y0 + size);
+    path.add_point(x0, y0 + size);
+    path
+}
+
+/// Two layers of a 10mm square with a couple of infill lines and a
+/// perimeter, next to a 5mm square with just a perimeter. The travel from
+/// one square to the other follows a perimeter, so it's wiped.
+fn toolpaths() -> Vec<ToolpathLayer> {
+    let region = Region::from_paths(&[square(0.0, 0.0, 10.0), square(20.0, 0.0, 5.0)]);
+    (0..2)
+        .map(|index| ToolpathLayer {
+            index,
+            z: 0.2 * (index + 1) as f32,
+            height: 0.2,
+            region: region.clone(),
+            paths: vec![
+                path(FeatureType::SparseInfill, &[(2.0, 2.0), (8.0, 8.0)]),
+                path(FeatureType::SparseInfill, &[(2.0, 8.0), (8.0, 2.0)]),
+                path(
+                    FeatureType::ExternalPerimeter,
+                    &[
+                        (0.0, 0.0),
+                        (10.0, 0.0),
+                        (10.0, 10.0),
+                        (0.0, 10.0),
+                        (0.0, 0.0),
+                    ],
+                ),
+                path(
+                    FeatureType::ExternalPerimeter,
+                    &[
+                        (20.0, 0.0),
+                        (25.0, 0.0),
+                        (25.0, 5.0),
+                        (20.0, 5.0),
+                        (20.0, 0.0),
+                    ],
+                ),
+            ],
+        })
+        .collect()
+}
+
+fn check_golden(flavor: GcodeFlavor, name: &str) {
+    let config = SlicerConfig {
+        extrusion_width: 0.4,
+        gcode: GcodeConfig {
+            flavor,
+            print_acceleration: Some(1000.0),
+            travel_acceleration: Some(3000.0),
+            ..Default::default()
+        },
+        retraction: RetractionConfig {
+            z_hop: 0.4,
+            wipe_distance: 1.0,
+            use_firmware_retraction: flavor == GcodeFlavor::RepRapFirmware,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let gcode = generate_gcode(&toolpaths(), &config);
+    // The fixture is meant to cover wiping as well as retraction.
+    assert!(gcode.contains("; wipe"));
+
+    let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden, &gcode).unwrap();
+    }
+    let expected = std::fs::read_to_string(&golden).unwrap();
+    assert_eq!(gcode, expected, "output differs from {}", golden.display());
+}
+
+#[test]
+fn marlin() {
+    check_golden(GcodeFlavor::Marlin, "marlin.gcode");
+}
+
+#[test]
+fn klipper() {
+    check_golden(GcodeFlavor::Klipper, "klipper.gcode");
+}
+
+#[test]
+fn reprapfirmware() {
+    check_golden(GcodeFlavor::RepRapFirmware, "reprapfirmware.gcode");
+}
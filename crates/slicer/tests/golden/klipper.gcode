@@ -0,0 +1,96 @@
+; estimated printing time = 10s
+; estimated time for SparseInfill = 1s
+; estimated time for ExternalPerimeter = 8s
+; estimated time for travel = 2s
+; layer times [s] = min 5.1, max 5.3, average 5.2
+; filament used [mm] = 4.57
+; filament used [g] = 0.01
+; filament cost = 0
+M140 S60
+M104 S210
+M190 S60
+M109 S210
+G28 ; home all axes
+G1 Z5 F3000 ; lift nozzle
+G21 ; millimeter units
+G90 ; absolute coordinates
+M82 ; absolute extrusion
+G92 E0
+;LAYER_CHANGE
+;Z:0.2
+SET_PRINT_STATS_INFO CURRENT_LAYER=1
+M73 P0
+G1 Z0.2 F600
+G92 E0
+;TYPE:SparseInfill
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X2 Y2 F9000
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X8 Y8 E0.25194 F2252
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X2 Y8 F9000
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X8 Y2 E0.50388 F2252
+;TYPE:ExternalPerimeter
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X0 Y0 F9000
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X10 Y0 E0.80079 F938
+G1 X10 Y10 E1.0977
+G1 X0 Y10 E1.39462
+G1 X0 Y0 E1.69153
+G1 X1 Y0 E0.89153 F9000 ; wipe
+G1 Z0.6 F600
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X20 Y0 F9000
+G1 Z0.2 F600
+G1 E1.69153 F2100
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X25 Y0 E1.83999 F938
+G1 X25 Y5 E1.98844
+G1 X20 Y5 E2.1369
+G1 X20 Y0 E2.28536
+;LAYER_CHANGE
+;Z:0.4
+SET_PRINT_STATS_INFO CURRENT_LAYER=2
+M73 P50
+G1 Z0.4 F600
+G92 E0
+;TYPE:SparseInfill
+G1 E-0.8 F2100
+G1 Z0.8 F600
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X2 Y2 F9000
+G1 Z0.4 F600
+M106 S255
+G1 E0 F2100
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X8 Y8 E0.25194 F2339
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X2 Y8 F9000
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X8 Y2 E0.50388 F2339
+;TYPE:ExternalPerimeter
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X0 Y0 F9000
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X10 Y0 E0.80079 F975
+G1 X10 Y10 E1.0977
+G1 X0 Y10 E1.39462
+G1 X0 Y0 E1.69153
+G1 X1 Y0 E0.89153 F9000 ; wipe
+G1 Z0.8 F600
+SET_VELOCITY_LIMIT ACCEL=3000
+G0 X20 Y0 F9000
+G1 Z0.4 F600
+G1 E1.69153 F2100
+SET_VELOCITY_LIMIT ACCEL=1000
+G1 X25 Y0 E1.83999 F975
+G1 X25 Y5 E1.98844
+G1 X20 Y5 E2.1369
+G1 X20 Y0 E2.28536
+M107
+M73 P100
+M104 S0 ; turn off hotend
+M140 S0 ; turn off bed
+M84 ; disable motors
@@ -0,0 +1,93 @@
+; estimated printing time = 11s
+; estimated time for SparseInfill = 1s
+; estimated time for ExternalPerimeter = 8s
+; estimated time for travel = 2s
+; layer times [s] = min 5.1, max 5.4, average 5.3
+; filament used [mm] = 4.57
+; filament used [g] = 0.01
+; filament cost = 0
+M140 S60
+G10 P0 S210 R210
+T0
+M190 S60
+M116 P0
+G28 ; home all axes
+G1 Z5 F3000 ; lift nozzle
+G21 ; millimeter units
+G90 ; absolute coordinates
+M82 ; absolute extrusion
+G92 E0
+M207 S0.8 F2100
+;LAYER:0
+G1 Z0.2 F600
+G92 E0
+;TYPE:SparseInfill
+M204 P3000 T3000
+G0 X2 Y2 F9000
+M204 P1000 T1000
+G1 X8 Y8 E0.25194 F2252
+M204 P3000 T3000
+G0 X2 Y8 F9000
+M204 P1000 T1000
+G1 X8 Y2 E0.50388 F2252
+;TYPE:ExternalPerimeter
+M204 P3000 T3000
+G0 X0 Y0 F9000
+M204 P1000 T1000
+G1 X10 Y0 E0.80079 F938
+G1 X10 Y10 E1.0977
+G1 X0 Y10 E1.39462
+G1 X0 Y0 E1.69153
+G1 X1 Y0 F9000 ; wipe
+G10 ; retract
+G1 Z0.6 F600
+M204 P3000 T3000
+G0 X20 Y0 F9000
+G1 Z0.2 F600
+G11 ; unretract
+M204 P1000 T1000
+G1 X25 Y0 E1.83999 F938
+G1 X25 Y5 E1.98844
+G1 X20 Y5 E2.1369
+G1 X20 Y0 E2.28536
+;LAYER:1
+G1 Z0.4 F600
+G92 E0
+;TYPE:SparseInfill
+G10 ; retract
+G1 Z0.8
+M204 P3000 T3000
+G0 X2 Y2 F9000
+G1 Z0.4 F600
+M106 S255
+G11 ; unretract
+M204 P1000 T1000
+G1 X8 Y8 E0.25194 F2339
+M204 P3000 T3000
+G0 X2 Y8 F9000
+M204 P1000 T1000
+G1 X8 Y2 E0.50388 F2339
+;TYPE:ExternalPerimeter
+M204 P3000 T3000
+G0 X0 Y0 F9000
+M204 P1000 T1000
+G1 X10 Y0 E0.80079 F975
+G1 X10 Y10 E1.0977
+G1 X0 Y10 E1.39462
+G1 X0 Y0 E1.69153
+G1 X1 Y0 F9000 ; wipe
+G10 ; retract
+G1 Z0.8 F600
+M204 P3000 T3000
+G0 X20 Y0 F9000
+G1 Z0.4 F600
+G11 ; unretract
+M204 P1000 T1000
+G1 X25 Y0 E1.83999 F975
+G1 X25 Y5 E1.98844
+G1 X20 Y5 E2.1369
+G1 X20 Y0 E2.28536
+M107
+M104 S0 ; turn off hotend
+M140 S0 ; turn off bed
+M84 ; disable motors